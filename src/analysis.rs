@@ -0,0 +1,362 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::panic::Location;
+use fxhash::FxHashMap;
+use crate::array::DArray;
+use crate::computation::{Computation, ComputationType, Cost, F64_SIZE};
+
+type Map<K, V> = FxHashMap<K, V>;
+
+/// The depth up to which the sources of an offending computation are shown in error reports.
+const REPORT_DEPTH: usize = 4;
+
+/// An error raised when a computation is created with sources of incompatible lengths.
+/// Holds a report of the offending expression, to make the error easy to locate in large graphs.
+#[derive(Clone, Debug)]
+pub struct ShapeError {
+    /// The name of the offending computation.
+    pub comp: String,
+    /// The location in the code which created the offending computation.
+    /// Only tracked when the `debug-provenance` feature is enabled.
+    pub location: Option<&'static Location<'static>>,
+    /// A description of the incompatibility.
+    pub message: String,
+    /// The offending expression, rendered as an indented tree of computations and their lengths.
+    pub expression: String,
+}
+
+impl ShapeError {
+    /// Creates an error report for a computation whose validation failed with the given message.
+    pub(crate) fn new(comp: &dyn Computation, location: Option<&'static Location<'static>>, message: String) -> ShapeError {
+        let sources = comp.sources();
+        let mut renderer = TreeRenderer::new(&sources, REPORT_DEPTH);
+        renderer.out.push_str(&format!("{}{}\n", comp.name(), render_location(location)));
+        for src in sources.iter() {
+            renderer.render(src, 1);
+        }
+        ShapeError {comp: comp.name(), location, message, expression: renderer.out}
+    }
+}
+
+impl Display for ShapeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid computation {}{}: {}\nExpression:\n{}",
+               self.comp, render_location(self.location), self.message, self.expression)
+    }
+}
+
+impl Error for ShapeError {}
+
+/// Validates a computation before it is added to the graph.
+pub(crate) fn validate(comp: &dyn Computation, location: Option<&'static Location<'static>>) -> Result<(), ShapeError> {
+    comp.validate().map_err(|message| ShapeError::new(comp, location, message))
+}
+
+/// Renders the location which created a computation, if it is known.
+fn render_location(location: Option<&'static Location<'static>>) -> String {
+    match location {
+        Some(location) => format!(" created at {}", location),
+        None => String::new(),
+    }
+}
+
+/// Renders computation graphs as indented trees, with a line per computation.
+/// Arrays used by several computations are expanded once, and are referenced by a label afterwards.
+struct TreeRenderer {
+    /// The maximal depth of the rendered arrays. Deeper arrays are elided.
+    max_depth: usize,
+    /// The number of computations using each array in the graph, counting the roots once.
+    parent_counts: Map<DArray, usize>,
+    /// The labels given to the arrays with several parents which were already rendered.
+    labels: Map<DArray, usize>,
+    /// The rendered tree.
+    out: String,
+}
+
+impl TreeRenderer {
+    /// Initializes a renderer for the graph spanned by the given roots.
+    fn new(roots: &[DArray], max_depth: usize) -> TreeRenderer {
+        let mut parent_counts: Map<DArray, usize> = Map::default();
+        let mut queue = vec![];
+        for root in roots {
+            if !parent_counts.contains_key(root) {
+                queue.push(root.clone());
+            }
+            *parent_counts.entry(root.clone()).or_insert(0) += 1;
+        }
+
+        let mut idx = 0;
+        while idx < queue.len() {
+            for src in queue[idx].comp().sources() {
+                if !parent_counts.contains_key(&src) {
+                    queue.push(src.clone());
+                }
+                *parent_counts.entry(src).or_insert(0) += 1;
+            }
+            idx += 1;
+        }
+
+        TreeRenderer {max_depth, parent_counts, labels: Map::default(), out: String::new()}
+    }
+
+    /// Renders an array and its sources at the given depth.
+    fn render(&mut self, array: &DArray, depth: usize) {
+        let indent = "  ".repeat(depth);
+        let description = format!("{} [{}]", array.comp().name(), array.len());
+
+        if let Some(label) = self.labels.get(array) {
+            self.out.push_str(&format!("{}{} -> #{}\n", indent, description, label));
+            return;
+        }
+        if depth > self.max_depth {
+            self.out.push_str(&format!("{}...\n", indent));
+            return;
+        }
+
+        let mut label = String::new();
+        if self.parent_counts[array] > 1 {
+            let new_label = self.labels.len() + 1;
+            self.labels.insert(array.clone(), new_label);
+            label = format!(" #{}", new_label);
+        }
+        self.out.push_str(&format!("{}{}{}{}\n", indent, description, label, render_location(array.creation_site())));
+
+        for src in array.comp().sources() {
+            self.render(&src, depth + 1);
+        }
+    }
+}
+
+impl DArray {
+    /// Renders the computation graph of the array as an indented tree, showing the computations and the
+    /// lengths of the arrays up to the given depth.
+    /// Arrays used by several computations are labeled as `#n` when first shown, and are referenced by
+    /// their label afterwards.
+    pub fn tree_string(&self, max_depth: usize) -> String {
+        let mut renderer = TreeRenderer::new(std::slice::from_ref(self), max_depth);
+        renderer.render(self, 0);
+        renderer.out
+    }
+
+    /// Prints the computation graph of the array as an indented tree. See [`DArray::tree_string`].
+    pub fn print_tree(&self, max_depth: usize) {
+        print!("{}", self.tree_string(max_depth));
+    }
+}
+
+/// An estimate of the resources used to evaluate a computation graph.
+pub struct CostReport {
+    /// The estimated cost of each array in the graph, in topological order.
+    pub nodes: Vec<(DArray, Cost)>,
+    /// The total estimated cost of each type of computation.
+    pub per_type: Map<ComputationType, Cost>,
+    /// The total estimated cost of the graph.
+    pub total: Cost,
+}
+
+impl Display for CostReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut per_type: Vec<_> = self.per_type.iter().collect();
+        per_type.sort_by_key(|(_, cost)| std::cmp::Reverse(cost.flops));
+        for (comp_type, cost) in per_type {
+            writeln!(f, "{:?}: {} flops, {} bytes read, {} bytes written", comp_type, cost.flops, cost.bytes_read, cost.bytes_written)?;
+        }
+        write!(f, "Total: {} flops, {} bytes read, {} bytes written", self.total.flops, self.total.bytes_read, self.total.bytes_written)
+    }
+}
+
+impl DArray {
+    /// Estimates the floating point operations and the memory traffic needed to evaluate every array
+    /// in the computation graph, including arrays which are already evaluated.
+    pub fn estimate_cost(&self) -> CostReport {
+        let mut report = CostReport {nodes: vec![], per_type: Map::default(), total: Cost::default()};
+        for array in self.topological_sort() {
+            let cost = array.comp().cost();
+            *report.per_type.entry(array.comp().get_type()).or_default() += cost;
+            report.total += cost;
+            report.nodes.push((array, cost));
+        }
+        report
+    }
+
+    /// Estimates the peak number of bytes allocated when evaluating the array.
+    /// Simulates the allocation planning of [`DArray::data`], in which arrays applied on zero on the buffers
+    /// of the arrays using them are not allocated. Evaluated arrays keep their buffers, so the peak is the
+    /// total size of the buffers allocated by the evaluation. Returns zero if the array is already evaluated.
+    pub fn estimate_peak_memory(&self) -> usize {
+        if self.is_initialized() {
+            return 0;
+        }
+        let plan = self.plan_evaluation();
+        plan.topo.iter()
+            .filter(|node| *node == self || plan.allocated.contains(node))
+            .map(|node| node.len() * F64_SIZE)
+            .sum()
+    }
+}
+
+/// A summary of a computation graph, listing its computations and the memory held by their results.
+pub struct GraphSummary {
+    /// The name, the length and the bytes held by each array in the graph, in evaluation order.
+    /// Arrays which are fused into the arrays using them hold no bytes.
+    pub nodes: Vec<(String, usize, usize)>,
+    /// The number of arrays with no sources, which hold the inputs and parameters of the graph.
+    pub leaves: usize,
+    /// The total number of elements in the arrays with no sources.
+    pub parameters: usize,
+    /// The number of bytes held by the graph once the array is evaluated,
+    /// counting the arrays which are already evaluated and the arrays the evaluation allocates.
+    pub memory: usize,
+}
+
+impl Display for GraphSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self.nodes.iter().map(|(name, ..)| name.len()).max().unwrap_or(0).max("Computation".len());
+        writeln!(f, "{:<width$} {:>10} {:>12}", "Computation", "Length", "Bytes", width = width)?;
+        for (name, len, bytes) in self.nodes.iter() {
+            writeln!(f, "{:<width$} {:>10} {:>12}", name, len, bytes, width = width)?;
+        }
+        writeln!(f, "Parameters: {} in {} arrays", self.parameters, self.leaves)?;
+        write!(f, "Total memory: {} bytes", self.memory)
+    }
+}
+
+impl DArray {
+    /// Summarizes the computation graph of the array in a single walk over the graph.
+    /// The memory is counted for the leaves and for the arrays the planner allocates when evaluating the array.
+    pub fn summary(&self) -> GraphSummary {
+        let plan = self.plan_evaluation();
+        let mut summary = GraphSummary {nodes: vec![], leaves: 0, parameters: 0, memory: 0};
+        for array in self.topological_sort().iter().rev() {
+            if array.comp().sources().is_empty() {
+                summary.leaves += 1;
+                summary.parameters += array.len();
+            }
+            // Leaves hold their data whether the planner copies it or reads it through.
+            let allocated = array.is_initialized() || array.comp().sources().is_empty() || array == self
+                || plan.allocated.contains(array);
+            let bytes = if allocated { array.len() * F64_SIZE } else { 0 };
+            summary.memory += bytes;
+            summary.nodes.push((array.comp().name(), array.len(), bytes));
+        }
+        summary
+    }
+
+    /// Prints the summary of the computation graph of the array. See [`DArray::summary`].
+    pub fn print_summary(&self) {
+        println!("{}", self.summary());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DArray;
+    use crate::computation::{ComputationType, Cost};
+    use crate::index_functions::ExpandComp;
+
+    #[test]
+    fn test_report() {
+        let array = DArray::from(vec![1., 2., 3.]).sin();
+        let err = DArray::try_from_comp(ExpandComp::new(array, 5)).err().unwrap();
+
+        assert_eq!(err.comp, "ExpandComp");
+        #[cfg(not(feature = "debug-provenance"))]
+        assert_eq!(err.expression, "ExpandComp\n  UnaryComp<SinFunc> [3]\n    FromDataComp [3]\n");
+    }
+
+    #[test]
+    #[cfg(not(feature = "debug-provenance"))]
+    fn test_tree() {
+        let array = DArray::from(vec![1., 2., 3.]);
+        let sin = array.sin();
+        let res = (&sin * &sin).sum();
+
+        assert_eq!(res.tree_string(5), "SumComp [1]\n  MulComp [3]\n    UnaryComp<SinFunc> [3] #1\n      FromDataComp [3]\n    UnaryComp<SinFunc> [3] -> #1\n");
+        assert_eq!(res.tree_string(1), "SumComp [1]\n  MulComp [3]\n    ...\n    ...\n");
+    }
+
+    #[test]
+    fn test_cost() {
+        let array_1 = DArray::from(vec![1., 2., 3.]);
+        let array_2 = DArray::from(vec![4., 5., 6.]);
+        let res = ((&array_1 + &array_2) * &array_1).sum();
+        let report = res.estimate_cost();
+
+        assert_eq!(report.nodes.len(), 5);
+        assert_eq!(report.per_type[&ComputationType::Add], Cost {flops: 3, bytes_read: 48, bytes_written: 24});
+        assert_eq!(report.per_type[&ComputationType::Binary], Cost {flops: 3, bytes_read: 48, bytes_written: 24});
+        assert_eq!(report.per_type[&ComputationType::Linear], Cost {flops: 3, bytes_read: 24, bytes_written: 8});
+        assert_eq!(report.per_type[&ComputationType::Other], Cost {flops: 0, bytes_read: 48, bytes_written: 48});
+        assert_eq!(report.total.flops, 9);
+    }
+
+    #[test]
+    fn test_peak_memory() {
+        let array_1 = DArray::from(vec![1., 2., 3.]);
+        let array_2 = DArray::from(vec![4., 5., 6.]);
+
+        // The sources of the addition are applied on its buffer, and are not allocated.
+        let add = &array_1 + &array_2;
+        assert_eq!(add.estimate_peak_memory(), 24);
+        // The sum reads through the addition and its sources, and only allocates its result.
+        let sum = (&array_1 + &array_2).sum();
+        assert_eq!(sum.estimate_peak_memory(), 8);
+        // The sum allocates sources which aren't additions or arrays created from data.
+        let sum = (&array_1 * &array_2).sum();
+        assert_eq!(sum.estimate_peak_memory(), 56);
+
+        add.data();
+        assert_eq!(add.estimate_peak_memory(), 0);
+        assert!(!array_1.is_initialized());
+        assert!(!array_2.is_initialized());
+    }
+
+    #[test]
+    fn test_summary() {
+        let weights = DArray::from(vec![1., 2., 3.]);
+        let input = DArray::from(vec![4., 5., 6.]);
+        let res = (&weights.sin() + &input).exp().sum();
+        let summary = res.summary();
+
+        assert_eq!(summary.nodes.last().unwrap(), &("SumComp".to_string(), 1, 8));
+        assert_eq!(summary.nodes.len(), 6);
+        assert_eq!(summary.leaves, 2);
+        assert_eq!(summary.parameters, 6);
+        // The sine is applied on the buffer of the addition, so it holds no memory of its own.
+        let fused: Vec<&str> = summary.nodes.iter().filter(|(.., bytes)| *bytes == 0).map(|(name, ..)| name.as_str()).collect();
+        assert_eq!(fused, ["UnaryComp<SinFunc>"]);
+        assert_eq!(summary.memory, 104);
+        assert!(summary.to_string().ends_with("Parameters: 6 in 2 arrays\nTotal memory: 104 bytes"));
+    }
+
+    #[test]
+    fn test_valid() {
+        let array = DArray::from(2.);
+        assert!(DArray::try_from_comp(ExpandComp::new(array, 5)).is_ok());
+    }
+
+    /// Tests that the locations creating the arrays are tracked and reported.
+    #[cfg(feature = "debug-provenance")]
+    #[test]
+    fn test_provenance() {
+        let line = line!() + 1;
+        let array = DArray::from(vec![1., 2., 3.]);
+        let sin = array.sin();
+        let err = DArray::try_from_comp(ExpandComp::new(sin.clone(), 5)).err().unwrap();
+
+        assert_eq!(array.creation_site().unwrap().file(), file!());
+        assert_eq!(array.creation_site().unwrap().line(), line);
+        assert_eq!(sin.creation_site().unwrap().line(), line + 1);
+        assert_eq!(err.location.unwrap().line(), line + 2);
+        assert!(err.expression.contains(&format!("UnaryComp<SinFunc> [3] created at {}:{}", file!(), line + 1)));
+    }
+
+    /// Tests that invalid operators fail when the graph is built, reporting the offending computation.
+    #[test]
+    #[should_panic(expected = "Invalid computation MulComp")]
+    fn test_panic() {
+        let array_1 = DArray::from(vec![1., 2., 3.]);
+        let array_2 = DArray::from(vec![1., 2.]);
+        let _array_3 = &array_1 * &array_2;
+    }
+}
@@ -1,78 +1,177 @@
-use std::cell::UnsafeCell;
-use crate::computation::{Computation, ComputationType, FromDataComp};
+use crate::computation::{Computation, ComputationType, FromDataComp, NodeComp};
 use std::hash::{Hash, Hasher};
-use std::ops::Deref;
-use std::sync::{Arc, RwLock};
+use std::ops::{Deref, Index};
+use std::panic::Location;
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use fxhash::{FxHashMap, FxHashSet};
 use itertools::izip;
 use rand::Rng;
 use crate::unary_functions::{DerivableOp, UnaryComp};
+use crate::analysis::{validate, ShapeError};
+use crate::backend::{Backend, CPU};
+use crate::index_functions::IndexComp;
 
 type Map<K, V> = FxHashMap<K, V>;
 type IdType = usize;
 
+/// The maximal length of arrays whose data is stored inline, without allocating a buffer.
+const INLINE_LEN: usize = 4;
+
+/// The length of arrays whose length depends on the data of their sources, until it is calculated.
+const DEFERRED_LEN: usize = usize::MAX;
+
+/// The maximal depth of the chains of arrays calculated recursively while evaluating an allocated array.
+const MAX_APPLY_DEPTH: usize = 256;
+
+/// The data of an array. The data of small arrays is stored inline, since scalars are very common
+/// and allocating buffers for them is relatively expensive.
+#[derive(Clone)]
+pub(crate) enum Storage {
+    Inline([f64; INLINE_LEN], usize),
+    Heap(Vec<f64>),
+}
+
+impl Storage {
+    /// Creates a zero-initialized storage of the given length, allocating buffers with the given backend.
+    pub(crate) fn zeros(len: usize, backend: &dyn Backend) -> Storage {
+        if len <= INLINE_LEN {
+            Storage::Inline([0.; INLINE_LEN], len)
+        } else {
+            Storage::Heap(backend.zeros(len))
+        }
+    }
+
+    /// Creates a storage holding a copy of the given data.
+    pub(crate) fn from_slice(data: &[f64], backend: &dyn Backend) -> Storage {
+        let mut storage = Storage::zeros(data.len(), backend);
+        storage.as_mut_slice().copy_from_slice(data);
+        storage
+    }
+
+    pub(crate) fn as_slice(&self) -> &[f64] {
+        match self {
+            Storage::Inline(data, len) => &data[..*len],
+            Storage::Heap(data) => data,
+        }
+    }
+
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [f64] {
+        match self {
+            Storage::Inline(data, len) => &mut data[..*len],
+            Storage::Heap(data) => data,
+        }
+    }
+}
+
 /// A wrapper over a float array, which dynamically creates a computation graph.
 /// The computation graph can then be used to automatically calculate derivatives of complex functions
 /// using backward propagation.
 struct DArrayInternal {
     /// The data stored by the array.
-    data: RwLock<UnsafeCell<Option<Vec<f64>>>>,
+    data: OnceLock<Storage>,
     /// The computation used to calculate the array. Tracks the computation graph.
-    comp: Box<dyn Computation>,
-    /// The length of the array held by the DArray.
-    length: usize,
+    comp: NodeComp,
+    /// The length of the array held by the DArray, or `DEFERRED_LEN` if the length depends on the data
+    /// of the sources and wasn't needed yet.
+    length: AtomicUsize,
     /// An ID, used to easily sort the arrays by order of creation.
     id: IdType,
+    /// The backend allocating the array's buffer and running the kernels of its computation.
+    backend: &'static dyn Backend,
+    /// The location in the code which created the array.
+    #[cfg(feature = "debug-provenance")]
+    location: &'static Location<'static>,
 }
 
 impl DArrayInternal {
-    /// Returns if the data in the DArrayInternal has been initialized yet.
-    fn is_initialized(&self) -> bool {
-        // Initializing.
-        unsafe {
-            self.data.read()
-                .unwrap()
-                .get()
-                .as_ref()
-                .unwrap()
-                .is_none()
+    /// Gets the data of the internal array, calculating it if it wasn't calculated yet.
+    fn data(&self) -> &[f64] {
+        // The cell lets a single thread initialize the data, and blocks other threads until it is done.
+        // Since the DArrays form a DAG, there is a partial ordering on the cells.
+        // One of the cells will always be minimal, and will be initialized successfully.
+        self.data.get_or_init(|| {
+            let mut data = Storage::zeros(self.len(), self.backend);
+            self.comp.apply_on_zero(data.as_mut_slice());
+            data
+        }).as_slice()
+    }
+    /// Returns the length of the array, calculating it if it was deferred.
+    /// Threads calculating a deferred length concurrently calculate the same length.
+    fn len(&self) -> usize {
+        let length = self.length.load(Ordering::Acquire);
+        if length != DEFERRED_LEN {
+            return length;
         }
+        let length = self.comp.len();
+        self.length.store(length, Ordering::Release);
+        length
+    }
+    /// Checks if the data in the DArrayInternal is initialized.
+    fn is_init(&self) -> bool {
+        self.data.get().is_some()
     }
 
-    /// Gets the data of the internal array.
-    fn data(&self) -> &Vec<f64> {
-        // Initializing.
-        unsafe {
-            if self.data.read()
-                .unwrap()
-                .get()
-                .as_ref()
-                .unwrap()
-                .is_none() {
-                // Before a thread is allowed to modify the data, it must first obtain the lock.
-                // Since the DArrays form a DAG, there is a partial ordering on the mutexes.
-                // One of the mutexes will always be minimal, and will be acquired successfully.
-                let mut guard = self.data.write().unwrap();
-                let data = guard.get_mut();
-                if data.is_none() {
-                    *data = Some(vec![0.; self.comp.len()]);
-                    self.comp.apply_on_zero(data.as_mut().unwrap());
-                }
-            }
+    /// Replaces the computation of the array with an empty one, returning the sources of the computation.
+    /// The returned arrays aren't referenced by the computation anymore.
+    fn take_sources(&mut self) -> Vec<DArray> {
+        let empty = NodeComp::FromData(FromDataComp {data: Storage::Inline([0.; INLINE_LEN], 0)});
+        std::mem::replace(&mut self.comp, empty).sources()
+    }
+}
 
-            self.data.read()
-                .unwrap()
-                .get()
-                .as_ref()
-                .unwrap()
-                .as_ref()
-                .unwrap()
+impl Drop for DArrayInternal {
+    /// Drops the arrays only used by the array iteratively, since dropping them recursively
+    /// would overflow the stack for deep graphs.
+    fn drop(&mut self) {
+        let mut stack = self.take_sources();
+        while let Some(array) = stack.pop() {
+            if let Ok(mut internal) = Arc::try_unwrap(array.internal) {
+                stack.extend(internal.take_sources());
+            }
         }
     }
-    /// Checks if the data in the DArrayInternal is initialized.
-    fn is_init(&self) -> bool {
-        unsafe {
-            self.data.read().unwrap().get().as_ref().unwrap().is_some()
+}
+
+/// A plan for the evaluation of an array.
+pub(crate) struct EvaluationPlan {
+    /// The uninitialized arrays in the graph, in topological order, starting from the evaluated array.
+    pub(crate) topo: Vec<DArray>,
+    /// The arrays allocated during the evaluation. The arrays not in the set are applied on zero
+    /// on the buffers of the arrays using them.
+    pub(crate) allocated: FxHashSet<DArray>,
+}
+
+/// The strategy used to calculate a Jacobian with backward passes.
+///
+/// Both strategies are reverse mode. The crate doesn't propagate tangents, so the columns of the Jacobian
+/// are calculated by deriving the graph of a backward pass again, which costs more than a forward mode pass would.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum DiffMode {
+    /// Calculates a column of the Jacobian for every element of the inputs,
+    /// using a backward pass over the graph of a vector-Jacobian product.
+    DoubleBackward,
+    /// Calculates a row of the Jacobian for every element of the output.
+    Reverse,
+}
+
+impl DiffMode {
+    /// The cost of a backward pass calculating a column of the Jacobian, relative to a backward pass
+    /// calculating a row. The graph of a vector-Jacobian product contains both the original graph
+    /// and the derivatives of its computations, so deriving it visits about twice as many computations.
+    const COLUMN_COST: usize = 2;
+
+    /// Selects the mode with the lower estimated cost, given the length of the output
+    /// and the total length of the inputs.
+    /// The double backward mode pays for a backward pass building the vector-Jacobian product,
+    /// and for a pass over the doubled graph for every column.
+    /// Combining the modes is never cheaper, since a single reverse pass calculates
+    /// the rows of the Jacobians of all inputs.
+    pub fn select(output_len: usize, input_len: usize) -> DiffMode {
+        if 1 + DiffMode::COLUMN_COST * input_len < output_len {
+            DiffMode::DoubleBackward
+        } else {
+            DiffMode::Reverse
         }
     }
 }
@@ -97,34 +196,145 @@ impl DArray {
     }
 
     /// Initializes an array from a slice of floats.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn from_data(data: &[f64]) -> DArray {
-        DArray::from_comp(FromDataComp {data: data.to_vec()})
+        DArray::from_data_on(data, &CPU)
+    }
+
+    /// Initializes an array from a slice of floats, evaluated by the given backend.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn from_data_on(data: &[f64], backend: &'static dyn Backend) -> DArray {
+        let comp = FromDataComp {data: Storage::from_slice(data, backend)};
+        match DArray::try_from_comp_on(comp, backend) {
+            Ok(array) => array,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Initializes an array from a vector of floats, selecting the backend used by the graphs computed from it.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn with_backend(data: Vec<f64>, backend: &'static dyn Backend) -> DArray {
+        DArray::from_data_on(data.as_slice(), backend)
     }
 
     /// Initializes an array from a slice of floats and the computation used to calculate it.
+    /// Panics with a report of the offending expression if the computation is invalid.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn from_comp(
         comp: impl Computation + Clone,
     ) -> DArray {
-        DArray::new(DArrayInternal {
-            data: RwLock::new(UnsafeCell::new(None)),
-            length: comp.len(),
-            comp: Box::new(comp),
+        match DArray::try_from_comp(comp) {
+            Ok(array) => array,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Initializes an array from the computation used to calculate it,
+    /// returning an error if the lengths of the computation's sources are incompatible.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn try_from_comp(
+        comp: impl Computation + Clone,
+    ) -> Result<DArray, ShapeError> {
+        let backend = comp.sources().first().map_or(&CPU as &'static dyn Backend, |src| src.backend());
+        DArray::try_from_comp_on(comp, backend)
+    }
+
+    /// Initializes an array from the computation used to calculate it, evaluated by the given backend.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn try_from_comp_on(
+        comp: impl Computation + Clone,
+        backend: &'static dyn Backend,
+    ) -> Result<DArray, ShapeError> {
+        validate(&comp, caller_location())?;
+        Ok(DArray::new(DArrayInternal {
+            data: OnceLock::new(),
+            length: AtomicUsize::new(if comp.deferred_len() { DEFERRED_LEN } else { comp.len() }),
+            comp: NodeComp::new(comp),
             id: rand::thread_rng().gen::<IdType>(),
-        })
+            backend,
+            #[cfg(feature = "debug-provenance")]
+            location: Location::caller(),
+        }))
+    }
+
+    /// Returns the location in the code which created the array.
+    /// Locations are only tracked when the `debug-provenance` feature is enabled, and are `None` otherwise.
+    pub fn creation_site(&self) -> Option<&'static Location<'static>> {
+        #[cfg(feature = "debug-provenance")]
+        return Some(self.internal.location);
+        #[cfg(not(feature = "debug-provenance"))]
+        None
+    }
+
+    /// Returns the backend allocating the array's buffer and running the kernels of its computation.
+    pub fn backend(&self) -> &'static dyn Backend {
+        self.internal.backend
     }
 
     /// Returns the length of the array held by the array.
     pub fn len(&self) -> usize {
-        self.internal.length
+        self.internal.len()
     }
 
     /// Returns a reference to the array's data.
-    pub fn data(&self) -> &Vec<f64> {
+    pub fn data(&self) -> &[f64] {
         // If the node is already initialized, we return the data and require no further computations.
         if self.is_initialized() {
             return self.internal.data();
         }
 
+        let plan = self.plan_evaluation();
+        for node in plan.topo.iter().rev() {
+            if plan.allocated.contains(node) {
+                node.internal.data();
+            }
+        }
+
+        self.internal.data()
+    }
+
+    /// Returns the single value of a scalar array, evaluating it if needed.
+    pub fn item(&self) -> f64 {
+        assert!(self.is_scalar(), "Called item on an array of length {}.", self.len());
+        self.data()[0]
+    }
+
+    /// Returns the element of the array at the given index, evaluating the array if needed.
+    pub fn get(&self, idx: usize) -> f64 {
+        assert!(idx < self.len(), "Index {} is out of bounds for an array of length {}.", idx, self.len());
+        self.data()[idx]
+    }
+
+    /// Calculates the data of the array, without planning the evaluation of its sources.
+    /// Used by executors which evaluate the arrays of an evaluation plan themselves.
+    pub(crate) fn evaluate_planned(&self) {
+        self.internal.data();
+    }
+
+    /// Evaluates several arrays together, planning a single evaluation of the graph they share.
+    /// Cheaper than calling [`DArray::data`] on every array, which plans the evaluation of each
+    /// array separately and can't reuse the arrays used by several of them.
+    /// Returns the data of every array.
+    pub fn eval_many<'t>(targets: &[&'t DArray]) -> Vec<&'t [f64]> {
+        let roots: Vec<DArray> = targets.iter().map(|&target| target.clone()).collect();
+        let plan = DArray::plan_evaluation_of(&roots);
+        for node in plan.topo.iter().rev() {
+            if plan.allocated.contains(node) {
+                node.internal.data();
+            }
+        }
+
+        targets.iter().map(|target| target.internal.data()).collect()
+    }
+
+    /// Plans the evaluation of the array, selecting the uninitialized arrays in the graph which are allocated.
+    pub(crate) fn plan_evaluation(&self) -> EvaluationPlan {
+        DArray::plan_evaluation_of(std::slice::from_ref(self))
+    }
+
+    /// Plans the evaluation of several arrays in a single traversal of their graph.
+    /// Evaluated arrays used by other evaluated arrays are allocated, so they are evaluated once.
+    pub(crate) fn plan_evaluation_of(roots: &[DArray]) -> EvaluationPlan {
         // The function selects a subset of the parent nodes of the given node, and calls `.data()` on them.
         // This reduces the number of recursive calls to the function in the internal .data() .
         // However, calling the function interferes with the allocation-reducing mechanism, so it should be minimized.
@@ -132,15 +342,22 @@ impl DArray {
         // * Unary, when called not on zero.
         // * Binary. When called normally allocate twice, when called on zero allocate once.
         // * Other. Perform no optimization, and always allocate all their child nodes.
+        // * Linear. Read their child node, but read through pointwise additions and arrays created from data.
         // Additions never allocate, but may propagate the call on zero.
         //
         // In addition, nodes with two or more parents should always be evaluated, to prevent evaluating them twice.
 
         // Counting the number of parents of each node.
         let mut parent_count = FxHashMap::default();
-        parent_count.insert(self.clone(), 0);
+        let mut queue = vec![];
+        for root in roots {
+            if !parent_count.contains_key(root) {
+                parent_count.insert(root.clone(), 0);
+                queue.push(root.clone());
+            }
+        }
+        let root_count = queue.len();
 
-        let mut queue = vec![self.clone()];
         let mut idx = 0;
         while idx < queue.len() {
             let node = &queue[idx];
@@ -160,10 +377,13 @@ impl DArray {
 
         // Initializing is_allocated with all nodes with more than one parent.
         let mut is_allocated: FxHashSet<DArray> = parent_count.iter().filter_map(|(node, &par_count)|if par_count > 1 {Some(node)} else {None}).cloned().collect();
+        // Evaluated arrays used by other nodes are also allocated, so their data is kept.
+        is_allocated.extend(queue[..root_count].iter().filter(|root| parent_count[*root] > 0).cloned());
         let mut is_applied_on_zero = FxHashSet::default();
+        let mut is_read_linearly = FxHashSet::default();
 
-        // Topological sorting.
-        let mut topo = vec![self.clone()];
+        // Topological sorting, starting from the evaluated arrays no other node uses.
+        let mut topo: Vec<DArray> = queue[..root_count].iter().filter(|root| parent_count[*root] == 0).cloned().collect();
         let mut idx = 0;
         while idx < topo.len() {
             let node = &topo[idx];
@@ -184,17 +404,27 @@ impl DArray {
 
         for node in topo.iter() {
             let sources = node.comp().sources();
+            // Arrays read by linear computations are read through if possible, and are allocated otherwise.
+            if is_read_linearly.contains(node) && !is_allocated.contains(node) {
+                match node.comp() {
+                    NodeComp::Add(_) | NodeComp::AddN(_) => {
+                        is_read_linearly.extend(sources);
+                        continue;
+                    }
+                    NodeComp::FromData(_) => continue,
+                    _ => {
+                        is_allocated.insert(node.clone());
+                    }
+                }
+            }
             let on_zero = is_allocated.contains(node) || is_applied_on_zero.contains(node);
 
             match node.comp().get_type() {
-                // Additions never allocate, and propagate applies on zero.
+                // Additions never allocate, and propagate applies on zero to their first unallocated source.
                 ComputationType::Add => {
-                    assert_eq!(sources.len(), 2);
                     if on_zero {
-                        if is_allocated.contains(&sources[0]) {
-                            is_applied_on_zero.insert(sources[1].clone());
-                        } else {
-                            is_applied_on_zero.insert(sources[0].clone());
+                        if let Some(source) = sources.iter().find(|source| !is_allocated.contains(*source)) {
+                            is_applied_on_zero.insert(source.clone());
                         }
                     }
                 }
@@ -214,15 +444,24 @@ impl DArray {
                         }
                     }
                 }
-                // Unaries always apply their child node on zero. They allocate if they are not applied on zero.
-                ComputationType::Unary => {
+                // Unaries always apply their first child node on zero, and allocate the scalar parameters
+                // in the rest of their child nodes. They allocate if they are not applied on zero.
+                // Expansions apply their scalar child node on zero on their first element.
+                ComputationType::Unary | ComputationType::Expand => {
                     is_applied_on_zero.insert(sources[0].clone());
-                    assert_eq!(sources.len(), 1);
+                    for param in sources[1..].iter() {
+                        is_allocated.insert(param.clone());
+                    }
                     // Unary are allocated if they are not applied on zero.
                     if !on_zero {
                         is_allocated.insert(node.clone());
                     }
                 }
+                // Linear computations read their child node linearly.
+                ComputationType::Linear => {
+                    assert_eq!(sources.len(), 1);
+                    is_read_linearly.insert(sources[0].clone());
+                }
                 // Other type allocate all their child nodes.
                 ComputationType::Other => {
                     for node in sources {
@@ -232,17 +471,27 @@ impl DArray {
             }
         }
 
+        // Arrays which are not allocated are calculated recursively by the arrays using them, so long chains
+        // of them would overflow the stack. The chains are split by allocating arrays, which are evaluated
+        // from the sources up, bounding the depth of the recursion.
+        let mut depths: Map<DArray, usize> = Map::default();
         for node in topo.iter().rev() {
-            if is_allocated.contains(node) {
-                node.internal.data();
+            let inlined: Vec<DArray> = node.comp().sources().into_iter()
+                .filter(|source| !is_allocated.contains(source) && depths.contains_key(source))
+                .collect();
+            let mut depth = 1 + inlined.iter().map(|source| depths[source]).max().unwrap_or(0);
+            if depth > MAX_APPLY_DEPTH {
+                is_allocated.extend(inlined);
+                depth = 1;
             }
+            depths.insert(node.clone(), depth);
         }
 
-        self.internal.data()
+        EvaluationPlan {topo, allocated: is_allocated}
     }
 
     /// Returns a reference to the array's computation.
-    pub fn comp(&self) -> &Box<dyn Computation> {
+    pub fn comp(&self) -> &NodeComp {
         &self.internal.comp
     }
 
@@ -292,36 +541,121 @@ impl DArray {
             self.len()
         );
 
-        // Initializing the derivative map.
+        // The gradients of every array from each of its parents, which are summed once all parents are visited.
+        let mut partial_grads: Map<DArray, Vec<DArray>> = Map::default();
+        partial_grads.insert(self.clone(), vec![DArray::from_data_on(&[1.], self.backend())]);
         let mut grads = Map::default();
-        grads.insert(self.clone(), DArray::from_data(&[1.]));
 
         for array in self.topological_sort() {
-            let array_grads = grads.get(&array).unwrap();
+            let array_grads = DArray::add_gradients(&partial_grads.remove(&array).unwrap());
             let sources = array.comp().sources();
-            let source_grads = array.comp().derivatives(array_grads.clone());
-
-            for (source, grad) in izip!(sources.iter(), source_grads.iter()) {
-                let old_grad = grads.get(source);
-                if old_grad.is_none() {
-                    grads.insert(source.clone(), grad.clone());
-                } else {
-                    let old_grad = old_grad.unwrap();
-                    let new_grad = old_grad + grad;
-                    grads.insert(source.clone(), new_grad);
-                }
+            let source_grads = array.comp().derivatives_with_result(&array, array_grads.clone());
+
+            for (source, grad) in izip!(sources, source_grads) {
+                partial_grads.entry(source).or_default().push(grad);
             }
+            grads.insert(array, array_grads);
         }
 
         grads
     }
 
+    /// Calculates the Jacobian-vector product of the array with respect to the given inputs,
+    /// the directional derivative of the array when every input moves along its tangent.
+    ///
+    /// Uses two backward passes: the first calculates the vector-Jacobian product `J^T u` for a dummy vector `u`,
+    /// and since it is linear in `u`, deriving its product with the tangents by `u` gives `J v`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn jvp(&self, inputs: &[DArray], tangents: &[DArray]) -> DArray {
+        assert_eq!(inputs.len(), tangents.len(), "Got {} inputs but {} tangents.", inputs.len(), tangents.len());
+        let zeros = || DArray::from_data_on(&vec![0.; self.len()], self.backend());
+
+        let dummy = zeros();
+        let vjp = (self * &dummy).sum().derive();
+        let directional: Vec<DArray> = izip!(inputs, tangents)
+            .filter_map(|(input, tangent)| vjp.get(input).map(|grad| (grad * tangent).sum()))
+            .collect();
+        if directional.is_empty() {
+            return zeros();
+        }
+
+        directional.into_iter().sum::<DArray>().derive().remove(&dummy).unwrap_or_else(zeros)
+    }
+
+    /// Calculates the Jacobians of the array with respect to the given inputs, selecting between calculating
+    /// them by rows and by columns by the lengths of the array and the inputs. See [`DiffMode::select`].
+    /// See [`DArray::jacobian`] for the layout of the Jacobians.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn grad_auto(&self, inputs: &[DArray]) -> Vec<DArray> {
+        let input_len = inputs.iter().map(|input| input.len()).sum();
+        self.jacobian(inputs, DiffMode::select(self.len(), input_len))
+    }
+
+    /// Calculates the Jacobians of the array with respect to the given inputs using the given mode.
+    /// The Jacobian of an input of length N is a row-major matrix with a row for every element of the array
+    /// and N columns, so its element `(i, j)` is the derivative of `self[i]` by `input[j]`.
+    ///
+    /// Reverse mode uses a backward pass for every element of the array.
+    /// Double backward mode calculates the vector-Jacobian product `J^T u` for a dummy vector `u` once,
+    /// and uses a backward pass by `u` for every element of the inputs.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn jacobian(&self, inputs: &[DArray], mode: DiffMode) -> Vec<DArray> {
+        let rows = self.len();
+        // Places the vectors in the rows or the columns of the Jacobian of an input of length `cols`.
+        let assemble = |vectors: Vec<(usize, DArray)>, cols: usize, mode: DiffMode| {
+            if vectors.is_empty() {
+                return DArray::from_data_on(&vec![0.; rows * cols], self.backend());
+            }
+            let placed: Vec<DArray> = vectors.into_iter()
+                .map(|(idx, vector)| match mode {
+                    DiffMode::Reverse => IndexComp::from_range(&vector, 0..cols, idx * cols, rows * cols),
+                    DiffMode::DoubleBackward => IndexComp::from_strided(&vector, (0, 1), (idx, cols), rows, rows * cols),
+                })
+                .collect();
+            DArray::add_n(&placed)
+        };
+
+        match mode {
+            DiffMode::Reverse => {
+                let row_grads: Vec<_> = (0..rows).map(|row| self.index(row).derive()).collect();
+                inputs.iter()
+                    .map(|input| {
+                        let rows = row_grads.iter().enumerate()
+                            .filter_map(|(row, grads)| grads.get(input).map(|grad| (row, grad.clone())))
+                            .collect();
+                        assemble(rows, input.len(), mode)
+                    })
+                    .collect()
+            }
+            DiffMode::DoubleBackward => {
+                let dummy = DArray::from_data_on(&vec![0.; rows], self.backend());
+                let vjp = (self * &dummy).sum().derive();
+                inputs.iter()
+                    .map(|input| {
+                        let cols = vjp.get(input)
+                            .map(|grad| (0..input.len())
+                                .filter_map(|col| grad.index(col).derive().remove(&dummy).map(|column| (col, column)))
+                                .collect())
+                            .unwrap_or_default();
+                        assemble(cols, input.len(), mode)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Returns if this is the only reference to the array, so that no other computation can use it.
+    pub(crate) fn is_unique(&self) -> bool {
+        Arc::strong_count(&self.internal) == 1
+    }
+
     /// Returns if the array represents a single item.
     pub fn is_scalar(&self) -> bool {
         self.len() == 1
     }
 
     /// Maps the array using a derivable function.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     pub fn map(&self, op: impl DerivableOp) -> DArray {
         DArray::from(UnaryComp::new(self.clone(), op.clone()))
     }
@@ -329,6 +663,37 @@ impl DArray {
     pub fn is_initialized(&self) -> bool {
         self.internal.is_init()
     }
+
+    /// Returns the elements of the array which may be nonzero, as pairs of an index and a value sorted by the index,
+    /// if they can be calculated without calculating the array.
+    /// This is the case for gathers, scatters and other index computations, and their sums with each other
+    /// and with calculated arrays, so the gradients of a gather from a long array can be read without allocating
+    /// an array as long as the source of the gather.
+    /// Returns `None` if the array was already calculated, or if its computation has no sparse form.
+    pub fn sparse_entries(&self) -> Option<Vec<(usize, f64)>> {
+        if self.is_initialized() {
+            return None;
+        }
+        let mut entries = self.comp().sparse_entries()?;
+        entries.sort_by_key(|(idx, _)| *idx);
+        entries.dedup_by(|(idx, val), (prev_idx, prev_val)| {
+            let same = idx == prev_idx;
+            if same {
+                *prev_val += *val;
+            }
+            same
+        });
+        Some(entries)
+    }
+}
+
+/// Returns the location of the caller when the `debug-provenance` feature is enabled.
+#[cfg_attr(feature = "debug-provenance", track_caller)]
+fn caller_location() -> Option<&'static Location<'static>> {
+    #[cfg(feature = "debug-provenance")]
+    return Some(Location::caller());
+    #[cfg(not(feature = "debug-provenance"))]
+    None
 }
 
 impl Eq for DArrayInternal {}
@@ -359,13 +724,24 @@ impl Hash for DArray {
     }
 }
 
+impl Index<usize> for DArray {
+    type Output = f64;
+
+    /// Returns the element of the array at the given index, evaluating the array if needed.
+    fn index(&self, idx: usize) -> &f64 {
+        &self.data()[idx]
+    }
+}
+
 impl From<f64> for DArray {
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn from(src: f64) -> Self {
         DArray::from_data(&[src])
     }
 }
 
 impl From<Vec<f64>> for DArray {
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn from(src: Vec<f64>) -> Self {
         DArray::from_data(src.as_slice())
     }
@@ -378,11 +754,14 @@ impl From<&DArray> for DArray {
 }
 
 impl <Comp: Computation + Clone> From<Comp> for DArray {
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn from(src: Comp) -> Self {
         DArray::from_comp(src)
     }
 }
 
+/// The operands of arithmetic operators with arrays: arrays, references to them, vectors and computations,
+/// which are turned into arrays. Scalars are handled by separate operators, which store them in the computation.
 pub trait DArrayRef {
     fn into(self) -> DArray;
 }
@@ -397,12 +776,27 @@ impl DArrayRef for &DArray {
         self.clone()
     }
 }
+impl DArrayRef for Vec<f64> {
+    fn into(self) -> DArray {
+        DArray::from(self)
+    }
+}
+impl <Comp: Computation + Clone> DArrayRef for Comp {
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn into(self) -> DArray {
+        DArray::from(self)
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use rand::prelude::StdRng;
     use rand::{Rng, SeedableRng};
-    use crate::array::{DArray, DArrayInternal};
+    use itertools::izip;
+    use crate::array::{DArray, DArrayInternal, DiffMode, Storage};
+    use crate::broadcast_functions::Axis;
+    use crate::index_functions::{ExpandComp, IndexComp};
+    use crate::test_utils::random_vec;
 
     const SEED: [u8; 32] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31];
     const DIFF: f64 = 1e-7;
@@ -418,6 +812,20 @@ mod tests {
         }
     }
 
+    /// Tests that the data of small arrays is stored inline, and the data of larger arrays in a buffer.
+    #[test]
+    fn test_storage() {
+        let small = DArray::from(vec![1., 2., 3., 4.]);
+        let large = DArray::from(vec![1., 2., 3., 4., 5.]);
+        let small_sum = &small + &small;
+        let large_sum = &large + &large;
+
+        assert_eq!(small_sum.data(), &[2., 4., 6., 8.]);
+        assert_eq!(large_sum.data(), &[2., 4., 6., 8., 10.]);
+        assert!(matches!(small_sum.internal.data.get(), Some(Storage::Inline(..))));
+        assert!(matches!(large_sum.internal.data.get(), Some(Storage::Heap(..))));
+    }
+
     /// Tests that the derivative of complex random rational functions are evaluated correctly.
     #[test]
     fn test_derivation() {
@@ -451,4 +859,168 @@ mod tests {
             assert_close(arr[0].data()[1] - arr[0].data()[0], grad * (root.data()[1] - root.data()[0]));
         }
     }
-}
\ No newline at end of file
+
+    /// Tests that linear computations read through additions and arrays created from data.
+    #[test]
+    fn test_linear_planning() {
+        let leaf = || DArray::from(vec![1., 2., 3.]);
+        let add = &leaf() + &leaf();
+        let sum = add.sum();
+        let sin = leaf().sin();
+        let direct = leaf();
+        let index = IndexComp::map_indices(&(&sin + &direct), [(0, 1), (2, 0)].into_iter(), 2);
+        let expand = DArray::from(ExpandComp::new(leaf().sum() + 1., 2));
+
+        assert!(sum.plan_evaluation().allocated.is_empty());
+        let allocated = index.plan_evaluation().allocated;
+        assert!(allocated.contains(&sin) && !allocated.contains(&direct));
+        assert!(expand.plan_evaluation().allocated.iter().all(|array| array == &expand));
+
+        assert_eq!(sum.data(), &[12.]);
+        assert!(!add.is_initialized());
+        assert_close(index.data()[0], 3_f64.sin() + 3.);
+        assert_close(index.data()[1], 1_f64.sin() + 1.);
+        assert_eq!(expand.data(), &[7., 7.]);
+    }
+
+    /// Tests that evaluating very deep graphs doesn't overflow the stack.
+    #[test]
+    fn test_deep_evaluation() {
+        let leaf = DArray::from(vec![1., 2.]);
+        let mut constants = leaf.clone();
+        for _ in 0..100_000 {
+            constants = constants + 1.;
+        }
+        assert_eq!(constants.data(), &[100_001., 100_002.]);
+
+        let mut sums = leaf.clone();
+        for _ in 0..100_000 {
+            sums = &sums + &DArray::from(vec![1., -1.]);
+        }
+        assert_eq!(sums.data(), &[100_001., -99_998.]);
+    }
+
+    /// Tests that dropping very deep graphs doesn't overflow the stack.
+    #[test]
+    fn test_deep_drop() {
+        let leaf = DArray::from(vec![1., 2.]);
+        let mut chain = leaf.clone();
+        for _ in 0..200_000 {
+            chain = chain.sin() * &leaf;
+        }
+        let shared = chain.clone();
+        drop(chain);
+        assert_eq!(shared.len(), 2);
+        drop(shared);
+        assert_eq!(leaf.data(), &[1., 2.]);
+    }
+
+    /// Tests evaluating several arrays sharing a subgraph together.
+    #[test]
+    fn test_eval_many() {
+        let a = DArray::from(vec![1., 2., 3.]);
+        let b = DArray::from(vec![3., 2., 1.]);
+        let shared = (&a * &b).sin();
+        let total = shared.sum();
+        let scaled = &shared * 2.;
+
+        let plan = DArray::plan_evaluation_of(&[total.clone(), scaled.clone(), shared.clone()]);
+        assert!(plan.allocated.contains(&shared));
+        assert!(!plan.allocated.contains(&total) && !plan.allocated.contains(&scaled));
+
+        let data = DArray::eval_many(&[&total, &scaled, &shared, &total]);
+        assert!(shared.is_initialized());
+        assert_close(data[0][0], 3_f64.sin() * 2. + 4_f64.sin());
+        assert_close(data[1][1], 4_f64.sin() * 2.);
+        assert_eq!(data[2], shared.data());
+        assert_eq!(data[3], data[0]);
+    }
+
+    /// Tests reading the elements of arrays.
+    #[test]
+    fn test_element_access() {
+        let array = DArray::from(vec![1., 2., 3.]) * 2.;
+        let scalar = DArray::from(3.) + 1.;
+
+        assert_eq!(scalar.item(), 4.);
+        assert_eq!(array.get(1), 4.);
+        assert_eq!(array[2], 6.);
+    }
+
+    #[test]
+    #[should_panic(expected = "Called item on an array of length 3.")]
+    fn test_item_non_scalar() {
+        DArray::from(vec![1., 2., 3.]).item();
+    }
+
+    /// Tests the Jacobian-vector product against a numeric directional derivative.
+    #[test]
+    fn test_jvp() {
+        let mut rng = StdRng::from_seed(SEED);
+        let func = |x: &DArray, y: &DArray| x.outer_mul(&y.sin()) + x.broadcast(3, 2, Axis::Cols);
+        for _ in 0..10 {
+            let x = random_vec(&mut rng, 3);
+            let y = random_vec(&mut rng, 2);
+            let dx = random_vec(&mut rng, 3);
+            let dy = random_vec(&mut rng, 2);
+            let (x_array, y_array) = (DArray::from(x.clone()), DArray::from(y.clone()));
+
+            let res = func(&x_array, &y_array);
+            let jvp = res.jvp(&[x_array, y_array], &[DArray::from(dx.clone()), DArray::from(dy.clone())]);
+
+            let shift = |v: &[f64], d: &[f64]| DArray::from(izip!(v, d).map(|(v, d)| v + d * DIFF).collect::<Vec<f64>>());
+            let shifted = func(&shift(&x, &dx), &shift(&y, &dy));
+            for i in 0..res.len() {
+                assert_close(jvp.data()[i] * DIFF, shifted.data()[i] - res.data()[i]);
+            }
+        }
+    }
+
+    /// Tests that the Jacobians calculated in both modes match a numeric derivation.
+    #[test]
+    fn test_jacobian() {
+        assert_eq!(DiffMode::select(1, 10), DiffMode::Reverse);
+        assert_eq!(DiffMode::select(10, 1), DiffMode::DoubleBackward);
+        assert_eq!(DiffMode::select(3, 2), DiffMode::Reverse);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let func = |x: &DArray, y: &DArray| x.outer_mul(&y.sin()) + x.broadcast(3, 2, Axis::Cols);
+        for _ in 0..10 {
+            let x = random_vec(&mut rng, 3);
+            let y = random_vec(&mut rng, 2);
+            let inputs = [DArray::from(x.clone()), DArray::from(y.clone()), DArray::from(vec![1., 2.])];
+            let res = func(&inputs[0], &inputs[1]);
+
+            let double = res.jacobian(&inputs, DiffMode::DoubleBackward);
+            let reverse = res.jacobian(&inputs, DiffMode::Reverse);
+            let auto = res.grad_auto(&inputs);
+            assert_eq!(reverse[2].data(), &[0.; 12]);
+            for (input, vals) in [x, y].iter().enumerate() {
+                assert_eq!(double[input].len(), res.len() * vals.len());
+                for col in 0..vals.len() {
+                    let mut shifted = vals.clone();
+                    shifted[col] += DIFF;
+                    let shifted = if input == 0 {
+                        func(&DArray::from(shifted), &inputs[1])
+                    } else {
+                        func(&inputs[0], &DArray::from(shifted))
+                    };
+                    for row in 0..res.len() {
+                        let idx = row * vals.len() + col;
+                        assert_close(double[input].data()[idx] * DIFF, shifted.data()[row] - res.data()[row]);
+                        assert_close(reverse[input].data()[idx], double[input].data()[idx]);
+                        assert_close(auto[input].data()[idx], double[input].data()[idx]);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Tests that the Jacobian-vector product with respect to unrelated inputs is zero.
+    #[test]
+    fn test_jvp_unrelated() {
+        let x = DArray::from(vec![1., 2.]);
+        let res = x.sin();
+        assert_eq!(res.jvp(&[DArray::from(vec![1., 2.])], &[DArray::from(vec![1., 1.])]).data(), &[0., 0.]);
+    }
+}
@@ -0,0 +1,164 @@
+use itertools::izip;
+
+/// The backend used to evaluate arrays when no other backend is selected.
+pub static CPU: CpuBackend = CpuBackend;
+
+/// Allocates the buffers of arrays and runs the kernels used by the built-in computations.
+///
+/// Every array has a backend, which is inherited from the first source of its computation.
+/// Arrays created from data use [`CPU`] unless created with [`DArray::with_backend`],
+/// so selecting the backend of the inputs of a graph selects the backend of the whole graph.
+///
+/// All kernels have plain scalar implementations, so a backend only overrides the kernels it
+/// speeds up.
+///
+/// [`DArray::with_backend`]: crate::DArray::with_backend
+pub trait Backend: Send + Sync {
+    /// The name of the backend, used in reports.
+    fn name(&self) -> &'static str;
+
+    /// Allocates a zero-initialized buffer of the given length.
+    /// Only used for arrays too long to be stored inline.
+    fn zeros(&self, len: usize) -> Vec<f64> {
+        vec![0.; len]
+    }
+
+    /// Adds `src` to `res` pointwise.
+    fn add_assign(&self, res: &mut [f64], src: &[f64]) {
+        for (res, src) in izip!(res.iter_mut(), src) {
+            *res += src;
+        }
+    }
+
+    /// Subtracts `src` from `res` pointwise.
+    fn sub_assign(&self, res: &mut [f64], src: &[f64]) {
+        for (res, src) in izip!(res.iter_mut(), src) {
+            *res -= src;
+        }
+    }
+
+    /// Multiplies `res` by `src` pointwise.
+    fn mul_assign(&self, res: &mut [f64], src: &[f64]) {
+        for (res, src) in izip!(res.iter_mut(), src) {
+            *res *= src;
+        }
+    }
+
+    /// Adds the pointwise product of `p1` and `p2` to `res`.
+    fn mul_add_assign(&self, res: &mut [f64], p1: &[f64], p2: &[f64]) {
+        for (res, p1, p2) in izip!(res.iter_mut(), p1, p2) {
+            *res += p1 * p2;
+        }
+    }
+
+    /// Adds the pointwise product of `p1` and `p2`, plus `p3`, to `res` in a single pass.
+    fn fma_add_assign(&self, res: &mut [f64], p1: &[f64], p2: &[f64], p3: &[f64]) {
+        for (res, p1, p2, p3) in izip!(res.iter_mut(), p1, p2, p3) {
+            *res += p1 * p2 + p3;
+        }
+    }
+
+    /// Divides `res` by `src` pointwise.
+    fn div_assign(&self, res: &mut [f64], src: &[f64]) {
+        for (res, src) in izip!(res.iter_mut(), src) {
+            *res /= src;
+        }
+    }
+
+    /// Replaces every element of `res` with the matching element of `src` divided by it.
+    fn inv_mul_assign(&self, res: &mut [f64], src: &[f64]) {
+        for (res, src) in izip!(res.iter_mut(), src) {
+            *res = src / *res;
+        }
+    }
+
+    /// Adds the pointwise quotient of `p1` and `p2` to `res`.
+    fn div_add_assign(&self, res: &mut [f64], p1: &[f64], p2: &[f64]) {
+        for (res, p1, p2) in izip!(res.iter_mut(), p1, p2) {
+            *res += p1 / p2;
+        }
+    }
+
+    /// Adds a scalar to every element of `res`.
+    fn add_scalar(&self, res: &mut [f64], scalar: f64) {
+        for res in res.iter_mut() {
+            *res += scalar;
+        }
+    }
+
+    /// Multiplies every element of `res` by a scalar.
+    fn mul_scalar(&self, res: &mut [f64], scalar: f64) {
+        for res in res.iter_mut() {
+            *res *= scalar;
+        }
+    }
+
+    /// Replaces every element `x` of `res` with `scale * x + shift`.
+    fn affine(&self, res: &mut [f64], scale: f64, shift: f64) {
+        for res in res.iter_mut() {
+            *res = scale * *res + shift;
+        }
+    }
+
+    /// Returns the sum of the elements of `src`.
+    fn sum(&self, src: &[f64]) -> f64 {
+        src.iter().sum()
+    }
+
+    /// Returns the sum of the pointwise product of `p1` and `p2`.
+    fn dot(&self, p1: &[f64], p2: &[f64]) -> f64 {
+        izip!(p1, p2).map(|(p1, p2)| p1 * p2).sum()
+    }
+}
+
+/// A backend running the scalar implementations of the kernels on the CPU.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct CpuBackend;
+
+impl Backend for CpuBackend {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::DArray;
+    use crate::backend::Backend;
+
+    /// A backend counting the buffers it allocates.
+    struct CountingBackend {
+        allocations: AtomicUsize,
+    }
+
+    impl Backend for CountingBackend {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn zeros(&self, len: usize) -> Vec<f64> {
+            self.allocations.fetch_add(1, Ordering::SeqCst);
+            vec![0.; len]
+        }
+    }
+
+    static COUNTING: CountingBackend = CountingBackend {allocations: AtomicUsize::new(0)};
+
+    /// Tests that the backend of the inputs is used by the whole graph, including its derivatives.
+    #[test]
+    fn test_backend_selection() {
+        let x = DArray::with_backend(vec![1., 2., 3., 4., 5.], &COUNTING);
+        let y = DArray::from(vec![5., 4., 3., 2., 1.]);
+        let res = (&x * &y + 1.).sum();
+
+        assert_eq!(res.backend().name(), "counting");
+        assert_eq!(y.backend().name(), "cpu");
+        assert_eq!(res.item(), 40.);
+        assert!(COUNTING.allocations.load(Ordering::SeqCst) > 0);
+
+        let grad = res.derive().get(&x).unwrap().clone();
+        assert_eq!(grad.backend().name(), "counting");
+        assert_eq!(grad.data(), &[5., 4., 3., 2., 1.]);
+    }
+}
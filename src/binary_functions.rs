@@ -1,19 +1,29 @@
-use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::iter::{Product, Sum};
+use std::ops::{Add, Div, Mul, Sub};
 use itertools::izip;
 
-use crate::computation::{Computation, ComputationType};
+use crate::computation::{Computation, ComputationType, Cost, NodeComp, F64_SIZE};
 use crate::array::{DArray, DArrayRef};
+use crate::index_functions::expand_array;
+
+/// Checks that the two sources of a pointwise computation have the same length.
+pub(crate) fn validate_same_len(p1: &DArray, p2: &DArray) -> Result<(), String> {
+    if p1.len() == p2.len() {
+        Ok(())
+    } else {
+        Err(format!("The sources have different lengths ({} and {}).", p1.len(), p2.len()))
+    }
+}
 
 /// A computation handling pointwise addition of two arrays.
 #[derive(Clone)]
-struct AddComp {
+pub struct AddComp {
     p1: DArray,
     p2: DArray,
 }
 
 impl<'t> AddComp {
     fn new(p1: DArray, p2: DArray) -> AddComp {
-        assert_eq!(p1.len(), p2.len());
         AddComp {p1, p2}
     }
 }
@@ -32,19 +42,14 @@ impl Computation for AddComp {
     }
 
     fn apply(&self, res_array: &mut [f64]) {
+        let backend = self.p1.backend();
         if self.p1.is_initialized() {
-            let data = self.p1.data();
-            for i in 0..self.len() {
-                res_array[i] += data[i];
-            }
+            backend.add_assign(res_array, self.p1.data());
         } else {
             self.p1.comp().apply(res_array);
         }
         if self.p2.is_initialized() {
-            let data = self.p2.data();
-            for i in 0..self.len() {
-                res_array[i] += data[i];
-            }
+            backend.add_assign(res_array, self.p2.data());
         } else {
             self.p2.comp().apply(res_array);
         }
@@ -56,23 +61,19 @@ impl Computation for AddComp {
 
     /// Applies addition on a zero array. Propagates the "apply on zero" to an uninitialized array if possible.
     fn apply_on_zero(&self, res_array: &mut [f64]) {
+        let backend = self.p1.backend();
         match (self.p1.is_initialized(), self.p2.is_initialized()) {
             (true, true) => {
-                for (res, i, j) in izip!(res_array.iter_mut(), self.p1.data(), self.p2.data()) {
-                    *res += i + j;
-                }
+                backend.add_assign(res_array, self.p1.data());
+                backend.add_assign(res_array, self.p2.data());
             }
             (true, false) => {
                 self.p2.comp().apply_on_zero(res_array);
-                for (res, i) in izip!(res_array.iter_mut(), self.p1.data()) {
-                    *res += i;
-                }
+                backend.add_assign(res_array, self.p1.data());
             }
             (false, true) => {
                 self.p1.comp().apply_on_zero(res_array);
-                for (res, i) in izip!(res_array.iter_mut(), self.p2.data()) {
-                    *res += i;
-                }
+                backend.add_assign(res_array, self.p2.data());
             }
             (false, false) => {
                 self.p1.comp().apply_on_zero(res_array);
@@ -80,10 +81,112 @@ impl Computation for AddComp {
             }
         }
     }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.p1, &self.p2)
+    }
+
+    /// The entries of a sum are the entries of its summands.
+    fn sparse_entries(&self) -> Option<Vec<(usize, f64)>> {
+        let mut entries = Vec::new();
+        for array in [&self.p1, &self.p2] {
+            entries.extend(summand_entries(array)?);
+        }
+        Some(entries)
+    }
+}
+
+/// Returns the entries of a summand of a sum: its nonzero elements if it was already calculated,
+/// and its sparse entries otherwise.
+fn summand_entries(array: &DArray) -> Option<Vec<(usize, f64)>> {
+    if array.is_initialized() {
+        Some(array.data().iter().copied().enumerate().filter(|(_, val)| *val != 0.).collect())
+    } else {
+        array.sparse_entries()
+    }
+}
+
+/// A computation handling pointwise addition of any number of arrays of the same length.
+/// Lighter and shallower than a chain of [`AddComp`]s.
+#[derive(Clone)]
+pub struct AddNComp {
+    arrays: Vec<DArray>,
+}
+
+impl Computation for AddNComp {
+    fn sources(&self) -> Vec<DArray> {
+        self.arrays.clone()
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![res_grads; self.arrays.len()]
+    }
+
+    fn len(&self) -> usize {
+        self.arrays[0].len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let backend = self.arrays[0].backend();
+        for array in self.arrays.iter() {
+            if array.is_initialized() {
+                backend.add_assign(res_array, array.data());
+            } else {
+                array.comp().apply(res_array);
+            }
+        }
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Add
+    }
+
+    /// Applies addition on a zero array. Propagates the "apply on zero" to the first uninitialized array.
+    fn apply_on_zero(&self, res_array: &mut [f64]) {
+        let backend = self.arrays[0].backend();
+        let on_zero = self.arrays.iter().position(|array| !array.is_initialized());
+        if let Some(on_zero) = on_zero {
+            self.arrays[on_zero].comp().apply_on_zero(res_array);
+        }
+        for (idx, array) in self.arrays.iter().enumerate() {
+            if array.is_initialized() {
+                backend.add_assign(res_array, array.data());
+            } else if Some(idx) != on_zero {
+                array.comp().apply(res_array);
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.arrays.is_empty() {
+            return Err("No arrays are added.".to_string());
+        }
+        self.arrays.iter().try_for_each(|array| validate_same_len(&self.arrays[0], array))
+    }
+
+    /// The entries of a sum are the entries of its summands.
+    fn sparse_entries(&self) -> Option<Vec<(usize, f64)>> {
+        let mut entries = Vec::new();
+        for array in self.arrays.iter() {
+            entries.extend(summand_entries(array)?);
+        }
+        Some(entries)
+    }
+}
+
+impl DArray {
+    /// Adds any number of arrays of the same length in a single computation.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn add_n(arrays: &[DArray]) -> DArray {
+        match arrays {
+            [array] => array.clone(),
+            _ => DArray::from(AddNComp {arrays: arrays.to_vec()}),
+        }
+    }
 }
 
 #[derive(Clone, Eq, PartialEq)]
-struct AddScalarComp {
+pub struct AddScalarComp {
     non_scalar: DArray,
     scalar: DArray,
 }
@@ -115,10 +218,7 @@ impl Computation for AddScalarComp {
 
     fn apply(&self, res_array: &mut [f64]) {
         self.non_scalar.comp().apply(res_array);
-        let c = self.scalar.data()[0];
-        for v in res_array.iter_mut() {
-            *v += c;
-        }
+        self.non_scalar.backend().add_scalar(res_array, self.scalar.data()[0]);
     }
 
     fn get_type(&self) -> ComputationType {
@@ -127,60 +227,385 @@ impl Computation for AddScalarComp {
 
     fn apply_on_zero(&self, res_array: &mut [f64]) {
         self.non_scalar.comp().apply_on_zero(res_array);
-        let c = self.scalar.data()[0];
-        for v in res_array.iter_mut() {
-            *v += c;
+        self.non_scalar.backend().add_scalar(res_array, self.scalar.data()[0]);
+    }
+}
+
+/// A computation adding a constant to every element of an array.
+/// The constant is stored in the computation, instead of in a scalar array.
+#[derive(Clone)]
+pub struct AddConstComp {
+    src: DArray,
+    cons: f64,
+}
+
+impl Computation for AddConstComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![res_grads]
+    }
+
+    fn len(&self) -> usize {
+        self.src.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        if self.src.is_initialized() {
+            self.src.backend().add_assign(res_array, self.src.data());
+        } else {
+            self.src.comp().apply(res_array);
+        }
+        self.src.backend().add_scalar(res_array, self.cons);
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Add
+    }
+
+    /// Applies the addition on a zero array, propagating the "apply on zero" to the source if it is uninitialized.
+    fn apply_on_zero(&self, res_array: &mut [f64]) {
+        if self.src.is_initialized() {
+            self.src.backend().add_assign(res_array, self.src.data());
+        } else {
+            self.src.comp().apply_on_zero(res_array);
+        }
+        self.src.backend().add_scalar(res_array, self.cons);
+    }
+}
+
+/// Adds two arrays of the same length. A product which no other computation can use is fused with the addition.
+#[cfg_attr(feature = "debug-provenance", track_caller)]
+fn add_arrays(p1: DArray, p2: DArray) -> DArray {
+    for (product, addend) in [(&p2, &p1), (&p1, &p2)] {
+        if product.is_unique() && !product.is_initialized() {
+            if let NodeComp::Mul(mul) = product.comp() {
+                return DArray::from(FmaComp::new(mul.p1.clone(), mul.p2.clone(), addend.clone()));
+            }
         }
     }
+    DArray::from(AddComp::new(p1, p2))
 }
 
-impl <Other: Into<DArray>> Add<Other> for &DArray {
+impl <Other: DArrayRef> Add<Other> for &DArray {
     type Output = DArray;
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn add(self, rhs: Other) -> Self::Output {
         let rhs = rhs.into();
         if self.is_scalar() || rhs.is_scalar() {
             DArray::from(AddScalarComp::new(self.clone(), rhs))
         } else {
-            DArray::from(AddComp::new(self.clone(), rhs))
+            add_arrays(self.clone(), rhs)
         }
     }
 }
 
-impl <Other: Into<DArray>> Add<Other> for DArray {
+impl <Other: DArrayRef> Add<Other> for DArray {
     type Output = DArray;
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn add(self, rhs: Other) -> Self::Output {
         let rhs = rhs.into();
         if self.is_scalar() ^ rhs.is_scalar() {
             DArray::from(AddScalarComp::new(self.clone(), rhs))
         } else {
-            DArray::from(AddComp::new(self, rhs))
+            add_arrays(self, rhs)
+        }
+    }
+}
+
+impl Add<f64> for &DArray {
+    type Output = DArray;
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn add(self, rhs: f64) -> Self::Output {
+        DArray::from(AddConstComp {src: self.clone(), cons: rhs})
+    }
+}
+
+impl Add<f64> for DArray {
+    type Output = DArray;
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn add(self, rhs: f64) -> Self::Output {
+        DArray::from(AddConstComp {src: self, cons: rhs})
+    }
+}
+
+/// A computation calculating `p1 * p2 + addend` pointwise in a single pass.
+/// Created explicitly with [`DArray::fma`], or by adding a product which isn't used by other computations.
+#[derive(Clone)]
+pub struct FmaComp {
+    addend: DArray,
+    p1: DArray,
+    p2: DArray,
+}
+
+impl FmaComp {
+    fn new(p1: DArray, p2: DArray, addend: DArray) -> FmaComp {
+        FmaComp {addend, p1, p2}
+    }
+}
+
+impl Computation for FmaComp {
+    /// The addend is the first source, so that it can be applied on zero.
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.addend.clone(), self.p1.clone(), self.p2.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![res_grads.clone(), &res_grads * &self.p2, &res_grads * &self.p1]
+    }
+
+    fn len(&self) -> usize {
+        self.addend.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        self.p1.backend().fma_add_assign(res_array, self.p1.data(), self.p2.data(), self.addend.data());
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Unary
+    }
+
+    fn apply_on_zero(&self, res_array: &mut [f64]) {
+        if self.addend.is_initialized() {
+            self.apply(res_array);
+        } else {
+            self.addend.comp().apply_on_zero(res_array);
+            self.p1.backend().mul_add_assign(res_array, self.p1.data(), self.p2.data());
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 2 * self.len(), bytes_read: 3 * self.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.addend, &self.p1)?;
+        validate_same_len(&self.addend, &self.p2)
+    }
+}
+
+impl DArray {
+    /// Calculates `self * other + addend` pointwise in a single computation.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn fma(&self, other: &DArray, addend: &DArray) -> DArray {
+        DArray::from(FmaComp::new(self.clone(), other.clone(), addend.clone()))
+    }
+}
+
+/// A computation handling the pointwise linear interpolation `start + weight * (end - start)` of two arrays.
+#[derive(Clone)]
+pub struct LerpComp {
+    start: DArray,
+    end: DArray,
+    weight: DArray,
+}
+
+impl LerpComp {
+    /// Initializes an interpolation, expanding scalar sources to the length of the others.
+    fn new(start: DArray, end: DArray, weight: DArray) -> LerpComp {
+        let longest = [&start, &end, &weight].into_iter().max_by_key(|array| array.len()).unwrap().clone();
+        LerpComp {start: expand_array(start, &longest), end: expand_array(end, &longest), weight: expand_array(weight, &longest)}
+    }
+}
+
+impl Computation for LerpComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.start.clone(), self.end.clone(), self.weight.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let end_grads = &res_grads * &self.weight;
+        vec![&res_grads - &end_grads, end_grads, &res_grads * (&self.end - &self.start)]
+    }
+
+    fn len(&self) -> usize {
+        self.start.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        for (res, start, end, weight) in izip!(res_array.iter_mut(), self.start.data(), self.end.data(), self.weight.data()) {
+            *res += start + weight * (end - start);
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 3 * self.len(), bytes_read: 3 * self.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.start, &self.end)?;
+        validate_same_len(&self.start, &self.weight)
+    }
+}
+
+impl DArray {
+    /// Interpolates linearly from `self` to `end` pointwise, calculating `self + weight * (end - self)`
+    /// in a single computation. Scalar arrays are expanded to the length of the others.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn lerp(&self, end: &DArray, weight: &DArray) -> DArray {
+        DArray::from(LerpComp::new(self.clone(), end.clone(), weight.clone()))
+    }
+}
+
+/// A computation handling pointwise subtraction of two arrays.
+#[derive(Clone)]
+pub struct SubComp {
+    p1: DArray,
+    p2: DArray,
+}
+
+impl Computation for SubComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.p1.clone(), self.p2.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![res_grads.clone(), -res_grads]
+    }
+
+    fn len(&self) -> usize {
+        self.p1.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let backend = self.p1.backend();
+        backend.add_assign(res_array, self.p1.data());
+        backend.sub_assign(res_array, self.p2.data());
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Binary
+    }
+
+    /// Applies one of the sources on zero, and subtracts the other source or subtracts from it.
+    fn apply_on_zero(&self, res_array: &mut [f64]) {
+        let backend = self.p1.backend();
+        if !self.p1.is_initialized() {
+            self.p1.comp().apply_on_zero(res_array);
+            backend.sub_assign(res_array, self.p2.data());
+        } else {
+            self.p2.comp().apply_on_zero(res_array);
+            backend.affine(res_array, -1., 0.);
+            backend.add_assign(res_array, self.p1.data());
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.p1, &self.p2)
+    }
+}
+
+/// A computation subtracting a scalar from an array, or an array from a scalar.
+#[derive(Clone)]
+pub struct SubScalarComp {
+    non_scalar: DArray,
+    scalar: DArray,
+    /// If the array is subtracted from the scalar, and not the scalar from the array.
+    reversed: bool,
+}
+
+impl SubScalarComp {
+    fn new(p1: DArray, p2: DArray) -> SubScalarComp {
+        if p2.is_scalar() {
+            return SubScalarComp {non_scalar: p1, scalar: p2, reversed: false};
+        }
+        if p1.is_scalar() {
+            return SubScalarComp {non_scalar: p2, scalar: p1, reversed: true};
+        }
+        panic!("SubScalarComp created with no scalars!")
+    }
+}
+
+impl Computation for SubScalarComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.non_scalar.clone(), self.scalar.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        if self.reversed {
+            vec![-&res_grads, res_grads.sum()]
+        } else {
+            vec![res_grads.clone(), -res_grads.sum()]
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.non_scalar.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let c = self.scalar.data()[0];
+        for (res, v) in res_array.iter_mut().zip(self.non_scalar.data()) {
+            *res += if self.reversed {c - v} else {v - c};
+        }
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Unary
+    }
+
+    fn apply_on_zero(&self, res_array: &mut [f64]) {
+        if self.non_scalar.is_initialized() {
+            self.apply(res_array);
+            return;
+        }
+        self.non_scalar.comp().apply_on_zero(res_array);
+        let c = self.scalar.data()[0];
+        if self.reversed {
+            self.non_scalar.backend().affine(res_array, -1., c);
+        } else {
+            self.non_scalar.backend().add_scalar(res_array, -c);
         }
     }
 }
 
-impl <OtherNeg : Into<DArray>, Other: Neg<Output = OtherNeg>>  Sub<Other> for &DArray {
+impl <Other: DArrayRef> Sub<Other> for &DArray {
     type Output = DArray;
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn sub(self, rhs: Other) -> Self::Output {
-        self.clone() + (-rhs)
+        self.clone() - rhs
     }
 }
-impl <OtherNeg : Into<DArray>, Other: Neg<Output = OtherNeg>>  Sub<Other> for DArray {
+impl <Other: DArrayRef> Sub<Other> for DArray {
     type Output = DArray;
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn sub(self, rhs: Other) -> Self::Output {
+        let rhs = rhs.into();
+        if self.is_scalar() != rhs.is_scalar() {
+            DArray::from(SubScalarComp::new(self, rhs))
+        } else {
+            DArray::from(SubComp {p1: self, p2: rhs})
+        }
+    }
+}
+
+impl Sub<f64> for &DArray {
+    type Output = DArray;
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn sub(self, rhs: f64) -> Self::Output {
+        self + (-rhs)
+    }
+}
+impl Sub<f64> for DArray {
+    type Output = DArray;
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn sub(self, rhs: f64) -> Self::Output {
         self + (-rhs)
     }
 }
 
 /// A computation handling pointwise multiplication of two arrays.
 #[derive(Clone)]
-struct MulComp {
+pub struct MulComp {
     p1: DArray,
     p2: DArray,
 }
 
 impl<'t> MulComp {
     fn new(p1: DArray, p2: DArray) -> MulComp {
-        assert_eq!(p1.len(), p2.len());
         MulComp {p1, p2}
     }
 }
@@ -192,8 +617,8 @@ impl Computation for MulComp {
 
     fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
         vec![
-            &self.p2 * &res_grads,
-            &self.p1 * &res_grads,
+            &res_grads * &self.p2,
+            &res_grads * &self.p1,
         ]
     }
 
@@ -203,9 +628,7 @@ impl Computation for MulComp {
 
     fn apply(&self, res_array: &mut [f64]) {
         assert_eq!(res_array.len(), self.len());
-        for (res, p1, p2) in izip!(res_array.iter_mut(), self.p1.data().iter(), self.p2.data().iter()) {
-            *res += p1 * p2;
-        }
+        self.p1.backend().mul_add_assign(res_array, self.p1.data(), self.p2.data());
     }
 
 
@@ -214,23 +637,24 @@ impl Computation for MulComp {
     }
 
     fn apply_on_zero(&self, res_array: &mut [f64]) {
+        let backend = self.p1.backend();
         if !self.p1.is_initialized() {
             self.p1.comp().apply_on_zero(res_array);
-            for (v1, v2) in izip!(res_array.iter_mut(), self.p2.data().iter()) {
-                *v1 *= v2;
-            }
+            backend.mul_assign(res_array, self.p2.data());
         } else {
             self.p2.comp().apply_on_zero(res_array);
-            for (v1, v2) in izip!(res_array.iter_mut(), self.p1.data().iter()) {
-                *v1 *= v2;
-            }
+            backend.mul_assign(res_array, self.p1.data());
         }
     }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.p1, &self.p2)
+    }
 }
 
 
 #[derive(Clone, Eq, PartialEq)]
-struct MulScalarComp {
+pub struct MulScalarComp {
     non_scalar: DArray,
     scalar: DArray,
 }
@@ -261,10 +685,9 @@ impl Computation for MulScalarComp {
     }
 
     fn apply(&self, res_array: &mut [f64]) {
-        self.non_scalar.comp().apply(res_array);
         let c = self.scalar.data()[0];
-        for v in res_array.iter_mut() {
-            *v += c;
+        for (res, v) in res_array.iter_mut().zip(self.non_scalar.data()) {
+            *res += v * c;
         }
     }
 
@@ -273,17 +696,19 @@ impl Computation for MulScalarComp {
     }
 
     fn apply_on_zero(&self, res_array: &mut [f64]) {
-        self.non_scalar.comp().apply_on_zero(res_array);
-        let c = self.scalar.data()[0];
-        for v in res_array.iter_mut() {
-            *v *= c;
+        if self.non_scalar.is_initialized() {
+            self.apply(res_array);
+            return;
         }
+        self.non_scalar.comp().apply_on_zero(res_array);
+        self.non_scalar.backend().mul_scalar(res_array, self.scalar.data()[0]);
     }
 }
 
 impl <Other: DArrayRef> Mul<Other> for &DArray {
     type Output = DArray;
 
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn mul(self, rhs: Other) -> Self::Output {
         let rhs = rhs.into();
         if self.is_scalar() ^ rhs.is_scalar() {
@@ -296,6 +721,7 @@ impl <Other: DArrayRef> Mul<Other> for &DArray {
 impl <Other: DArrayRef> Mul<Other> for DArray {
     type Output = DArray;
 
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn mul(self, rhs: Other) -> Self::Output {
         let rhs = rhs.into();
         if self.is_scalar() ^ rhs.is_scalar() {
@@ -306,18 +732,839 @@ impl <Other: DArrayRef> Mul<Other> for DArray {
     }
 }
 
+/// A computation handling pointwise division of two arrays.
+#[derive(Clone)]
+pub struct DivComp {
+    p1: DArray,
+    p2: DArray,
+}
+
+impl DivComp {
+    /// Initializes a division, expanding a scalar divided by an array or an array divided by a scalar.
+    fn new(p1: DArray, p2: DArray) -> DivComp {
+        DivComp {p1: expand_array(p1.clone(), &p2), p2: expand_array(p2, &p1)}
+    }
+}
+
+impl Computation for DivComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.p1.clone(), self.p2.clone()]
+    }
+
+    /// Uses the quotient rule, reusing the derivative by the numerator in the derivative by the denominator.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let p1_grads = &res_grads / &self.p2;
+        let p2_grads = -((&p1_grads * &self.p1) / &self.p2);
+        vec![p1_grads, p2_grads]
+    }
+
+    fn len(&self) -> usize {
+        self.p1.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        assert_eq!(res_array.len(), self.len());
+        self.p1.backend().div_add_assign(res_array, self.p1.data(), self.p2.data());
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Binary
+    }
+
+    fn apply_on_zero(&self, res_array: &mut [f64]) {
+        let backend = self.p1.backend();
+        if !self.p1.is_initialized() {
+            self.p1.comp().apply_on_zero(res_array);
+            backend.div_assign(res_array, self.p2.data());
+        } else {
+            self.p2.comp().apply_on_zero(res_array);
+            backend.inv_mul_assign(res_array, self.p1.data());
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.p1, &self.p2)
+    }
+}
+
 impl <Other: DArrayRef> Div<Other> for DArray {
     type Output = DArray;
 
-    fn div(self, rhs: Other) -> Self::Output {
-        self * rhs.into().powi(-1)
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn div(self, rhs: Other) -> Self::Output {
+        DArray::from(DivComp::new(self, rhs.into()))
+    }
+}
+impl <Other: DArrayRef> Div<Other> for &DArray {
+    type Output = DArray;
+
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn div(self, rhs: Other) -> Self::Output {
+        DArray::from(DivComp::new(self.clone(), rhs.into()))
+    }
+}
+
+/// A computation handling the pointwise remainder of the division of two arrays, with the sign of the numerator.
+#[derive(Clone)]
+pub struct RemComp {
+    p1: DArray,
+    p2: DArray,
+}
+
+impl RemComp {
+    /// Initializes a remainder, expanding a scalar numerator or denominator.
+    fn new(p1: DArray, p2: DArray) -> RemComp {
+        RemComp {p1: expand_array(p1.clone(), &p2), p2: expand_array(p2, &p1)}
+    }
+}
+
+impl Computation for RemComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.p1.clone(), self.p2.clone()]
+    }
+
+    /// The remainder is `p1 - trunc(p1 / p2) * p2`, so its derivative by the numerator is 1 and its
+    /// derivative by the denominator is `-trunc(p1 / p2)`, everywhere except on the jumps.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let quotient = (&self.p1 / &self.p2).trunc();
+        vec![res_grads.clone(), -(&res_grads * &quotient)]
+    }
+
+    fn len(&self) -> usize {
+        self.p1.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        for (res, p1, p2) in izip!(res_array.iter_mut(), self.p1.data(), self.p2.data()) {
+            *res += p1 % p2;
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.p1, &self.p2)
+    }
+}
+
+impl DArray {
+    /// Returns the pointwise remainder of the division by another array, with the sign of `self`.
+    /// The derivative by `self` is 1 everywhere except on the jumps of the remainder.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn rem(&self, other: &DArray) -> DArray {
+        DArray::from(RemComp::new(self.clone(), other.clone()))
+    }
+}
+
+/// A computation handling the pointwise power `base^exponent * ln(base)^log_power` of two arrays.
+/// The logarithm factor only appears in the derivatives, which have the same form.
+#[derive(Clone)]
+pub struct PowComp {
+    base: DArray,
+    exponent: DArray,
+    log_power: i32,
+}
+
+impl PowComp {
+    /// Initializes a power, expanding a scalar base or exponent.
+    fn new(base: DArray, exponent: DArray, log_power: i32) -> PowComp {
+        PowComp {base: expand_array(base.clone(), &exponent), exponent: expand_array(exponent, &base), log_power}
+    }
+}
+
+impl Computation for PowComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.base.clone(), self.exponent.clone()]
+    }
+
+    /// The derivative by the base is `b * a^(b - 1) * ln(a)^k + k * a^(b - 1) * ln(a)^(k - 1)`,
+    /// and the derivative by the exponent is `a^b * ln(a)^(k + 1)`.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let lowered = &self.exponent - 1.;
+        let mut base_grads = DArray::from(PowComp::new(self.base.clone(), lowered.clone(), self.log_power)) * &self.exponent;
+        if self.log_power > 0 {
+            base_grads = base_grads + DArray::from(PowComp::new(self.base.clone(), lowered, self.log_power - 1)) * self.log_power as f64;
+        }
+        let exponent_grads = DArray::from(PowComp::new(self.base.clone(), self.exponent.clone(), self.log_power + 1));
+        vec![&res_grads * &base_grads, &res_grads * &exponent_grads]
+    }
+
+    fn len(&self) -> usize {
+        self.base.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        for (res, base, exponent) in izip!(res_array.iter_mut(), self.base.data(), self.exponent.data()) {
+            let pow = base.powf(*exponent);
+            // Zero powers stay zero, instead of being multiplied by the infinite logarithm of a zero base.
+            *res += if self.log_power == 0 || pow == 0. { pow } else { pow * base.ln().powi(self.log_power) };
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.base, &self.exponent)
+    }
+}
+
+impl DArray {
+    /// Returns the pointwise power `self^exponent`, differentiable by both the base and the exponent.
+    /// Negative bases are only defined for integer exponents, and the derivative by the exponent is `NaN` for them.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn pow(&self, exponent: &DArray) -> DArray {
+        DArray::from(PowComp::new(self.clone(), exponent.clone(), 0))
+    }
+}
+
+/// A computation handling the pointwise four-quadrant arctangent `atan2(y, x)` of two arrays.
+#[derive(Clone)]
+pub struct Atan2Comp {
+    y: DArray,
+    x: DArray,
+}
+
+impl Atan2Comp {
+    /// Initializes an arctangent, expanding a scalar `y` or `x`.
+    fn new(y: DArray, x: DArray) -> Atan2Comp {
+        Atan2Comp {y: expand_array(y.clone(), &x), x: expand_array(x, &y)}
+    }
+}
+
+impl Computation for Atan2Comp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.y.clone(), self.x.clone()]
+    }
+
+    /// The derivatives are `x / (x^2 + y^2)` by `y` and `-y / (x^2 + y^2)` by `x`,
+    /// which are continuous across the jump of the angle between `-pi` and `pi`.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let scaled_grads = &res_grads / (&self.x * &self.x + &self.y * &self.y);
+        vec![&scaled_grads * &self.x, -(&scaled_grads * &self.y)]
+    }
+
+    fn len(&self) -> usize {
+        self.y.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        for (res, y, x) in izip!(res_array.iter_mut(), self.y.data(), self.x.data()) {
+            *res += y.atan2(*x);
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.y, &self.x)
+    }
+}
+
+impl DArray {
+    /// Returns the pointwise angle of the points `(x, y)`, with `self` as `y`, in the range `[-pi, pi]`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn atan2(&self, x: &DArray) -> DArray {
+        DArray::from(Atan2Comp::new(self.clone(), x.clone()))
+    }
+}
+
+/// A computation handling the pointwise length `sqrt(x^2 + y^2)` of the points `(x, y)` of two arrays,
+/// without overflowing for large values.
+#[derive(Clone)]
+pub struct HypotComp {
+    x: DArray,
+    y: DArray,
+}
+
+impl HypotComp {
+    /// Initializes a length, expanding a scalar `x` or `y`.
+    fn new(x: DArray, y: DArray) -> HypotComp {
+        HypotComp {x: expand_array(x.clone(), &y), y: expand_array(y, &x)}
+    }
+
+    /// The derivatives are `x / h` and `y / h`, where `h` is the length. Both are zero at the origin.
+    fn derivatives_by_length(&self, length: &DArray, res_grads: DArray) -> Vec<DArray> {
+        // Bounding the length away from zero only changes the derivatives at the origin, where both are zero.
+        let scaled_grads = res_grads / length.max(f64::MIN_POSITIVE);
+        vec![&scaled_grads * &self.x, &scaled_grads * &self.y]
+    }
+}
+
+impl Computation for HypotComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.x.clone(), self.y.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        self.derivatives_by_length(&DArray::from(self.clone()), res_grads)
+    }
+
+    /// Reuses the result as the length, instead of calculating it again.
+    fn derivatives_with_result(&self, res: &DArray, res_grads: DArray) -> Vec<DArray> {
+        self.derivatives_by_length(res, res_grads)
+    }
+
+    fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        for (res, x, y) in izip!(res_array.iter_mut(), self.x.data(), self.y.data()) {
+            *res += x.hypot(*y);
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.x, &self.y)
+    }
+}
+
+impl DArray {
+    /// Returns the pointwise length `sqrt(x^2 + y^2)` of the points `(x, y)`, with `self` as `x`,
+    /// without overflowing for large values.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn hypot(&self, y: &DArray) -> DArray {
+        DArray::from(HypotComp::new(self.clone(), y.clone()))
+    }
+}
+
+/// A computation picking every element from one of two arrays, by whether the element of a mask is nonzero.
+#[derive(Clone)]
+pub struct SelectComp {
+    mask: DArray,
+    on_true: DArray,
+    on_false: DArray,
+}
+
+impl SelectComp {
+    /// Initializes a selection, expanding scalar branches.
+    fn new(mask: DArray, on_true: DArray, on_false: DArray) -> SelectComp {
+        SelectComp {on_true: expand_array(on_true, &mask), on_false: expand_array(on_false, &mask), mask}
+    }
+}
+
+impl Computation for SelectComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.mask.clone(), self.on_true.clone(), self.on_false.clone()]
+    }
+
+    /// The gradients are routed to the chosen branch, and the derivative by the mask is zero.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let zeros = DArray::with_backend(vec![0.; self.len()], self.mask.backend());
+        vec![
+            zeros.clone(),
+            DArray::select(&self.mask, &res_grads, &zeros),
+            DArray::select(&self.mask, &zeros, &res_grads),
+        ]
+    }
+
+    fn len(&self) -> usize {
+        self.mask.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        for (res, mask, on_true, on_false) in izip!(res_array.iter_mut(), self.mask.data(), self.on_true.data(), self.on_false.data()) {
+            *res += if *mask != 0. { *on_true } else { *on_false };
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.mask, &self.on_true)?;
+        validate_same_len(&self.mask, &self.on_false)
+    }
+}
+
+impl DArray {
+    /// Picks every element from `on_true` where the mask is nonzero, and from `on_false` otherwise.
+    /// Unlike multiplying the branches by masks, `NaN` values in the unchosen branch don't reach the result.
+    /// Scalar branches are expanded to the length of the mask.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn select(mask: &DArray, on_true: &DArray, on_false: &DArray) -> DArray {
+        DArray::from(SelectComp::new(mask.clone(), on_true.clone(), on_false.clone()))
+    }
+}
+
+/// A computation replacing the elements of an array where a mask is nonzero with a constant.
+#[derive(Clone)]
+pub struct MaskedFillComp {
+    src: DArray,
+    mask: DArray,
+    value: f64,
+}
+
+impl Computation for MaskedFillComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone(), self.mask.clone()]
+    }
+
+    /// The gradients of the filled elements are blocked, and the derivative by the mask is zero.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let zeros = DArray::with_backend(vec![0.; self.len()], self.mask.backend());
+        vec![res_grads.masked_fill(&self.mask, 0.), zeros]
+    }
+
+    fn len(&self) -> usize {
+        self.src.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        for (res, src, mask) in izip!(res_array.iter_mut(), self.src.data(), self.mask.data()) {
+            *res += if *mask != 0. { self.value } else { *src };
+        }
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Unary
+    }
+
+    fn apply_on_zero(&self, res_array: &mut [f64]) {
+        if self.src.is_initialized() {
+            self.apply(res_array);
+        } else {
+            self.src.comp().apply_on_zero(res_array);
+            for (res, mask) in res_array.iter_mut().zip(self.mask.data()) {
+                if *mask != 0. {
+                    *res = self.value;
+                }
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.src, &self.mask)
+    }
+}
+
+impl DArray {
+    /// Replaces the elements where the mask is nonzero with the given value, as used for attention masks
+    /// and padding. The gradients of the replaced elements are zero.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn masked_fill(&self, mask: &DArray, value: f64) -> DArray {
+        DArray::from(MaskedFillComp {src: self.clone(), mask: mask.clone(), value})
+    }
+}
+
+/// A pointwise comparison of two values.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+enum Comparison {
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    Eq,
+}
+
+impl Comparison {
+    fn compare(&self, p1: f64, p2: f64) -> bool {
+        match self {
+            Comparison::Gt => p1 > p2,
+            Comparison::Lt => p1 < p2,
+            Comparison::Ge => p1 >= p2,
+            Comparison::Le => p1 <= p2,
+            Comparison::Eq => p1 == p2,
+        }
+    }
+}
+
+/// A computation comparing two arrays pointwise, returning ones where the comparison holds and zeros otherwise.
+#[derive(Clone)]
+pub struct CompareComp {
+    p1: DArray,
+    p2: DArray,
+    comparison: Comparison,
+}
+
+impl CompareComp {
+    /// Initializes a comparison, expanding a scalar array compared to an array.
+    fn new(p1: DArray, p2: DArray, comparison: Comparison) -> CompareComp {
+        CompareComp {p1: expand_array(p1.clone(), &p2), p2: expand_array(p2, &p1), comparison}
+    }
+}
+
+impl Computation for CompareComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.p1.clone(), self.p2.clone()]
+    }
+
+    /// The masks are piecewise constant, so the derivatives are zero.
+    fn derivatives(&self, _: DArray) -> Vec<DArray> {
+        let zeros = DArray::with_backend(vec![0.; self.len()], self.p1.backend());
+        vec![zeros.clone(), zeros]
+    }
+
+    fn len(&self) -> usize {
+        self.p1.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        for (res, p1, p2) in izip!(res_array.iter_mut(), self.p1.data(), self.p2.data()) {
+            if self.comparison.compare(*p1, *p2) {
+                *res += 1.;
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.p1, &self.p2)
+    }
+}
+
+impl DArray {
+    /// Returns an array with ones where `self` is larger than `other` and zeros otherwise.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn gt_array(&self, other: &DArray) -> DArray {
+        DArray::from(CompareComp::new(self.clone(), other.clone(), Comparison::Gt))
+    }
+    /// Returns an array with ones where `self` is smaller than `other` and zeros otherwise.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn lt_array(&self, other: &DArray) -> DArray {
+        DArray::from(CompareComp::new(self.clone(), other.clone(), Comparison::Lt))
+    }
+    /// Returns an array with ones where `self` is larger than or equal to `other` and zeros otherwise.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn ge_array(&self, other: &DArray) -> DArray {
+        DArray::from(CompareComp::new(self.clone(), other.clone(), Comparison::Ge))
+    }
+    /// Returns an array with ones where `self` is smaller than or equal to `other` and zeros otherwise.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn le_array(&self, other: &DArray) -> DArray {
+        DArray::from(CompareComp::new(self.clone(), other.clone(), Comparison::Le))
+    }
+    /// Returns an array with ones where `self` is equal to `other` and zeros otherwise.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn eq_array(&self, other: &DArray) -> DArray {
+        DArray::from(CompareComp::new(self.clone(), other.clone(), Comparison::Eq))
+    }
+}
+
+/// A coefficient of an affine computation, either a constant or a scalar array.
+#[derive(Clone)]
+pub enum AffineCoef {
+    Const(f64),
+    Array(DArray),
+}
+
+impl AffineCoef {
+    /// Returns the value of the coefficient, evaluating it if needed.
+    fn value(&self) -> f64 {
+        match self {
+            AffineCoef::Const(cons) => *cons,
+            AffineCoef::Array(array) => array.data()[0],
+        }
+    }
+}
+
+impl From<f64> for AffineCoef {
+    fn from(cons: f64) -> Self {
+        AffineCoef::Const(cons)
+    }
+}
+
+impl From<DArray> for AffineCoef {
+    fn from(array: DArray) -> Self {
+        AffineCoef::Array(array)
+    }
+}
+
+impl From<&DArray> for AffineCoef {
+    fn from(array: &DArray) -> Self {
+        AffineCoef::Array(array.clone())
+    }
+}
+
+/// A computation calculating `scale * src + shift` in a single pass, where the scale and the shift
+/// are constants or scalar arrays.
+#[derive(Clone)]
+pub struct AffineComp {
+    src: DArray,
+    scale: AffineCoef,
+    shift: AffineCoef,
+}
+
+impl AffineComp {
+    pub fn new(src: DArray, scale: AffineCoef, shift: AffineCoef) -> AffineComp {
+        AffineComp {src, scale, shift}
+    }
+}
+
+impl Computation for AffineComp {
+    /// The sources are the transformed array, followed by the coefficients which are arrays.
+    fn sources(&self) -> Vec<DArray> {
+        let mut sources = vec![self.src.clone()];
+        for coef in [&self.scale, &self.shift] {
+            if let AffineCoef::Array(array) = coef {
+                sources.push(array.clone());
+            }
+        }
+        sources
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let mut derivatives = vec![DArray::from(AffineComp::new(res_grads.clone(), self.scale.clone(), AffineCoef::Const(0.)))];
+        if let AffineCoef::Array(_) = self.scale {
+            derivatives.push((&res_grads * &self.src).sum());
+        }
+        if let AffineCoef::Array(_) = self.shift {
+            derivatives.push(res_grads.sum());
+        }
+        derivatives
+    }
+
+    fn len(&self) -> usize {
+        self.src.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let (scale, shift) = (self.scale.value(), self.shift.value());
+        for (res, src) in res_array.iter_mut().zip(self.src.data()) {
+            *res += scale * src + shift;
+        }
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Unary
+    }
+
+    fn apply_on_zero(&self, res_array: &mut [f64]) {
+        if self.src.is_initialized() {
+            self.apply(res_array);
+        } else {
+            self.src.comp().apply_on_zero(res_array);
+            self.src.backend().affine(res_array, self.scale.value(), self.shift.value());
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 2 * self.len(), bytes_read: self.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (name, coef) in [("scale", &self.scale), ("shift", &self.shift)] {
+            if let AffineCoef::Array(array) = coef {
+                if !array.is_scalar() {
+                    return Err(format!("The {} must be a scalar, but has length {}.", name, array.len()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DArray {
+    /// Returns `scale * self + shift`, calculated in a single computation.
+    /// The scale and the shift can be constants or scalar arrays.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn affine(&self, scale: impl Into<AffineCoef>, shift: impl Into<AffineCoef>) -> DArray {
+        DArray::from(AffineComp::new(self.clone(), scale.into(), shift.into()))
+    }
+}
+
+// Operators with a float on the left hand side.
+// The orphan rules don't allow implementing them generically over `DArrayRef`, so they are implemented
+// for both `DArray` and `&DArray`.
+impl Add<DArray> for f64 {
+    type Output = DArray;
+
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn add(self, rhs: DArray) -> Self::Output {
+        rhs + self
     }
 }
-impl <Other: DArrayRef> Div<Other> for &DArray {
+impl Add<&DArray> for f64 {
     type Output = DArray;
 
-    fn div(self, rhs: Other) -> Self::Output {
-        self * rhs.into().powi(-1)
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn add(self, rhs: &DArray) -> Self::Output {
+        rhs + self
+    }
+}
+
+impl Sub<DArray> for f64 {
+    type Output = DArray;
+
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn sub(self, rhs: DArray) -> Self::Output {
+        rhs.affine(-1., self)
+    }
+}
+impl Sub<&DArray> for f64 {
+    type Output = DArray;
+
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn sub(self, rhs: &DArray) -> Self::Output {
+        rhs.affine(-1., self)
+    }
+}
+
+impl Mul<DArray> for f64 {
+    type Output = DArray;
+
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn mul(self, rhs: DArray) -> Self::Output {
+        rhs * self
+    }
+}
+impl Mul<&DArray> for f64 {
+    type Output = DArray;
+
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn mul(self, rhs: &DArray) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl Div<DArray> for f64 {
+    type Output = DArray;
+
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn div(self, rhs: DArray) -> Self::Output {
+        rhs.recip_scaled(self)
+    }
+}
+impl Div<&DArray> for f64 {
+    type Output = DArray;
+
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn div(self, rhs: &DArray) -> Self::Output {
+        rhs.recip_scaled(self)
+    }
+}
+
+/// A trait for derivable functions of two floats, mirroring [`DerivableOp`].
+/// Used to more easily implement pointwise functions of two arrays.
+///
+/// [`DerivableOp`]: crate::unary_functions::DerivableOp
+pub trait DerivableBinaryOp : Clone + 'static {
+    type LhsDerivative: DerivableBinaryOp;
+    type RhsDerivative: DerivableBinaryOp;
+
+    /// Applies the function to two floats.
+    fn apply(&self, p1: &f64, p2: &f64) -> f64;
+    /// Calculates the derivative of the function by its first argument.
+    fn lhs_derivative(&self) -> Self::LhsDerivative;
+    /// Calculates the derivative of the function by its second argument.
+    fn rhs_derivative(&self) -> Self::RhsDerivative;
+}
+
+/// A computation handling generic differentiable functions applied pointwise to two arrays.
+#[derive(Clone)]
+pub struct BinaryComp<Op: DerivableBinaryOp> {
+    p1: DArray,
+    p2: DArray,
+    /// The function applied to the arrays.
+    op: Op,
+}
+
+impl<Op: DerivableBinaryOp> BinaryComp<Op> {
+    /// Initializes a new binary computation object, expanding a scalar array applied with an array.
+    pub fn new(p1: DArray, p2: DArray, op: Op) -> BinaryComp<Op> {
+        BinaryComp {p1: expand_array(p1.clone(), &p2), p2: expand_array(p2, &p1), op}
+    }
+}
+
+impl<Op: DerivableBinaryOp> Computation for BinaryComp<Op> {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.p1.clone(), self.p2.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![
+            self.p1.map_binary(&self.p2, self.op.lhs_derivative()) * &res_grads,
+            self.p1.map_binary(&self.p2, self.op.rhs_derivative()) * &res_grads,
+        ]
+    }
+
+    fn len(&self) -> usize {
+        self.p1.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        for (res, p1, p2) in izip!(res_array.iter_mut(), self.p1.data(), self.p2.data()) {
+            *res += self.op.apply(p1, p2);
+        }
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Binary
+    }
+
+    fn apply_on_zero(&self, res_array: &mut [f64]) {
+        if !self.p1.is_initialized() {
+            self.p1.comp().apply_on_zero(res_array);
+            for (res, p2) in res_array.iter_mut().zip(self.p2.data()) {
+                *res = self.op.apply(res, p2);
+            }
+        } else {
+            self.p2.comp().apply_on_zero(res_array);
+            for (res, p1) in res_array.iter_mut().zip(self.p1.data()) {
+                *res = self.op.apply(p1, res);
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.p1, &self.p2)
+    }
+}
+
+impl DArray {
+    /// Applies a derivable function pointwise to two arrays, expanding a scalar applied with an array.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn map_binary(&self, other: &DArray, op: impl DerivableBinaryOp) -> DArray {
+        DArray::from(BinaryComp::new(self.clone(), other.clone(), op))
+    }
+}
+
+/// Combines the arrays pairwise in a balanced tree, so that the depth of the resulting graph is
+/// logarithmic in the number of arrays instead of linear.
+/// Returns `empty` if there are no arrays.
+#[cfg_attr(feature = "debug-provenance", track_caller)]
+fn reduce_balanced(mut arrays: Vec<DArray>, empty: f64, op: impl Fn(&DArray, &DArray) -> DArray) -> DArray {
+    if arrays.is_empty() {
+        return DArray::from(empty);
+    }
+    while arrays.len() > 1 {
+        arrays = arrays.chunks(2)
+            .map(|pair| match pair {
+                [a, b] => op(a, b),
+                [a] => a.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    arrays.pop().unwrap()
+}
+
+/// Sums the arrays in a single computation if they have the same length, and by broadcasting scalars otherwise.
+/// Returns zero if there are no arrays.
+#[cfg_attr(feature = "debug-provenance", track_caller)]
+fn sum_arrays(arrays: Vec<DArray>) -> DArray {
+    if !arrays.is_empty() && arrays.iter().all(|array| array.len() == arrays[0].len()) {
+        DArray::add_n(&arrays)
+    } else {
+        reduce_balanced(arrays, 0., |a, b| a + b)
+    }
+}
+
+impl Sum<DArray> for DArray {
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn sum<I: Iterator<Item = DArray>>(iter: I) -> Self {
+        sum_arrays(iter.collect())
+    }
+}
+
+impl<'t> Sum<&'t DArray> for DArray {
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn sum<I: Iterator<Item = &'t DArray>>(iter: I) -> Self {
+        sum_arrays(iter.cloned().collect())
+    }
+}
+
+impl Product<DArray> for DArray {
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn product<I: Iterator<Item = DArray>>(iter: I) -> Self {
+        reduce_balanced(iter.collect(), 1., |a, b| a * b)
+    }
+}
+
+impl<'t> Product<&'t DArray> for DArray {
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn product<I: Iterator<Item = &'t DArray>>(iter: I) -> Self {
+        reduce_balanced(iter.cloned().collect(), 1., |a, b| a * b)
     }
 }
 
@@ -325,6 +1572,8 @@ impl <Other: DArrayRef> Div<Other> for &DArray {
 #[cfg(test)]
 mod tests {
     use crate::array::DArray;
+    use crate::binary_functions::{AddNComp, DerivableBinaryOp};
+    use crate::computation::{Computation, NodeComp};
     use crate::test_utils::*;
 
 
@@ -379,4 +1628,421 @@ mod tests {
     fn test_div() {
         test_binary(|array1, array2| array1 / array2);
     }
+
+    /// Tests the operators with a float on the left hand side.
+    #[test]
+    fn test_lhs_float() {
+        let mut rng = StdRng::from_seed(SEED);
+        for _ in 0..10 {
+            let v = random_vec(&mut rng, 3);
+            let array = DArray::from(v.clone());
+
+            for (i, x) in v.iter().enumerate() {
+                assert_close((2. + &array).data()[i], 2. + x);
+                assert_close((2. - &array).data()[i], 2. - x);
+                assert_close((2. * &array).data()[i], 2. * x);
+                assert_close((2. / &array).data()[i], 2. / x);
+            }
+
+            assert_gradients(|arrays| (1. + &arrays[0]).powi(2).sum(), &[&v]);
+            assert_gradients(|arrays| (1. - &arrays[0]).powi(2).sum(), &[&v]);
+            assert_gradients(|arrays| (3. * &arrays[0]).powi(2).sum(), &[&v]);
+            assert_gradients(|arrays| (3. / &arrays[0]).sum(), &[&v]);
+        }
+    }
+
+    /// Tests that constants are stored in the computations using them, instead of in separate arrays.
+    #[test]
+    fn test_embedded_constants() {
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 5);
+        let array = DArray::from(v.clone());
+        let res = (&array + 2.) - 1.;
+
+        assert_eq!(res.topological_sort().len(), 3);
+        for (i, x) in v.iter().enumerate() {
+            assert_close(res.data()[i], x + 1.);
+        }
+        assert_gradients(|arrays| ((&arrays[0] + 2.).powi(2) - 3.).sum(), &[&v]);
+    }
+
+    /// Tests the operators with vectors and computations on the right hand side.
+    #[test]
+    fn test_rhs_conversions() {
+        let array = DArray::from(vec![1., 2., 4.]);
+        assert_eq!((&array + vec![1., 1., 2.]).data(), &[2., 3., 6.]);
+        assert_eq!((array.clone() - vec![1., 1., 2.]).data(), &[0., 1., 2.]);
+        assert_eq!((&array * vec![2., 0., 1.]).data(), &[2., 0., 4.]);
+        assert_eq!((&array + AddNComp {arrays: vec![array.clone(), array.clone()]}).data(), &[3., 6., 12.]);
+    }
+
+    /// Tests multiplication by a scalar array, evaluated on zero and otherwise.
+    #[test]
+    fn test_mul_scalar_values() {
+        let array = DArray::from(vec![1., 2., 4.]);
+        let scalar = DArray::from(3.);
+        assert_eq!((&array * &scalar).data(), &[3., 6., 12.]);
+        assert_eq!(((&array + 1.) * &scalar).data(), &[6., 9., 15.]);
+        // The second source of an addition is applied without zeroing the result first.
+        assert_eq!(((&array + 1.) + &array * &scalar).data(), &[5., 9., 17.]);
+    }
+
+    /// Tests subtraction of arrays and scalars, evaluated on zero and otherwise.
+    #[test]
+    fn test_sub_values() {
+        let mut rng = StdRng::from_seed(SEED);
+        let v1 = random_vec(&mut rng, 5);
+        let v2 = random_vec(&mut rng, 5);
+        let (array_1, array_2) = (DArray::from(v1.clone()), DArray::from(v2.clone()));
+        let scalar = DArray::from(v2[0]);
+
+        let res = (&array_1 - &array_2) + (&array_1.sin() - &array_2.sin());
+        let by_scalar = &array_1 - &scalar;
+        let of_scalar = (&scalar - &array_1.sin()).sin();
+        for i in 0..5 {
+            assert_close(res.data()[i], v1[i] - v2[i] + v1[i].sin() - v2[i].sin());
+            assert_close(by_scalar.data()[i], v1[i] - v2[0]);
+            assert_close(of_scalar.data()[i], (v2[0] - v1[i].sin()).sin());
+        }
+        assert_eq!(by_scalar.topological_sort().len(), 3);
+
+        assert_gradients(|arrays| (&arrays[0] - &arrays[1]).powi(2).sum(), &[&v1, &v2]);
+        assert_gradients(|arrays| (&arrays[0] - arrays[1].index(0)).powi(2).sum(), &[&v1, &v2]);
+        assert_gradients(|arrays| (arrays[1].index(0) - &arrays[0]).powi(2).sum(), &[&v1, &v2]);
+    }
+
+    /// Tests that division is calculated directly, and not through the reciprocal of the denominator.
+    #[test]
+    fn test_div_values() {
+        let mut rng = StdRng::from_seed(SEED);
+        let v1 = random_vec(&mut rng, 5);
+        let v2 = random_vec(&mut rng, 5);
+        let res = DArray::from(v1.clone()) / DArray::from(v2.clone());
+        let by_scalar = DArray::from(v1.clone()) / DArray::from(v2[0]);
+        let of_scalar = DArray::from(v1[0]) / DArray::from(v2.clone());
+
+        for i in 0..5 {
+            assert_eq!(res.data()[i], v1[i] / v2[i]);
+            assert_eq!(by_scalar.data()[i], v1[i] / v2[0]);
+            assert_eq!(of_scalar.data()[i], v1[0] / v2[i]);
+        }
+        assert_gradients(|arrays| (&arrays[0] / &arrays[1]).sum(), &[&v1, &v2]);
+        assert_gradients(|arrays| (&arrays[0] / arrays[1].index(0)).sum(), &[&v1, &v2]);
+        assert_gradients(|arrays| {
+            let res = (&arrays[0] / &arrays[1]).sum();
+            res.derive().get(&arrays[1]).unwrap().sum()
+        }, &[&v1, &v2]);
+    }
+
+    #[test]
+    fn test_rem() {
+        let res = DArray::from(vec![7., -7., 7.5]).rem(&DArray::from(vec![3., 3., -2.]));
+        assert_eq!(res.data(), &[1., -1., 1.5]);
+        assert_eq!(DArray::from(vec![7., -7.]).rem(&DArray::from(3.)).data(), &[1., -1.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v1 = random_vec(&mut rng, 5);
+        let v2 = random_vec(&mut rng, 5);
+        assert_gradients(|arrays| arrays[0].rem(&arrays[1]).powi(2).sum(), &[&v1, &v2]);
+    }
+
+    #[test]
+    fn test_pow() {
+        let res = DArray::from(vec![4., -2., 0.]).pow(&DArray::from(vec![1.5, 3., 2.]));
+        assert_eq!(res.data(), &[8., -8., 0.]);
+        assert_eq!(DArray::from(vec![2., 3.]).pow(&DArray::from(2.)).data(), &[4., 9.]);
+        assert_eq!(DArray::from(2.).pow(&DArray::from(vec![2., 3.])).data(), &[4., 8.]);
+
+        // The derivative by the exponent vanishes at a zero base.
+        let base = DArray::from(vec![0., -2.]);
+        let exponent = DArray::from(vec![2., 3.]);
+        let grads = base.pow(&exponent).sum().derive();
+        assert_eq!(grads.get(&base).unwrap().data(), &[0., 12.]);
+        assert_eq!(grads.get(&exponent).unwrap().data()[0], 0.);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v1: Vec<f64> = random_vec(&mut rng, 5).iter().map(|x| x.abs() + 0.1).collect();
+        let v2 = random_vec(&mut rng, 5);
+        assert_gradients(|arrays| arrays[0].pow(&arrays[1]).sum(), &[&v1, &v2]);
+        assert_gradients(|arrays| arrays[0].pow(&arrays[1]).sum(), &[&v1, &vec![0.7]]);
+        // Tests the second derivatives.
+        assert_gradients(|arrays| {
+            let grads = arrays[0].pow(&arrays[1]).sum().derive();
+            (grads.get(&arrays[0]).unwrap() + grads.get(&arrays[1]).unwrap()).sum()
+        }, &[&v1, &v2]);
+    }
+
+    #[test]
+    fn test_atan2() {
+        let y = DArray::from(vec![1., 1., -1., 1e-20, -1e-20]);
+        let x = DArray::from(vec![1., -1., -1., -1., -1.]);
+        let res = y.atan2(&x);
+        let pi = std::f64::consts::PI;
+        for (res, expected) in res.data().iter().zip([pi / 4., 3. * pi / 4., -3. * pi / 4., pi, -pi]) {
+            assert_close(*res, expected);
+        }
+        // The derivatives are continuous across the jump of the angle.
+        let grads = res.sum().derive();
+        assert_eq!(&grads.get(&y).unwrap().data()[3..], &[-1., -1.]);
+        assert_eq!(&grads.get(&x).unwrap().data()[3..], &[-1e-20, 1e-20]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v1 = random_vec(&mut rng, 5);
+        let v2 = random_vec(&mut rng, 5);
+        assert_gradients(|arrays| arrays[0].atan2(&arrays[1]).sum(), &[&v1, &v2]);
+        assert_gradients(|arrays| arrays[0].atan2(&arrays[1]).sum(), &[&v1, &vec![-0.5]]);
+    }
+
+    #[test]
+    fn test_hypot() {
+        let x = DArray::from(vec![3., 1e200, 0.]);
+        let y = DArray::from(vec![-4., 1e200, 0.]);
+        let res = x.hypot(&y);
+        assert_eq!(res.data()[0], 5.);
+        assert_close(res.data()[1], 2_f64.sqrt() * 1e200);
+        assert_eq!(res.data()[2], 0.);
+
+        let grads = res.sum().derive();
+        // The derivatives reuse the length instead of calculating it again.
+        assert!(grads[&x].topological_sort().contains(&res));
+        let expected = [(0.6, -0.8), (std::f64::consts::FRAC_1_SQRT_2, std::f64::consts::FRAC_1_SQRT_2), (0., 0.)];
+        for ((x_grad, y_grad), (x_expected, y_expected)) in grads[&x].data().iter().zip(grads[&y].data()).zip(expected) {
+            assert_close(*x_grad, x_expected);
+            assert_close(*y_grad, y_expected);
+        }
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v1 = random_vec(&mut rng, 5);
+        let v2 = random_vec(&mut rng, 5);
+        assert_gradients(|arrays| arrays[0].hypot(&arrays[1]).sum(), &[&v1, &v2]);
+        assert_gradients(|arrays| arrays[0].hypot(&arrays[1]).sum(), &[&v1, &vec![-0.5]]);
+    }
+
+    #[test]
+    fn test_select() {
+        let x = DArray::from(vec![-1., 0.5, 4.]);
+        let mask = x.gt(1.);
+        let res = DArray::select(&mask, &x.ln(), &DArray::from(-5.));
+        assert_eq!(res.data(), &[-5., -5., 4_f64.ln()]);
+
+        let grads = res.sum().derive();
+        assert_eq!(grads[&x].data(), &[0., 0., 0.25]);
+        assert_eq!(grads[&mask].data(), &[0., 0., 0.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v1 = random_vec(&mut rng, 5);
+        let v2 = random_vec(&mut rng, 5);
+        let mask = DArray::from(vec![1., 0., 0., 1., 1.]);
+        assert_gradients(|arrays| DArray::select(&mask, &arrays[0], &arrays[1]).powi(2).sum(), &[&v1, &v2]);
+        assert_gradients(|arrays| DArray::select(&mask, &arrays[0].sin(), &arrays[1]).sum(), &[&v1, &vec![0.5]]);
+    }
+
+    #[test]
+    fn test_compare() {
+        let a = DArray::from(vec![1., 2., 3.]);
+        let b = DArray::from(vec![3., 2., 1.]);
+        assert_eq!(a.gt_array(&b).data(), &[0., 0., 1.]);
+        assert_eq!(a.lt_array(&b).data(), &[1., 0., 0.]);
+        assert_eq!(a.ge_array(&b).data(), &[0., 1., 1.]);
+        assert_eq!(a.le_array(&b).data(), &[1., 1., 0.]);
+        assert_eq!(a.eq_array(&b).data(), &[0., 1., 0.]);
+        assert_eq!(a.gt_array(&DArray::from(1.5)).data(), &[0., 1., 1.]);
+        assert_eq!(DArray::from(1.5).gt_array(&a).data(), &[1., 0., 0.]);
+
+        // A piecewise loss, choosing the larger of two quantities.
+        let mask = a.gt_array(&b);
+        let res = (&mask * &a + (1. - &mask) * &b).sum();
+        assert_eq!(res.data(), &[8.]);
+        let grads = res.derive();
+        assert_eq!(grads[&a].data(), &[0., 0., 1.]);
+        assert_eq!(grads[&b].data(), &[1., 1., 0.]);
+    }
+
+    /// The function `coef * a^m * b^n`, with the derivatives of the same form.
+    #[derive(Copy, Clone)]
+    struct PowProdFunc {
+        coef: f64,
+        m: i32,
+        n: i32,
+    }
+
+    impl DerivableBinaryOp for PowProdFunc {
+        type LhsDerivative = PowProdFunc;
+        type RhsDerivative = PowProdFunc;
+
+        fn apply(&self, p1: &f64, p2: &f64) -> f64 {
+            self.coef * p1.powi(self.m) * p2.powi(self.n)
+        }
+
+        fn lhs_derivative(&self) -> Self::LhsDerivative {
+            PowProdFunc {coef: self.coef * self.m as f64, m: self.m - 1, n: self.n}
+        }
+
+        fn rhs_derivative(&self) -> Self::RhsDerivative {
+            PowProdFunc {coef: self.coef * self.n as f64, m: self.m, n: self.n - 1}
+        }
+    }
+
+    #[test]
+    fn test_map_binary() {
+        let op = PowProdFunc {coef: 2., m: 2, n: 3};
+        let a = DArray::from(vec![1., -2.]);
+        let b = DArray::from(vec![2., 1.]);
+        assert_eq!(a.map_binary(&b, op).data(), &[16., 8.]);
+        assert_eq!(a.map_binary(&DArray::from(2.), op).data(), &[16., 64.]);
+        // Applied on zero through either source.
+        let res = (&a + 1.).map_binary(&b, op);
+        assert_eq!(res.data(), &[op.apply(&2., &2.), op.apply(&-1., &1.)]);
+        let res = b.map_binary(&(&a + 1.), op);
+        assert_eq!(res.data(), &[op.apply(&2., &2.), op.apply(&1., &-1.)]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v1 = random_vec(&mut rng, 5);
+        let v2 = random_vec(&mut rng, 5);
+        assert_gradients(|arrays| arrays[0].map_binary(&arrays[1], op).sum(), &[&v1, &v2]);
+        assert_gradients(|arrays| arrays[0].map_binary(&arrays[1], op).sum(), &[&v1, &vec![0.5]]);
+        // Tests the second derivatives.
+        assert_gradients(|arrays| {
+            let grads = arrays[0].map_binary(&arrays[1], op).sum().derive();
+            (grads.get(&arrays[0]).unwrap() * grads.get(&arrays[1]).unwrap()).sum()
+        }, &[&v1, &v2]);
+    }
+
+    #[test]
+    fn test_fma() {
+        let a = DArray::from(vec![1., 2., 3.]);
+        let b = DArray::from(vec![-1., 0.5, 2.]);
+        let c = DArray::from(vec![4., 5., 6.]);
+        assert_eq!(a.fma(&b, &c).data(), &[3., 6., 12.]);
+
+        // Temporary products are fused with the addition, but products used elsewhere are not.
+        let fused = &a * &b + &c;
+        assert!(matches!(fused.comp(), NodeComp::Fma(_)));
+        assert_eq!(fused.data(), &[3., 6., 12.]);
+        assert!(matches!((&c + &a * &b).comp(), NodeComp::Fma(_)));
+        let product = &a * &b;
+        assert!(matches!((&product + &c).comp(), NodeComp::Add(_)));
+        assert!(matches!((product + &c).comp(), NodeComp::Fma(_)));
+
+        // The addend is applied on zero.
+        let res = (&a * &b + (&c + 1.)).sum();
+        assert_eq!(res.data(), &[24.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v1 = random_vec(&mut rng, 5);
+        let v2 = random_vec(&mut rng, 5);
+        let v3 = random_vec(&mut rng, 5);
+        assert_gradients(|arrays| arrays[0].fma(&arrays[1], &arrays[2]).powi(2).sum(), &[&v1, &v2, &v3]);
+        assert_gradients(|arrays| (&arrays[0] * &arrays[1] + arrays[2].sin()).powi(2).sum(), &[&v1, &v2, &v3]);
+    }
+
+    #[test]
+    fn test_lerp() {
+        let a = DArray::from(vec![1., 2., 3.]);
+        let b = DArray::from(vec![3., 0., 3.]);
+        assert_eq!(a.lerp(&b, &DArray::from(vec![0., 0.25, 1.])).data(), &[1., 1.5, 3.]);
+        assert_eq!(a.lerp(&b, &DArray::from(0.5)).data(), &[2., 1., 3.]);
+        assert_eq!(DArray::from(1.).lerp(&b, &DArray::from(vec![0., 0.5, 1.])).data(), &[1., 0.5, 3.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v1 = random_vec(&mut rng, 5);
+        let v2 = random_vec(&mut rng, 5);
+        let v3 = random_vec(&mut rng, 5);
+        assert_gradients(|arrays| arrays[0].lerp(&arrays[1], &arrays[2]).powi(2).sum(), &[&v1, &v2, &v3]);
+        assert_gradients(|arrays| arrays[0].lerp(&arrays[1], &arrays[2]).powi(2).sum(), &[&v1, &v2, &vec![0.3]]);
+    }
+
+    #[test]
+    fn test_masked_fill() {
+        let scores = DArray::from(vec![1., 2., 3., 4.]);
+        let padding = DArray::from(vec![0., 1., 0., 1.]);
+        let res = scores.masked_fill(&padding, f64::NEG_INFINITY);
+        assert_eq!(res.data(), &[1., f64::NEG_INFINITY, 3., f64::NEG_INFINITY]);
+        // Applied on zero.
+        assert_eq!((&scores + 1.).masked_fill(&padding, 0.).data(), &[2., 0., 4., 0.]);
+
+        let grads = (scores.masked_fill(&padding, 0.) * &scores).sum().derive();
+        assert_eq!(grads[&scores].data(), &[2., 0., 6., 0.]);
+        assert_eq!(grads[&padding].data(), &[0.; 4]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 4);
+        assert_gradients(|arrays| arrays[0].sin().masked_fill(&padding, 2.).powi(2).sum(), &[&v]);
+    }
+
+    /// Tests affine computations with constant and array coefficients.
+    #[test]
+    fn test_affine() {
+        let mut rng = StdRng::from_seed(SEED);
+        for _ in 0..10 {
+            let v = random_vec(&mut rng, 5);
+            let coefs = random_vec(&mut rng, 2);
+            let res = DArray::from(v.clone()).affine(coefs[0], DArray::from(coefs[1]));
+            for (i, x) in v.iter().enumerate() {
+                assert_close(res.data()[i], coefs[0] * x + coefs[1]);
+            }
+
+            assert_gradients(|arrays| arrays[0].affine(2., -1.).powi(2).sum(), &[&v]);
+            assert_gradients(|arrays| {
+                arrays[0].affine(arrays[1].index(0), arrays[1].index(1)).powi(2).sum()
+            }, &[&v, &coefs]);
+            // The derivatives are affine computations themselves, and can be derived again.
+            assert_gradients(|arrays| {
+                let res = arrays[0].affine(arrays[1].index(0), 1.).powi(2).sum();
+                res.derive().get(&arrays[0]).unwrap().sum()
+            }, &[&v, &coefs]);
+        }
+    }
+
+    /// Tests summing and multiplying iterators of arrays.
+    #[test]
+    fn test_iter_sum_product() {
+        let mut rng = StdRng::from_seed(SEED);
+        let vecs: Vec<Vec<f64>> = (0..5).map(|_| random_vec(&mut rng, 3)).collect();
+        let arrays: Vec<DArray> = vecs.iter().cloned().map(DArray::from).collect();
+
+        let sum: DArray = arrays.iter().sum();
+        let product: DArray = arrays.clone().into_iter().product();
+        for i in 0..3 {
+            assert_close(sum.data()[i], vecs.iter().map(|v| v[i]).sum());
+            assert_close(product.data()[i], vecs.iter().map(|v| v[i]).product());
+        }
+
+        assert_eq!(std::iter::empty::<DArray>().sum::<DArray>().data(), &[0.]);
+        assert_eq!(std::iter::empty::<DArray>().product::<DArray>().data(), &[1.]);
+
+        assert_gradients(|arrays| arrays.iter().sum::<DArray>().powi(2).sum(), &vecs);
+        assert_gradients(|arrays| arrays.iter().product::<DArray>().sum(), &vecs);
+    }
+
+    #[test]
+    fn test_add_n() {
+        let mut rng = StdRng::from_seed(SEED);
+        let vecs: Vec<Vec<f64>> = (0..5).map(|_| random_vec(&mut rng, 3)).collect();
+        let arrays: Vec<DArray> = vecs.iter().cloned().map(DArray::from).collect();
+
+        let sum: DArray = arrays.iter().sum();
+        assert!(matches!(sum.comp(), NodeComp::AddN(_)));
+        assert_eq!(sum.comp().sources().len(), 5);
+
+        // Uninitialized sources are applied on the result.
+        let sum = DArray::add_n(&[arrays[0].sin(), &arrays[1] * &arrays[2], arrays[3].clone(), arrays[4].sin()]);
+        for (i, res) in sum.data().iter().enumerate() {
+            assert_close(*res, vecs[0][i].sin() + vecs[1][i] * vecs[2][i] + vecs[3][i] + vecs[4][i].sin());
+        }
+
+        // Scalars are broadcast before being summed.
+        let mixed: DArray = [arrays[0].clone(), DArray::from(2.), arrays[1].clone()].iter().sum();
+        assert_close(mixed.data()[0], vecs[0][0] + 2. + vecs[1][0]);
+
+        // The gradients of an array used several times are summed in a single computation.
+        let x = &arrays[0];
+        let grads = (x.sin() + x.cos() + x.exp()).sum().derive();
+        assert!(matches!(grads.get(x).unwrap().comp(), NodeComp::AddN(_)));
+        for (grad, x) in grads.get(x).unwrap().data().iter().zip(&vecs[0]) {
+            assert_close(*grad, x.cos() - x.sin() + x.exp());
+        }
+
+        assert_gradients(|arrays| DArray::add_n(arrays).powi(2).sum(), &vecs);
+    }
 }
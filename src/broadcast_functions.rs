@@ -0,0 +1,809 @@
+use crate::computation::{Computation, ComputationType, Cost, F64_SIZE};
+use crate::array::DArray;
+use crate::index_functions::{for_each_summand, IndexComp};
+
+/// The side of the square blocks in which matrices are transposed, so that the rows read and written
+/// by a block stay in the cache.
+const TRANSPOSE_BLOCK: usize = 32;
+
+/// An axis of a matrix stored in a flat array.
+/// Matrices are stored in row-major order, so the element `(i, j)` of a matrix with `cols` columns
+/// is stored at index `i * cols + j`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Axis {
+    /// The axis going over the rows of the matrix. Reducing along it leaves an element per column.
+    Rows,
+    /// The axis going over the columns of the matrix. Reducing along it leaves an element per row.
+    Cols,
+}
+
+impl Axis {
+    /// Returns the lengths of the matrix viewed as an `outer × len × inner` tensor, where `len` is the length of the axis.
+    fn split(&self, rows: usize, cols: usize) -> (usize, usize, usize) {
+        match self {
+            Axis::Rows => (1, rows, cols),
+            Axis::Cols => (rows, cols, 1),
+        }
+    }
+}
+
+/// A computation summing an axis of a row-major tensor.
+/// The tensor is viewed as an `outer × len × inner` tensor, where the summed axis has length `len`,
+/// `outer` is the product of the lengths of the axes before it and `inner` is the product of the lengths of the axes after it.
+#[derive(Clone)]
+pub struct SumAxisComp {
+    src: DArray,
+    outer: usize,
+    len: usize,
+    inner: usize,
+}
+
+impl SumAxisComp {
+    /// Sums a row-major `rows × cols` matrix along the axis.
+    pub fn new(src: DArray, rows: usize, cols: usize, axis: Axis) -> SumAxisComp {
+        SumAxisComp::from_split(src, axis.split(rows, cols))
+    }
+
+    /// Sums the middle axis of an `outer × len × inner` tensor.
+    pub fn from_split(src: DArray, (outer, len, inner): (usize, usize, usize)) -> SumAxisComp {
+        SumAxisComp {src, outer, len, inner}
+    }
+}
+
+impl Computation for SumAxisComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    /// The derivative of summing an axis repeats the gradients along it.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![DArray::from(BroadcastComp::from_split(res_grads, (self.outer, self.len, self.inner)))]
+    }
+
+    fn len(&self) -> usize {
+        self.outer * self.inner
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let (len, inner) = (self.len, self.inner);
+        if inner == 0 {
+            return;
+        }
+        for_each_summand(&self.src, &mut |data| {
+            for (res, block) in res_array.chunks_mut(inner).zip(data.chunks(len * inner)) {
+                if inner == 1 {
+                    res[0] += block.iter().sum::<f64>();
+                    continue;
+                }
+                for row in block.chunks(inner) {
+                    for (res, x) in res.iter_mut().zip(row) {
+                        *res += x;
+                    }
+                }
+            }
+        });
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Linear
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: self.src.len(), bytes_read: self.src.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.src.len() == self.outer * self.len * self.inner {
+            Ok(())
+        } else {
+            Err(format!("A source of length {} is not a {}×{}×{} tensor.", self.src.len(), self.outer, self.len, self.inner))
+        }
+    }
+}
+
+/// A computation repeating the elements of a row-major tensor along a new axis.
+/// The source is an `outer × inner` tensor, which is repeated to an `outer × len × inner` tensor.
+/// Broadcasting a row vector along the rows of a matrix repeats it in every row, and broadcasting a column vector
+/// along the columns repeats it in every column.
+#[derive(Clone)]
+pub struct BroadcastComp {
+    src: DArray,
+    outer: usize,
+    len: usize,
+    inner: usize,
+}
+
+impl BroadcastComp {
+    /// Repeats a vector along the axis of a row-major `rows × cols` matrix.
+    pub fn new(src: DArray, rows: usize, cols: usize, axis: Axis) -> BroadcastComp {
+        BroadcastComp::from_split(src, axis.split(rows, cols))
+    }
+
+    /// Repeats an `outer × inner` tensor to an `outer × len × inner` tensor.
+    pub fn from_split(src: DArray, (outer, len, inner): (usize, usize, usize)) -> BroadcastComp {
+        BroadcastComp {src, outer, len, inner}
+    }
+}
+
+impl Computation for BroadcastComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    /// The derivative of repeating along an axis sums the gradients along it.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![DArray::from(SumAxisComp::from_split(res_grads, (self.outer, self.len, self.inner)))]
+    }
+
+    fn len(&self) -> usize {
+        self.outer * self.len * self.inner
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let (len, inner) = (self.len, self.inner);
+        if len * inner == 0 {
+            return;
+        }
+        for_each_summand(&self.src, &mut |data| {
+            for (block, src) in res_array.chunks_mut(len * inner).zip(data.chunks(inner)) {
+                for row in block.chunks_mut(inner) {
+                    for (res, x) in row.iter_mut().zip(src) {
+                        *res += x;
+                    }
+                }
+            }
+        });
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Linear
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: self.len(), bytes_read: self.src.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let expected = self.outer * self.inner;
+        if self.src.len() == expected {
+            Ok(())
+        } else {
+            Err(format!("A source of length {} can't be broadcast to a {}×{}×{} tensor, which requires length {}.",
+                        self.src.len(), self.outer, self.len, self.inner, expected))
+        }
+    }
+}
+
+/// The pointwise operation used to combine the elements of an outer computation.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum OuterOp {
+    Add,
+    Sub,
+    Mul,
+}
+
+impl OuterOp {
+    fn apply(&self, a: f64, b: f64) -> f64 {
+        match self {
+            OuterOp::Add => a + b,
+            OuterOp::Sub => a - b,
+            OuterOp::Mul => a * b,
+        }
+    }
+}
+
+/// A computation combining a column vector of length N and a row vector of length M into an N×M matrix,
+/// such that `res[i * M + j] = op(col[i], row[j])`.
+#[derive(Clone)]
+pub struct OuterComp {
+    col: DArray,
+    row: DArray,
+    op: OuterOp,
+}
+
+impl OuterComp {
+    pub fn new(col: DArray, row: DArray, op: OuterOp) -> OuterComp {
+        OuterComp {col, row, op}
+    }
+}
+
+impl Computation for OuterComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.col.clone(), self.row.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let (rows, cols) = (self.col.len(), self.row.len());
+        match self.op {
+            OuterOp::Add => vec![
+                res_grads.sum_axis(rows, cols, Axis::Cols),
+                res_grads.sum_axis(rows, cols, Axis::Rows),
+            ],
+            OuterOp::Sub => vec![
+                res_grads.sum_axis(rows, cols, Axis::Cols),
+                -res_grads.sum_axis(rows, cols, Axis::Rows),
+            ],
+            OuterOp::Mul => vec![
+                DArray::from(MatVecComp {matrix: res_grads.clone(), vector: self.row.clone(), rows, cols, transposed: false}),
+                DArray::from(MatVecComp {matrix: res_grads, vector: self.col.clone(), rows, cols, transposed: true}),
+            ],
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.col.len() * self.row.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        assert_eq!(res_array.len(), self.len());
+        let row_data = self.row.data();
+        for (res_row, c) in res_array.chunks_mut(self.row.len()).zip(self.col.data()) {
+            for (res, r) in res_row.iter_mut().zip(row_data) {
+                *res += self.op.apply(*c, *r);
+            }
+        }
+    }
+}
+
+/// The distance between vectors used by a pairwise distance computation.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Distance {
+    /// The squared Euclidean distance, the sum of the squared differences.
+    SquaredEuclidean,
+    /// The L1 distance, the sum of the absolute differences.
+    L1,
+}
+
+impl Distance {
+    /// Returns the contribution of a pair of coordinates to the distance.
+    fn apply(&self, a: f64, b: f64) -> f64 {
+        match self {
+            Distance::SquaredEuclidean => (a - b) * (a - b),
+            Distance::L1 => (a - b).abs(),
+        }
+    }
+}
+
+/// A computation calculating the distances between every row of an N×D matrix and every row of an
+/// M×D matrix, such that `res[i * M + j] = distance(a[i], b[j])`.
+#[derive(Clone)]
+pub struct CdistComp {
+    a: DArray,
+    b: DArray,
+    dim: usize,
+    distance: Distance,
+}
+
+impl CdistComp {
+    pub fn new(a: DArray, b: DArray, dim: usize, distance: Distance) -> CdistComp {
+        CdistComp {a, b, dim, distance}
+    }
+}
+
+impl Computation for CdistComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.a.clone(), self.b.clone()]
+    }
+
+    /// Calculates the derivatives one coordinate at a time, using the pairwise differences of the coordinate.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let (rows, cols) = (self.a.len() / self.dim, self.b.len() / self.dim);
+        let column = |array: &DArray, len: usize, d: usize| {
+            IndexComp::from_strided(array, (d, self.dim), (0, 1), len, len)
+        };
+        let scatter = |array: &DArray, len: usize, d: usize| {
+            IndexComp::from_strided(array, (0, 1), (d, self.dim), len, len * self.dim)
+        };
+
+        let mut a_grads = vec![];
+        let mut b_grads = vec![];
+        for d in 0..self.dim {
+            let diff = column(&self.a, rows, d).outer_sub(&column(&self.b, cols, d));
+            let local_grads = match self.distance {
+                Distance::SquaredEuclidean => diff * 2.,
+                Distance::L1 => diff.signum(),
+            };
+            let weighted = &res_grads * &local_grads;
+            a_grads.push(scatter(&weighted.sum_axis(rows, cols, Axis::Cols), rows, d));
+            b_grads.push(scatter(&-weighted.sum_axis(rows, cols, Axis::Rows), cols, d));
+        }
+        vec![a_grads.into_iter().sum(), b_grads.into_iter().sum()]
+    }
+
+    fn len(&self) -> usize {
+        (self.a.len() / self.dim) * (self.b.len() / self.dim)
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        assert_eq!(res_array.len(), self.len());
+        let b_data = self.b.data();
+        let mut res_iter = res_array.iter_mut();
+        for a_row in self.a.data().chunks(self.dim) {
+            for b_row in b_data.chunks(self.dim) {
+                let res = res_iter.next().unwrap();
+                *res += a_row.iter().zip(b_row).map(|(a, b)| self.distance.apply(*a, *b)).sum::<f64>();
+            }
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 3 * self.len() * self.dim, bytes_read: (self.a.len() + self.b.len()) * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.dim == 0 {
+            return Err("The vectors must have a positive dimension.".to_string());
+        }
+        for array in [&self.a, &self.b] {
+            if array.len() % self.dim != 0 {
+                return Err(format!("A source of length {} isn't a matrix of vectors of dimension {}.", array.len(), self.dim));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A computation transposing a row-major `rows × cols` matrix into a `cols × rows` matrix.
+#[derive(Clone)]
+pub struct TransposeComp {
+    src: DArray,
+    rows: usize,
+    cols: usize,
+}
+
+impl Computation for TransposeComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![DArray::from(TransposeComp {src: res_grads, rows: self.cols, cols: self.rows})]
+    }
+
+    fn len(&self) -> usize {
+        self.rows * self.cols
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        for_each_summand(&self.src, &mut |data| {
+            for row_block in (0..self.rows).step_by(TRANSPOSE_BLOCK) {
+                for col_block in (0..self.cols).step_by(TRANSPOSE_BLOCK) {
+                    for i in row_block..self.rows.min(row_block + TRANSPOSE_BLOCK) {
+                        for j in col_block..self.cols.min(col_block + TRANSPOSE_BLOCK) {
+                            res_array[j * self.rows + i] += data[i * self.cols + j];
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Linear
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: self.len(), bytes_read: self.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.src.len() == self.rows * self.cols {
+            Ok(())
+        } else {
+            Err(format!("A source of length {} is not a {}×{} matrix.", self.src.len(), self.rows, self.cols))
+        }
+    }
+}
+
+/// A computation multiplying a row-major `rows × cols` matrix by a vector of length `cols`,
+/// or its transpose by a vector of length `rows`.
+#[derive(Clone)]
+pub struct MatVecComp {
+    matrix: DArray,
+    vector: DArray,
+    rows: usize,
+    cols: usize,
+    transposed: bool,
+}
+
+impl Computation for MatVecComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.matrix.clone(), self.vector.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let matrix_grads = if self.transposed {
+            self.vector.outer_mul(&res_grads)
+        } else {
+            res_grads.outer_mul(&self.vector)
+        };
+        let vector_grads = MatVecComp {matrix: self.matrix.clone(), vector: res_grads, transposed: !self.transposed, ..*self};
+        vec![matrix_grads, DArray::from(vector_grads)]
+    }
+
+    fn len(&self) -> usize {
+        if self.transposed { self.cols } else { self.rows }
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        assert_eq!(res_array.len(), self.len());
+        // A matrix without columns has no elements, so the products are zero.
+        if self.cols == 0 {
+            return;
+        }
+        let vector = self.vector.data();
+        let rows = self.matrix.data().chunks(self.cols);
+        if self.transposed {
+            // Accumulating whole rows reads the matrix in order.
+            for (row, v) in rows.zip(vector) {
+                for (res, x) in res_array.iter_mut().zip(row) {
+                    *res += x * v;
+                }
+            }
+        } else {
+            let backend = self.matrix.backend();
+            for (res, row) in res_array.iter_mut().zip(rows) {
+                *res += backend.dot(row, vector);
+            }
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 2 * self.matrix.len(), bytes_read: (self.matrix.len() + self.vector.len()) * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.matrix.len() != self.rows * self.cols {
+            return Err(format!("A source of length {} is not a {}×{} matrix.", self.matrix.len(), self.rows, self.cols));
+        }
+        let expected = if self.transposed { self.rows } else { self.cols };
+        if self.vector.len() != expected {
+            return Err(format!("A vector of length {} can't be multiplied by a {}×{} matrix{}.",
+                               self.vector.len(), self.rows, self.cols, if self.transposed { " transposed" } else { "" }));
+        }
+        Ok(())
+    }
+}
+
+/// A computation multiplying a batch of `m × k` matrices by a batch of `k × n` matrices, all stored row-major
+/// and one after the other. Either side may be stored transposed, which the derivatives use to avoid
+/// transposing their sources.
+#[derive(Clone)]
+pub struct MatMulComp {
+    a: DArray,
+    b: DArray,
+    batch: usize,
+    m: usize,
+    k: usize,
+    n: usize,
+    transpose_a: bool,
+    transpose_b: bool,
+}
+
+impl MatMulComp {
+    /// Multiplies each matrix of `a` by the corresponding matrix of `b`, where the matrices are the stored
+    /// matrices transposed if the corresponding flag is set.
+    fn new(a: DArray, b: DArray, batch: usize, (m, k, n): (usize, usize, usize), transpose_a: bool, transpose_b: bool) -> MatMulComp {
+        MatMulComp {a, b, batch, m, k, n, transpose_a, transpose_b}
+    }
+}
+
+impl Computation for MatMulComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.a.clone(), self.b.clone()]
+    }
+
+    /// With `A` and `B` the multiplied matrices, the derivatives are `G·Bᵀ` and `Aᵀ·G`, which are transposed again
+    /// for the sources stored transposed.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let (batch, m, k, n) = (self.batch, self.m, self.k, self.n);
+        let a_grads = if self.transpose_a {
+            MatMulComp::new(self.b.clone(), res_grads.clone(), batch, (k, n, m), self.transpose_b, true)
+        } else {
+            MatMulComp::new(res_grads.clone(), self.b.clone(), batch, (m, n, k), false, !self.transpose_b)
+        };
+        let b_grads = if self.transpose_b {
+            MatMulComp::new(res_grads, self.a.clone(), batch, (n, m, k), true, self.transpose_a)
+        } else {
+            MatMulComp::new(self.a.clone(), res_grads, batch, (k, m, n), !self.transpose_a, false)
+        };
+        vec![DArray::from(a_grads), DArray::from(b_grads)]
+    }
+
+    fn len(&self) -> usize {
+        self.batch * self.m * self.n
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        assert_eq!(res_array.len(), self.len());
+        let (m, k, n) = (self.m, self.k, self.n);
+        // Products of matrices without rows or columns are empty.
+        if m * n == 0 {
+            return;
+        }
+        // The strides between the rows and the columns of the multiplied matrices.
+        let (a_row, a_col) = if self.transpose_a { (1, m) } else { (k, 1) };
+        let (b_row, b_col) = if self.transpose_b { (1, k) } else { (n, 1) };
+        let (a_data, b_data) = (self.a.data(), self.b.data());
+        for (idx, res) in res_array.chunks_mut(m * n).enumerate() {
+            let a = &a_data[idx * m * k..(idx + 1) * m * k];
+            let b = &b_data[idx * k * n..(idx + 1) * k * n];
+            // Iterating over the columns of the result last reads the rows of untransposed matrices in order.
+            for i in 0..m {
+                let res_row = &mut res[i * n..(i + 1) * n];
+                for l in 0..k {
+                    let x = a[i * a_row + l * a_col];
+                    for (j, res) in res_row.iter_mut().enumerate() {
+                        *res += x * b[l * b_row + j * b_col];
+                    }
+                }
+            }
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {
+            flops: 2 * self.batch * self.m * self.k * self.n,
+            bytes_read: (self.a.len() + self.b.len()) * F64_SIZE,
+            bytes_written: self.len() * F64_SIZE,
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let (a_rows, a_cols) = if self.transpose_a { (self.k, self.m) } else { (self.m, self.k) };
+        let (b_rows, b_cols) = if self.transpose_b { (self.n, self.k) } else { (self.k, self.n) };
+        for (array, rows, cols) in [(&self.a, a_rows, a_cols), (&self.b, b_rows, b_cols)] {
+            if array.len() != self.batch * rows * cols {
+                return Err(format!("A source of length {} is not a batch of {} {}×{} matrices.", array.len(), self.batch, rows, cols));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DArray {
+    /// Transposes a row-major `rows × cols` matrix.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn transpose(&self, rows: usize, cols: usize) -> DArray {
+        DArray::from(TransposeComp {src: self.clone(), rows, cols})
+    }
+    /// Sums a row-major `rows × cols` matrix along the given axis.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn sum_axis(&self, rows: usize, cols: usize, axis: Axis) -> DArray {
+        DArray::from(SumAxisComp::new(self.clone(), rows, cols, axis))
+    }
+    /// Repeats the array along the given axis of a row-major `rows × cols` matrix.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn broadcast(&self, rows: usize, cols: usize, axis: Axis) -> DArray {
+        DArray::from(BroadcastComp::new(self.clone(), rows, cols, axis))
+    }
+    /// Returns the matrix of pairwise sums `self[i] + other[j]`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn outer_add(&self, other: &DArray) -> DArray {
+        DArray::from(OuterComp::new(self.clone(), other.clone(), OuterOp::Add))
+    }
+    /// Returns the matrix of pairwise differences `self[i] - other[j]`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn outer_sub(&self, other: &DArray) -> DArray {
+        DArray::from(OuterComp::new(self.clone(), other.clone(), OuterOp::Sub))
+    }
+    /// Returns the matrix of pairwise products `self[i] * other[j]`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn outer_mul(&self, other: &DArray) -> DArray {
+        DArray::from(OuterComp::new(self.clone(), other.clone(), OuterOp::Mul))
+    }
+    /// Returns the outer product of the arrays, the row-major matrix of pairwise products. See [`DArray::outer_mul`].
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn outer(&self, other: &DArray) -> DArray {
+        self.outer_mul(other)
+    }
+    /// Multiplies the row-major `m × k` matrix by the row-major `k × n` matrix.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn matmul(&self, other: &DArray, m: usize, k: usize, n: usize) -> DArray {
+        self.batch_matmul(other, 1, m, k, n)
+    }
+    /// Multiplies every row-major `m × k` matrix of a batch by the corresponding `k × n` matrix of the other batch,
+    /// in a single computation. The matrices of a batch are stored one after the other.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn batch_matmul(&self, other: &DArray, batch: usize, m: usize, k: usize, n: usize) -> DArray {
+        DArray::from(MatMulComp::new(self.clone(), other.clone(), batch, (m, k, n), false, false))
+    }
+    /// Multiplies the row-major `rows × cols` matrix by a vector of length `cols`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn matvec(&self, vector: &DArray, rows: usize, cols: usize) -> DArray {
+        DArray::from(MatVecComp {matrix: self.clone(), vector: vector.clone(), rows, cols, transposed: false})
+    }
+    /// Multiplies the transpose of the row-major `rows × cols` matrix by a vector of length `rows`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn matvec_transposed(&self, vector: &DArray, rows: usize, cols: usize) -> DArray {
+        DArray::from(MatVecComp {matrix: self.clone(), vector: vector.clone(), rows, cols, transposed: true})
+    }
+    /// Returns the matrix of distances between the rows of `self` and the rows of `other`, which are
+    /// row-major matrices of vectors of dimension `dim`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn cdist(&self, other: &DArray, dim: usize, distance: Distance) -> DArray {
+        DArray::from(CdistComp::new(self.clone(), other.clone(), dim, distance))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DArray;
+    use crate::broadcast_functions::{Axis, Distance};
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_outer_values() {
+        let col = DArray::from(vec![1., 2., 3.]);
+        let row = DArray::from(vec![10., 20.]);
+
+        assert_eq!(col.outer_add(&row).data(), &vec![11., 21., 12., 22., 13., 23.]);
+        assert_eq!(col.outer_sub(&row).data(), &vec![-9., -19., -8., -18., -7., -17.]);
+        assert_eq!(col.outer_mul(&row).data(), &vec![10., 20., 20., 40., 30., 60.]);
+    }
+
+    #[test]
+    fn test_axis_values() {
+        let matrix = DArray::from(vec![1., 2., 3., 4., 5., 6.]);
+
+        assert_eq!(matrix.sum_axis(2, 3, Axis::Rows).data(), &vec![5., 7., 9.]);
+        assert_eq!(matrix.sum_axis(2, 3, Axis::Cols).data(), &vec![6., 15.]);
+        assert_eq!(DArray::from(vec![1., 2.]).broadcast(2, 3, Axis::Cols).data(), &vec![1., 1., 1., 2., 2., 2.]);
+        assert_eq!(DArray::from(vec![1., 2., 3.]).broadcast(2, 3, Axis::Rows).data(), &vec![1., 2., 3., 1., 2., 3.]);
+    }
+
+    #[test]
+    fn test_outer_derivatives() {
+        let mut rng = StdRng::from_seed(SEED);
+        for _ in 0..10 {
+            let col = random_vec(&mut rng, 3);
+            let row = random_vec(&mut rng, 4);
+            let weights = DArray::from(random_vec(&mut rng, 12));
+
+            assert_gradients(|arrays| (arrays[0].outer_add(&arrays[1]) * &weights).sum(), &[&col, &row]);
+            assert_gradients(|arrays| (arrays[0].outer_sub(&arrays[1]) * &weights).sum(), &[&col, &row]);
+            assert_gradients(|arrays| (arrays[0].outer_mul(&arrays[1]) * &weights).sum(), &[&col, &row]);
+        }
+    }
+
+    #[test]
+    fn test_cdist() {
+        let a = DArray::from(vec![0., 0., 1., 2.]);
+        let b = DArray::from(vec![1., 1., 3., -1., 0., 2.]);
+
+        assert_eq!(a.cdist(&b, 2, Distance::SquaredEuclidean).data(), &[2., 10., 4., 1., 13., 1.]);
+        assert_eq!(a.cdist(&b, 2, Distance::L1).data(), &[2., 4., 2., 1., 5., 1.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        for _ in 0..10 {
+            let a = random_vec(&mut rng, 6);
+            let b = random_vec(&mut rng, 9);
+            let weights = DArray::from(random_vec(&mut rng, 6));
+            for distance in [Distance::SquaredEuclidean, Distance::L1] {
+                assert_gradients(|arrays| (arrays[0].cdist(&arrays[1], 3, distance) * &weights).sum(), &[&a, &b]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transpose() {
+        let matrix = DArray::from(vec![1., 2., 3., 4., 5., 6.]);
+        assert_eq!(matrix.transpose(2, 3).data(), &[1., 4., 2., 5., 3., 6.]);
+        assert_eq!(matrix.transpose(2, 3).transpose(3, 2).data(), matrix.data());
+        assert_eq!((&matrix + &matrix).transpose(3, 2).data(), &[2., 6., 10., 4., 8., 12.]);
+
+        // Matrices larger than a block.
+        let (rows, cols) = (70, 45);
+        let large = DArray::from((0..rows * cols).map(|x| x as f64).collect::<Vec<_>>());
+        let transposed = large.transpose(rows, cols);
+        for (idx, x) in transposed.data().iter().enumerate() {
+            assert_eq!(*x, ((idx % rows) * cols + idx / rows) as f64);
+        }
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 12);
+        let weights = DArray::from(random_vec(&mut rng, 12));
+        assert_gradients(|arrays| (arrays[0].transpose(3, 4).sin() * &weights).sum(), &[&v]);
+    }
+
+    #[test]
+    fn test_matvec() {
+        let matrix = DArray::from(vec![1., 2., 3., 4., 5., 6.]);
+        assert_eq!(matrix.matvec(&DArray::from(vec![1., 0., -1.]), 2, 3).data(), &[-2., -2.]);
+        assert_eq!(matrix.matvec_transposed(&DArray::from(vec![1., 2.]), 2, 3).data(), &[9., 12., 15.]);
+        assert_eq!(DArray::from(vec![1., 2.]).outer(&DArray::from(vec![3., 4., 5.])).data(), &[3., 4., 5., 6., 8., 10.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let m = random_vec(&mut rng, 12);
+        let v3 = random_vec(&mut rng, 3);
+        let v4 = random_vec(&mut rng, 4);
+        assert_gradients(|arrays| arrays[0].matvec(&arrays[1], 4, 3).sin().sum(), &[&m, &v3]);
+        assert_gradients(|arrays| arrays[0].matvec_transposed(&arrays[1], 4, 3).sin().sum(), &[&m, &v4]);
+        assert_gradients(|arrays| arrays[0].outer(&arrays[1]).sin().sum(), &[&v3, &v4]);
+    }
+
+    /// Tests products with matrices without rows or columns.
+    #[test]
+    fn test_matvec_empty() {
+        let empty = DArray::from(vec![]);
+        assert_eq!(empty.matvec(&empty, 2, 0).data(), &[0., 0.]);
+        assert_eq!(empty.matvec_transposed(&DArray::from(vec![1., 2.]), 2, 0).data(), &[] as &[f64]);
+
+        let a = DArray::from(vec![1., 2.]);
+        let grads = a.outer(&empty).sum().derive();
+        assert_eq!(grads[&a].data(), &[0., 0.]);
+        assert_eq!(grads[&empty].data(), &[] as &[f64]);
+    }
+
+    #[test]
+    #[should_panic(expected = "A vector of length 2 can't be multiplied by a 2×3 matrix.")]
+    fn test_matvec_fail() {
+        DArray::from(vec![1.; 6]).matvec(&DArray::from(vec![1., 2.]), 2, 3).data();
+    }
+
+    #[test]
+    fn test_matmul() {
+        let a = DArray::from(vec![1., 2., 3., 4., 5., 6.]);
+        let b = DArray::from(vec![1., 0., -1., 2., 0., 1.]);
+        assert_eq!(a.matmul(&b, 2, 3, 2).data(), &[-1., 7., -1., 16.]);
+        assert_eq!(b.matmul(&a, 3, 2, 3).data(), &[1., 2., 3., 7., 8., 9., 4., 5., 6.]);
+        assert_eq!(a.batch_matmul(&b, 2, 1, 3, 1).data(), &[-2., 14.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let a = random_vec(&mut rng, 24);
+        let b = random_vec(&mut rng, 30);
+        let weights = DArray::from(random_vec(&mut rng, 40));
+        assert_gradients(|arrays| (arrays[0].batch_matmul(&arrays[1], 2, 4, 3, 5).sin() * &weights).sum(), &[&a, &b]);
+        let square = random_vec(&mut rng, 9);
+        assert_gradients(|arrays| arrays[0].matmul(&arrays[0], 3, 3, 3).sin().sum(), &[&square]);
+    }
+
+    /// Tests that the derivatives of the transposed products used by the derivatives are correct.
+    #[test]
+    fn test_matmul_second_derivatives() {
+        let mut rng = StdRng::from_seed(SEED);
+        let a = random_vec(&mut rng, 12);
+        let b = random_vec(&mut rng, 30);
+
+        let weights = DArray::from(random_vec(&mut rng, 42));
+
+        assert_gradients(|arrays| {
+            let grads = arrays[0].batch_matmul(&arrays[1], 2, 2, 3, 5).powi(2).sum().derive();
+            let res = (DArray::concat(&[&grads[&arrays[0]], &grads[&arrays[1]]]) * &weights).powi(2).sum();
+            res.derive()[&arrays[1]].sum()
+        }, &[&a, &b]);
+    }
+
+    /// Tests products of matrices with a zero dimension.
+    #[test]
+    fn test_matmul_empty() {
+        let empty = DArray::from(vec![]);
+        let a = DArray::from(vec![1., 2., 3., 4.]);
+        assert_eq!(empty.batch_matmul(&a, 2, 0, 1, 2).data(), &[] as &[f64]);
+        assert_eq!(a.batch_matmul(&empty, 2, 2, 1, 0).data(), &[] as &[f64]);
+        // An inner dimension of zero gives zero matrices.
+        assert_eq!(empty.matmul(&empty, 2, 0, 3).data(), &[0.; 6]);
+
+        let grads = empty.batch_matmul(&a, 2, 0, 1, 2).sum().derive();
+        assert_eq!(grads[&a].data(), &[0.; 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "A source of length 6 is not a batch of 2 3×2 matrices.")]
+    fn test_matmul_fail() {
+        DArray::from(vec![1.; 12]).batch_matmul(&DArray::from(vec![1.; 6]), 2, 2, 3, 2).data();
+    }
+
+    /// Tests that the derivatives of the outer computations can be derived again.
+    #[test]
+    fn test_outer_second_derivatives() {
+        let mut rng = StdRng::from_seed(SEED);
+        let col = random_vec(&mut rng, 3);
+        let row = random_vec(&mut rng, 4);
+
+        assert_gradients(|arrays| {
+            let res = arrays[0].outer_mul(&arrays[1]).powi(2).sum();
+            res.derive().get(&arrays[0]).unwrap().sum()
+        }, &[&col, &row]);
+    }
+}
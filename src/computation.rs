@@ -1,11 +1,24 @@
-use crate::array::DArray;
+use std::any::Any;
+use std::ops::{Add, AddAssign};
+use crate::array::{DArray, Storage};
+use crate::binary_functions::{AddComp, AddConstComp, AddNComp, AddScalarComp, AffineComp, Atan2Comp, CompareComp, DivComp, FmaComp, HypotComp, LerpComp, MaskedFillComp, MulComp, MulScalarComp, PowComp, RemComp, SelectComp, SubComp, SubScalarComp};
+use crate::broadcast_functions::{BroadcastComp, CdistComp, MatMulComp, MatVecComp, OuterComp, SumAxisComp, TransposeComp};
+use crate::index_functions::{ChunkComp, ConcatComp, CorrelateComp, CumProdComp, CumSumComp, DotComp, ExpandComp, IndexComp, MaskSelectComp, MaskedSumComp, MaxReduceComp, MomentsComp, NormComp, NormalizeComp, OneHotComp, PadComp, ProdComp, ReverseComp, RollingComp, ShiftComp, SoftmaxComp, SumComp, TopKComp, VarianceComp};
+use crate::loss_functions::{BceComp, HingeComp};
+use crate::unary_functions::ThresholdComp;
 
 /// Useful metadata for computations. Used to unwrap the types of computations
 /// and do more complex graph analysis.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
 pub enum ComputationType {
     Add,
     Binary,
     Unary,
+    /// Computations linear in their single source, which read through the pointwise additions
+    /// and the arrays created from data among their sources instead of allocating them.
+    Linear,
+    /// Expansions of a scalar, which apply their source on zero on their first element.
+    Expand,
     Other,
 }
 
@@ -16,6 +29,12 @@ pub trait Computation : 'static {
     fn sources(&self) -> Vec<DArray>;
     /// Calculates the derivatives of the computation by each of the parent arrays.
     fn derivatives(&self, res_grads: DArray) -> Vec<DArray>;
+    /// Calculates the derivatives of the computation, given the array it computes.
+    /// Used by the backward pass, so that derivatives using the result don't calculate it again.
+    /// The default implementation ignores the result.
+    fn derivatives_with_result(&self, _res: &DArray, res_grads: DArray) -> Vec<DArray> {
+        self.derivatives(res_grads)
+    }
     /// The length of the result array.
     fn len(&self) -> usize;
     /// Calculates the function and adds the result to the given array.
@@ -28,6 +47,263 @@ pub trait Computation : 'static {
     fn apply_on_zero(&self, res_array: &mut [f64]) {
         self.apply(res_array);
     }
+    /// Returns if the length of the result is only known from the data of the sources, so that it is calculated
+    /// when it is first needed instead of when the computation is added to the graph.
+    /// The default implementation returns `false`.
+    fn deferred_len(&self) -> bool {
+        false
+    }
+    /// Checks that the lengths of the sources are compatible with the computation.
+    /// Called when the computation is added to the graph. The default implementation accepts all sources.
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+    /// Estimates the cost of calculating the computation.
+    /// The default implementation assumes a floating point operation per element of the result,
+    /// and reading every element of the sources once.
+    fn cost(&self) -> Cost {
+        Cost {
+            flops: self.len(),
+            bytes_read: self.sources().iter().map(|src| src.len() * F64_SIZE).sum(),
+            bytes_written: self.len() * F64_SIZE,
+        }
+    }
+    /// Returns a human-readable name of the computation, used in error reports.
+    /// The default implementation returns the type name without its module paths.
+    fn name(&self) -> String {
+        short_type_name(std::any::type_name::<Self>())
+    }
+    /// Returns the elements of the result which may be nonzero, as pairs of an index and a value which may repeat,
+    /// if they can be calculated without calculating the result. See [`DArray::sparse_entries`].
+    /// The default implementation returns `None`.
+    fn sparse_entries(&self) -> Option<Vec<(usize, f64)>> {
+        None
+    }
+}
+
+/// Declares the computations which are built into the crate.
+/// Generates the `NodeComp` enum, which stores the built-in computations of the graph directly,
+/// so that calling them is a jump on the variant instead of a virtual call.
+///
+/// Every public computation of the crate is built in, except the generic `UnaryComp` and `BinaryComp`,
+/// which can't be listed for every op, and `NullComp`. The private computations used only by derivatives
+/// stay boxed. New computations are added to the list along with their implementation.
+macro_rules! builtin_computations {
+    ($($variant:ident($comp:ty),)*) => {
+        /// The computation of an array in the graph.
+        /// Built-in computations are stored directly, and other computations are boxed.
+        pub enum NodeComp {
+            $($variant($comp),)*
+            Custom(Box<dyn Computation>),
+        }
+
+        impl NodeComp {
+            /// Wraps a computation, storing it directly if it is a built-in computation.
+            pub(crate) fn new<Comp: Computation>(comp: Comp) -> NodeComp {
+                // Moving the computation out of an option can be done through `Any`, without boxing it.
+                let mut comp = Some(comp);
+                let any = &mut comp as &mut dyn Any;
+                $(
+                    if let Some(builtin) = any.downcast_mut::<Option<$comp>>() {
+                        return NodeComp::$variant(builtin.take().unwrap());
+                    }
+                )*
+                NodeComp::Custom(Box::new(comp.unwrap()))
+            }
+        }
+
+        impl Computation for NodeComp {
+            fn sources(&self) -> Vec<DArray> {
+                match self {
+                    $(NodeComp::$variant(comp) => comp.sources(),)*
+                    NodeComp::Custom(comp) => comp.sources(),
+                }
+            }
+
+            fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+                match self {
+                    $(NodeComp::$variant(comp) => comp.derivatives(res_grads),)*
+                    NodeComp::Custom(comp) => comp.derivatives(res_grads),
+                }
+            }
+
+            fn derivatives_with_result(&self, res: &DArray, res_grads: DArray) -> Vec<DArray> {
+                match self {
+                    $(NodeComp::$variant(comp) => comp.derivatives_with_result(res, res_grads),)*
+                    NodeComp::Custom(comp) => comp.derivatives_with_result(res, res_grads),
+                }
+            }
+
+            fn len(&self) -> usize {
+                match self {
+                    $(NodeComp::$variant(comp) => comp.len(),)*
+                    NodeComp::Custom(comp) => comp.len(),
+                }
+            }
+
+            fn apply(&self, res_array: &mut [f64]) {
+                match self {
+                    $(NodeComp::$variant(comp) => comp.apply(res_array),)*
+                    NodeComp::Custom(comp) => comp.apply(res_array),
+                }
+            }
+
+            fn get_type(&self) -> ComputationType {
+                match self {
+                    $(NodeComp::$variant(comp) => comp.get_type(),)*
+                    NodeComp::Custom(comp) => comp.get_type(),
+                }
+            }
+
+            fn deferred_len(&self) -> bool {
+                match self {
+                    $(NodeComp::$variant(comp) => comp.deferred_len(),)*
+                    NodeComp::Custom(comp) => comp.deferred_len(),
+                }
+            }
+
+            fn apply_on_zero(&self, res_array: &mut [f64]) {
+                match self {
+                    $(NodeComp::$variant(comp) => comp.apply_on_zero(res_array),)*
+                    NodeComp::Custom(comp) => comp.apply_on_zero(res_array),
+                }
+            }
+
+            fn validate(&self) -> Result<(), String> {
+                match self {
+                    $(NodeComp::$variant(comp) => comp.validate(),)*
+                    NodeComp::Custom(comp) => comp.validate(),
+                }
+            }
+
+            fn cost(&self) -> Cost {
+                match self {
+                    $(NodeComp::$variant(comp) => comp.cost(),)*
+                    NodeComp::Custom(comp) => comp.cost(),
+                }
+            }
+
+            fn name(&self) -> String {
+                match self {
+                    $(NodeComp::$variant(comp) => comp.name(),)*
+                    NodeComp::Custom(comp) => comp.name(),
+                }
+            }
+
+            fn sparse_entries(&self) -> Option<Vec<(usize, f64)>> {
+                match self {
+                    $(NodeComp::$variant(comp) => comp.sparse_entries(),)*
+                    NodeComp::Custom(comp) => comp.sparse_entries(),
+                }
+            }
+        }
+    };
+}
+
+builtin_computations! {
+    FromData(FromDataComp),
+    Add(AddComp),
+    AddN(AddNComp),
+    AddScalar(AddScalarComp),
+    AddConst(AddConstComp),
+    Fma(FmaComp),
+    Lerp(LerpComp),
+    Sub(SubComp),
+    SubScalar(SubScalarComp),
+    Mul(MulComp),
+    MulScalar(MulScalarComp),
+    Div(DivComp),
+    Rem(RemComp),
+    Pow(PowComp),
+    Atan2(Atan2Comp),
+    Hypot(HypotComp),
+    Select(SelectComp),
+    MaskedFill(MaskedFillComp),
+    Compare(CompareComp),
+    Affine(AffineComp),
+    SumAxis(SumAxisComp),
+    Broadcast(BroadcastComp),
+    Outer(OuterComp),
+    Cdist(CdistComp),
+    Transpose(TransposeComp),
+    MatVec(MatVecComp),
+    MatMul(MatMulComp),
+    Index(IndexComp),
+    MaxReduce(MaxReduceComp),
+    TopK(TopKComp),
+    Sum(SumComp),
+    Dot(DotComp),
+    Prod(ProdComp),
+    CumSum(CumSumComp),
+    CumProd(CumProdComp),
+    Variance(VarianceComp),
+    Moments(MomentsComp),
+    MaskedSum(MaskedSumComp),
+    Norm(NormComp),
+    Softmax(SoftmaxComp),
+    Normalize(NormalizeComp),
+    Rolling(RollingComp),
+    Chunk(ChunkComp),
+    MaskSelect(MaskSelectComp),
+    Reverse(ReverseComp),
+    Shift(ShiftComp),
+    Pad(PadComp),
+    Correlate(CorrelateComp),
+    Concat(ConcatComp),
+    OneHot(OneHotComp),
+    Expand(ExpandComp),
+    Bce(BceComp),
+    Hinge(HingeComp),
+    Threshold(ThresholdComp),
+}
+
+/// The size of an element of an array in bytes.
+pub(crate) const F64_SIZE: usize = std::mem::size_of::<f64>();
+
+/// An estimate of the resources used to calculate a computation.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Cost {
+    /// The number of floating point operations.
+    pub flops: usize,
+    /// The number of bytes read from the sources.
+    pub bytes_read: usize,
+    /// The number of bytes written to the result.
+    pub bytes_written: usize,
+}
+
+impl Add for Cost {
+    type Output = Cost;
+
+    fn add(self, rhs: Cost) -> Cost {
+        Cost {
+            flops: self.flops + rhs.flops,
+            bytes_read: self.bytes_read + rhs.bytes_read,
+            bytes_written: self.bytes_written + rhs.bytes_written,
+        }
+    }
+}
+
+impl AddAssign for Cost {
+    fn add_assign(&mut self, rhs: Cost) {
+        *self = *self + rhs;
+    }
+}
+
+/// Removes the module paths from a type name, keeping the names of the generic parameters.
+fn short_type_name(name: &str) -> String {
+    let mut res = String::new();
+    let mut path = String::new();
+    for c in name.chars() {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            path.push(c);
+        } else {
+            res.push_str(path.rsplit("::").next().unwrap());
+            path.clear();
+            res.push(c);
+        }
+    }
+    res.push_str(path.rsplit("::").next().unwrap());
+    res
 }
 
 /// A computation that does nothing.
@@ -55,7 +331,7 @@ impl Computation for NullComp {
 
 #[derive(Clone)]
 pub struct FromDataComp {
-    pub(crate) data: Vec<f64>,
+    pub(crate) data: Storage,
 }
 
 impl Computation for FromDataComp {
@@ -68,15 +344,39 @@ impl Computation for FromDataComp {
     }
 
     fn len(&self) -> usize {
-        self.data.len()
+        self.data.as_slice().len()
     }
 
     fn apply(&self, res: &mut [f64]) {
-        assert_eq!(self.data.len(), res.len());
+        let data = self.data.as_slice();
+        assert_eq!(data.len(), res.len());
         for i in 0..self.len() {
-            res[i] += self.data[i];
+            res[i] += data[i];
         }
     }
+
+    /// Copying the data requires no floating point operations.
+    fn cost(&self) -> Cost {
+        Cost {flops: 0, bytes_read: self.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use crate::DArray;
+    use crate::computation::NodeComp;
+
+    /// Tests that built-in computations are stored directly, and other computations are boxed.
+    #[test]
+    fn test_node_comp() {
+        let array = DArray::from(vec![1., 2., 3.]);
+
+        assert!(matches!(array.comp(), NodeComp::FromData(_)));
+        assert!(matches!((&array + &array).comp(), NodeComp::Add(_)));
+        assert!(matches!((&array * &array).comp(), NodeComp::Mul(_)));
+        assert!(matches!(array.sum().comp(), NodeComp::Sum(_)));
+        assert!(matches!(array.sin().comp(), NodeComp::Custom(_)));
+    }
+}
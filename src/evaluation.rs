@@ -0,0 +1,297 @@
+use std::any::Any;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use fxhash::FxHashMap;
+use crate::array::DArray;
+use crate::computation::Computation;
+
+type Map<K, V> = FxHashMap<K, V>;
+
+/// The arrays allocated when evaluating an array, and the dependencies between them.
+/// An array depends on the allocated arrays which have to be evaluated before it can be calculated,
+/// either because they are its sources, or because they are sources of arrays applied on its buffer.
+struct TaskGraph {
+    /// The allocated arrays, in topological order.
+    tasks: Vec<DArray>,
+    /// The number of tasks each task depends on.
+    dependency_counts: Vec<usize>,
+    /// The tasks depending on each task.
+    dependents: Vec<Vec<usize>>,
+}
+
+impl TaskGraph {
+    /// Builds the task graph of the evaluation of an uninitialized array.
+    fn new(array: &DArray) -> TaskGraph {
+        let plan = array.plan_evaluation();
+        let tasks: Vec<DArray> = plan.topo.iter()
+            .filter(|node| *node == array || plan.allocated.contains(node))
+            .cloned()
+            .collect();
+        let task_ids: Map<DArray, usize> = tasks.iter().cloned().enumerate().map(|(idx, task)| (task, idx)).collect();
+
+        let mut dependency_counts = vec![0; tasks.len()];
+        let mut dependents = vec![vec![]; tasks.len()];
+        for (idx, task) in tasks.iter().enumerate() {
+            // Arrays which are not allocated have a single parent, so each of them is searched once.
+            let mut stack = task.comp().sources();
+            while let Some(node) = stack.pop() {
+                if node.is_initialized() {
+                    continue;
+                }
+                match task_ids.get(&node) {
+                    Some(&dependency) => {
+                        dependency_counts[idx] += 1;
+                        dependents[dependency].push(idx);
+                    }
+                    None => stack.extend(node.comp().sources()),
+                }
+            }
+        }
+
+        TaskGraph {tasks, dependency_counts, dependents}
+    }
+}
+
+/// The state of a parallel evaluation, shared between the worker threads.
+struct ExecutorState {
+    /// The tasks whose dependencies were all evaluated.
+    ready: Vec<usize>,
+    /// The number of unevaluated dependencies of each task.
+    remaining: Vec<usize>,
+    /// The number of evaluated tasks.
+    done: usize,
+    /// The payload of a panic raised by one of the tasks, which stops the evaluation.
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+/// A handle used to cancel a running evaluation, possibly from another thread.
+#[derive(Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken::default()
+    }
+
+    /// Requests the cancellation of the evaluations using the token.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns if the cancellation was requested.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// The error returned by a cancelled evaluation.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Cancelled {
+    /// The number of arrays evaluated before the evaluation was cancelled.
+    pub evaluated: usize,
+    /// The number of arrays the evaluation had to evaluate.
+    pub total: usize,
+}
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "The evaluation was cancelled after evaluating {} of {} arrays.", self.evaluated, self.total)
+    }
+}
+
+impl Error for Cancelled {}
+
+impl DArray {
+    /// Returns a reference to the array's data, reporting the progress of the evaluation.
+    /// The callback is called with the number of evaluated arrays and the total number of arrays to evaluate
+    /// every time `every` arrays are evaluated, and when the evaluation ends.
+    /// The token is checked before every array is evaluated. If it was cancelled, the evaluation stops.
+    /// The arrays already evaluated keep their data, so evaluating the array again resumes the evaluation.
+    pub fn data_with_progress(
+        &self,
+        every: usize,
+        mut callback: impl FnMut(usize, usize),
+        cancel: &CancelToken,
+    ) -> Result<&[f64], Cancelled> {
+        assert!(every > 0, "Progress must be reported at least every array!");
+        if self.is_initialized() {
+            return Ok(self.data());
+        }
+
+        let plan = self.plan_evaluation();
+        let tasks: Vec<&DArray> = plan.topo.iter()
+            .rev()
+            .filter(|node| *node == self || plan.allocated.contains(node))
+            .collect();
+
+        for (evaluated, task) in tasks.iter().enumerate() {
+            if cancel.is_cancelled() {
+                return Err(Cancelled {evaluated, total: tasks.len()});
+            }
+            task.evaluate_planned();
+            if (evaluated + 1) % every == 0 || evaluated + 1 == tasks.len() {
+                callback(evaluated + 1, tasks.len());
+            }
+        }
+
+        Ok(self.data())
+    }
+
+    /// Returns a reference to the array's data, evaluating independent branches of the computation graph
+    /// in parallel on the given number of threads.
+    /// The arrays are allocated as in [`DArray::data`], and every allocated array is calculated on a worker thread
+    /// once all the arrays it depends on are evaluated.
+    /// [`std::thread::available_parallelism`] can be used to choose the number of threads.
+    pub fn data_parallel(&self, threads: usize) -> &[f64] {
+        assert!(threads > 0, "Parallel evaluation requires at least one thread!");
+        if self.is_initialized() {
+            return self.data();
+        }
+
+        let graph = TaskGraph::new(self);
+        let ready = (0..graph.tasks.len()).filter(|idx| graph.dependency_counts[*idx] == 0).collect();
+        let state = Mutex::new(ExecutorState {ready, remaining: graph.dependency_counts.clone(), done: 0, panic: None});
+        let condvar = Condvar::new();
+
+        std::thread::scope(|scope| {
+            for _ in 0..threads.min(graph.tasks.len()) {
+                scope.spawn(|| {
+                    loop {
+                        // Waiting for a task to be ready.
+                        let task = {
+                            let mut state = state.lock().unwrap();
+                            loop {
+                                if state.done == graph.tasks.len() || state.panic.is_some() {
+                                    return;
+                                }
+                                if let Some(task) = state.ready.pop() {
+                                    break task;
+                                }
+                                state = condvar.wait(state).unwrap();
+                            }
+                        };
+
+                        let res = catch_unwind(AssertUnwindSafe(|| graph.tasks[task].evaluate_planned()));
+
+                        let mut state = state.lock().unwrap();
+                        match res {
+                            Ok(()) => {
+                                state.done += 1;
+                                for &dependent in graph.dependents[task].iter() {
+                                    state.remaining[dependent] -= 1;
+                                    if state.remaining[dependent] == 0 {
+                                        state.ready.push(dependent);
+                                    }
+                                }
+                            }
+                            Err(panic) => {
+                                state.panic.get_or_insert(panic);
+                            }
+                        }
+                        condvar.notify_all();
+                    }
+                });
+            }
+        });
+
+        if let Some(panic) = state.into_inner().unwrap().panic {
+            resume_unwind(panic);
+        }
+        self.data()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DArray;
+    use crate::computation::Computation;
+    use crate::evaluation::CancelToken;
+    use crate::test_utils::*;
+
+    /// Builds a wide random graph, with many independent branches.
+    fn wide_graph(seed: [u8; 32]) -> DArray {
+        let mut rng = StdRng::from_seed(seed);
+        let mut branches: Vec<DArray> = (0..16).map(|_| DArray::from(random_vec(&mut rng, 100))).collect();
+        for _ in 0..200 {
+            let p1 = rng.gen_range(0..branches.len());
+            let p2 = rng.gen_range(0..branches.len());
+            branches[p1] = match rng.gen_range(0..3) {
+                0 => (&branches[p1] + &branches[p2]).sin(),
+                1 => (&branches[p1] * &branches[p2]).cos(),
+                _ => &branches[p1].sum().sin() * &branches[p2],
+            };
+        }
+        branches.iter().fold(DArray::from(0.), |acc, branch| acc + branch.sum())
+    }
+
+    #[test]
+    fn test_parallel() {
+        for threads in [1, 2, 8] {
+            let sequential = wide_graph(SEED);
+            let parallel = wide_graph(SEED);
+            assert_eq!(sequential.data(), parallel.data_parallel(threads));
+        }
+    }
+
+    #[test]
+    fn test_progress() {
+        let mut reports = vec![];
+        let res = wide_graph(SEED);
+        let data = res.data_with_progress(10, |evaluated, total| reports.push((evaluated, total)), &CancelToken::new()).unwrap();
+
+        let total = reports.last().unwrap().1;
+        assert_eq!(data, wide_graph(SEED).data());
+        assert_eq!(reports.len(), total.div_ceil(10));
+        assert!(reports.iter().all(|(evaluated, _)| evaluated % 10 == 0 || *evaluated == total));
+    }
+
+    #[test]
+    fn test_cancel() {
+        let cancel = CancelToken::new();
+        let res = wide_graph(SEED);
+        let err = res.data_with_progress(10, |_, _| cancel.cancel(), &cancel).err().unwrap();
+
+        assert_eq!(err.evaluated, 10);
+        assert!(!res.is_initialized());
+        // Evaluating the array again resumes the evaluation.
+        assert_eq!(res.data(), wide_graph(SEED).data());
+    }
+
+    /// A computation which panics when it is calculated.
+    #[derive(Clone)]
+    struct PanicComp {
+        src: DArray,
+    }
+
+    impl Computation for PanicComp {
+        fn sources(&self) -> Vec<DArray> {
+            vec![self.src.clone()]
+        }
+
+        fn derivatives(&self, _: DArray) -> Vec<DArray> {
+            vec![]
+        }
+
+        fn len(&self) -> usize {
+            self.src.len()
+        }
+
+        fn apply(&self, _: &mut [f64]) {
+            panic!("PanicComp was applied");
+        }
+    }
+
+    /// Tests that panics in the worker threads are propagated to the caller.
+    #[test]
+    #[should_panic(expected = "PanicComp was applied")]
+    fn test_parallel_panic() {
+        let array = DArray::from(vec![1., 2.]);
+        let res = DArray::from(PanicComp {src: array.sin()}) + array.sin().sum();
+        res.data_parallel(2);
+    }
+}
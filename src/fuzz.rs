@@ -0,0 +1,265 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use rand::prelude::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::array::DArray;
+
+/// The relative shift of the inputs used for numeric differentiation.
+const DIFF: f64 = 1e-7;
+/// The allowed relative error between the results of the graph and their expected values.
+const ALLOWED_ERROR: f64 = 1e-3;
+/// The relative rounding error of the output allowed when comparing its changes, since the change
+/// of a large output by a small shift cancels most of its digits.
+const OUTPUT_ROUNDING: f64 = 1e-12;
+
+/// A computation applied by the steps of a random graph.
+#[derive(Copy, Clone)]
+pub enum FuzzOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Sin,
+    /// A custom computation on a single array, keeping its length.
+    Unary(fn(&DArray) -> DArray),
+    /// A custom computation on two arrays of the same length, keeping their length.
+    Binary(fn(&DArray, &DArray) -> DArray),
+}
+
+impl FuzzOp {
+    /// Applies the computation. Unary computations only use the first array.
+    fn apply(&self, p1: &DArray, p2: &DArray) -> DArray {
+        match self {
+            FuzzOp::Add => p1 + p2,
+            FuzzOp::Sub => p1 - p2,
+            FuzzOp::Mul => p1 * p2,
+            FuzzOp::Div => p1 / p2,
+            FuzzOp::Sin => p1.sin(),
+            FuzzOp::Unary(func) => func(p1),
+            FuzzOp::Binary(func) => func(p1, p2),
+        }
+    }
+}
+
+/// The configuration of the random graphs used to stress-test evaluation and derivation.
+///
+/// A graph starts with a number of random input arrays. Every step replaces a random array with
+/// a random computation on it and on another random array, and the output is the sum of the first array.
+#[derive(Clone)]
+pub struct FuzzConfig {
+    /// The computations applied by the steps, chosen uniformly.
+    pub ops: Vec<FuzzOp>,
+    /// The number of input arrays.
+    pub inputs: usize,
+    /// The number of computations in the graph.
+    pub steps: usize,
+    /// The length of the arrays, whose elements are chosen uniformly in `[-0.5, 0.5)`.
+    pub len: usize,
+    /// The seed of the random graph.
+    pub seed: [u8; 32],
+}
+
+impl Default for FuzzConfig {
+    /// Random rational functions, like the graphs of the benchmarks.
+    fn default() -> FuzzConfig {
+        FuzzConfig {
+            ops: vec![FuzzOp::Add, FuzzOp::Mul, FuzzOp::Div],
+            inputs: 10,
+            steps: 100,
+            len: 1000,
+            seed: std::array::from_fn(|idx| idx as u8),
+        }
+    }
+}
+
+impl FuzzConfig {
+    /// Generates the random graph of the configuration.
+    pub fn generate(&self) -> RandomGraph {
+        assert!(!self.ops.is_empty() && self.inputs > 0, "Random graphs require computations and inputs!");
+        let mut rng = StdRng::from_seed(self.seed);
+        let data: Vec<Vec<f64>> = (0..self.inputs)
+            .map(|_| (0..self.len).map(|_| rng.gen::<f64>() - 0.5).collect())
+            .collect();
+        let steps = (0..self.steps)
+            .map(|_| (self.ops[rng.gen_range(0..self.ops.len())], rng.gen_range(0..self.inputs), rng.gen_range(0..self.inputs)))
+            .collect();
+
+        let inputs: Vec<DArray> = data.iter().cloned().map(DArray::from).collect();
+        let mut graph = RandomGraph {output: DArray::from(0.), inputs, data, steps};
+        graph.output = graph.build(&graph.inputs);
+        graph
+    }
+
+    /// Generates the random graph of the configuration and verifies it.
+    /// See [`RandomGraph::verify`].
+    pub fn fuzz(&self) -> Result<(), FuzzError> {
+        self.generate().verify()
+    }
+}
+
+/// A random graph, generated by a [`FuzzConfig`].
+pub struct RandomGraph {
+    /// The input arrays of the graph.
+    pub inputs: Vec<DArray>,
+    /// The scalar output of the graph.
+    pub output: DArray,
+    /// The data of the inputs.
+    data: Vec<Vec<f64>>,
+    /// The steps of the graph, as a computation and the indices of the arrays it is applied on.
+    steps: Vec<(FuzzOp, usize, usize)>,
+}
+
+impl RandomGraph {
+    /// Builds the output of the graph from the given inputs.
+    fn build(&self, inputs: &[DArray]) -> DArray {
+        let mut arrays = inputs.to_vec();
+        for (op, p1, p2) in self.steps.iter() {
+            arrays[*p1] = op.apply(&arrays[*p1], &arrays[*p2]);
+        }
+        arrays[0].sum()
+    }
+
+    /// Verifies the evaluation and the derivation of the graph.
+    ///
+    /// The output must be finite, since overflowing graphs can't be compared meaningfully.
+    /// It is compared to a parallel evaluation of a copy of the graph, and the derivatives of the output
+    /// by every element of every input are compared to a central numeric derivation.
+    /// Since a copy of the graph is evaluated for every element of the inputs, verifying large graphs is slow.
+    pub fn verify(&self) -> Result<(), FuzzError> {
+        let output = self.output.item();
+        if !output.is_finite() {
+            return Err(FuzzError::NonFinite {output});
+        }
+        let parallel = self.build(&self.inputs).data_parallel(2)[0];
+        if !is_close(output, parallel) {
+            return Err(FuzzError::Evaluation {expected: output, observed: parallel});
+        }
+
+        let grads = self.output.derive();
+        for (input, array) in self.inputs.iter().enumerate() {
+            let grad = grads.get(array).map_or_else(|| vec![0.; array.len()], |grad| grad.data().to_vec());
+            for index in 0..array.len() {
+                let diff = DIFF * (1. + self.data[input][index].abs());
+                let shifted_output = |shift: f64| {
+                    let mut shifted = self.data[input].clone();
+                    shifted[index] += shift;
+                    let mut inputs = self.inputs.clone();
+                    inputs[input] = DArray::from(shifted);
+                    self.build(&inputs).item()
+                };
+
+                let expected = grad[index] * diff;
+                // The central difference cancels the second order term, which is large near the poles of the graph.
+                let observed = (shifted_output(diff) - shifted_output(-diff)) / 2.;
+                // Differences far below the shift or the rounding error of the output are noise, and are not meaningful.
+                let noise = (diff * 1e-6).max(output.abs() * OUTPUT_ROUNDING);
+                if (expected - observed).abs() > noise && !is_close(expected, observed) {
+                    return Err(FuzzError::Gradient {input, index, expected, observed});
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the ratio of the difference of two numbers and their average is below the allowed error.
+fn is_close(a: f64, b: f64) -> bool {
+    a == b || (a - b).abs() * 2. / (a.abs() + b.abs()) < ALLOWED_ERROR
+}
+
+/// A mismatch found while verifying a random graph.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FuzzError {
+    /// The output of the graph overflowed or is undefined.
+    NonFinite {
+        output: f64,
+    },
+    /// The parallel evaluation of the output differs from its evaluation.
+    Evaluation {
+        expected: f64,
+        observed: f64,
+    },
+    /// The derivative by an element of an input differs from the numeric derivative.
+    /// The values are the changes of the output when shifting the element, as predicted by the derivative
+    /// and as observed.
+    Gradient {
+        input: usize,
+        index: usize,
+        expected: f64,
+        observed: f64,
+    },
+}
+
+impl Display for FuzzError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FuzzError::NonFinite {output} =>
+                write!(f, "The output of the graph is {}.", output),
+            FuzzError::Evaluation {expected, observed} =>
+                write!(f, "The parallel evaluation of the output is {}, but its evaluation is {}.", observed, expected),
+            FuzzError::Gradient {input, index, expected, observed} =>
+                write!(f, "Shifting element {} of input {} changed the output by {}, but the derivative predicted {}.",
+                       index, input, observed, expected),
+        }
+    }
+}
+
+impl Error for FuzzError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::fuzz::{FuzzConfig, FuzzError, FuzzOp};
+    use crate::unary_functions::Rounding;
+
+    #[test]
+    fn test_fuzz() {
+        for seed in 0..10 {
+            let config = FuzzConfig {
+                // The products and the exponents are squashed, so that repeating them can't overflow.
+                ops: vec![FuzzOp::Add, FuzzOp::Sub, FuzzOp::Binary(|x, y| (x * y).sin()), FuzzOp::Sin, FuzzOp::Unary(|x| x.sin().exp() * 0.1)],
+                inputs: 3,
+                steps: 30,
+                len: 4,
+                seed: [seed; 32],
+            };
+            assert_eq!(config.fuzz(), Ok(()));
+        }
+    }
+
+    /// Tests that the rational functions of the default configuration, whose outputs can be large
+    /// and whose poles can be close, aren't reported.
+    #[test]
+    fn test_fuzz_default() {
+        for seed in 0..20 {
+            let config = FuzzConfig {len: 3, seed: [seed; 32], ..FuzzConfig::default()};
+            assert_eq!(config.fuzz(), Ok(()));
+        }
+    }
+
+    /// Tests that wrong derivatives are detected.
+    #[test]
+    fn test_fuzz_mismatch() {
+        // The straight-through estimator uses the derivative of the identity instead of zero.
+        let config = FuzzConfig {
+            ops: vec![FuzzOp::Unary(|x| x.round_with(Rounding::Floor, true))],
+            inputs: 1,
+            steps: 1,
+            len: 3,
+            ..FuzzConfig::default()
+        };
+        assert!(matches!(config.fuzz(), Err(FuzzError::Gradient {input: 0, ..})));
+    }
+
+    /// Tests that overflowing graphs are reported instead of being compared.
+    #[test]
+    fn test_fuzz_non_finite() {
+        let config = FuzzConfig {
+            ops: vec![FuzzOp::Unary(|x| x.exp() * f64::INFINITY)],
+            inputs: 1,
+            steps: 1,
+            len: 3,
+            ..FuzzConfig::default()
+        };
+        assert!(matches!(config.fuzz(), Err(FuzzError::NonFinite {..})));
+    }
+}
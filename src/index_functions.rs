@@ -1,5 +1,126 @@
-use crate::computation::Computation;
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::sync::Arc;
+use itertools::izip;
+use rand::seq::SliceRandom;
+use crate::computation::{Computation, ComputationType, Cost, F64_SIZE, NodeComp};
 use crate::array::DArray;
+use crate::binary_functions::validate_same_len;
+
+/// Calls the function on the data of the arrays summed into the given array.
+/// Used by linear computations, which read through uninitialized pointwise additions and arrays
+/// created from data instead of evaluating them.
+pub(crate) fn for_each_summand(array: &DArray, func: &mut impl FnMut(&[f64])) {
+    if !array.is_initialized() {
+        match array.comp() {
+            NodeComp::Add(_) | NodeComp::AddN(_) => {
+                for src in array.comp().sources() {
+                    for_each_summand(&src, func);
+                }
+                return;
+            }
+            NodeComp::FromData(leaf) => {
+                func(leaf.data.as_slice());
+                return;
+            }
+            _ => {}
+        }
+    }
+    func(array.data())
+}
+
+/// A running sum of floats.
+/// With the `compensated-sum` feature, the rounding errors of the additions are accumulated separately
+/// with Neumaier's algorithm, so that the error of the sum doesn't grow with the number of elements.
+#[derive(Default)]
+struct Accumulator {
+    sum: f64,
+    compensation: f64,
+}
+
+impl Accumulator {
+    fn add(&mut self, x: f64) {
+        if cfg!(feature = "compensated-sum") {
+            let sum = self.sum + x;
+            self.compensation += if self.sum.abs() >= x.abs() { (self.sum - sum) + x } else { (x - sum) + self.sum };
+            self.sum = sum;
+        } else {
+            self.sum += x;
+        }
+    }
+
+    fn total(&self) -> f64 {
+        self.sum + self.compensation
+    }
+}
+
+impl FromIterator<f64> for Accumulator {
+    fn from_iter<I: IntoIterator<Item = f64>>(iter: I) -> Accumulator {
+        let mut acc = Accumulator::default();
+        iter.into_iter().for_each(|x| acc.add(x));
+        acc
+    }
+}
+
+/// The pairs of indices of an index computation, stored by their structure.
+#[derive(Clone)]
+enum IndexMap {
+    /// A list of pairs of indices.
+    Pairs(Vec<(usize, usize)>),
+    /// The pairs `(src_start + k * src_stride, tar_start + k * tar_stride)` for every `k` below `count`.
+    /// Covers ranges, strides and broadcasts of a single element.
+    Affine {src_start: usize, src_stride: usize, tar_start: usize, tar_stride: usize, count: usize},
+    /// The pairs `(indices[k], k)`.
+    Gather(Arc<[usize]>),
+    /// The pairs `(k, indices[k])`.
+    Scatter(Arc<[usize]>),
+}
+
+impl IndexMap {
+    /// Returns the map with every pair reversed.
+    fn inverted(&self) -> IndexMap {
+        match self {
+            IndexMap::Pairs(pairs) => IndexMap::Pairs(pairs.iter().map(|(i, j)| (*j, *i)).collect()),
+            IndexMap::Affine {src_start, src_stride, tar_start, tar_stride, count} => IndexMap::Affine {
+                src_start: *tar_start, src_stride: *tar_stride, tar_start: *src_start, tar_stride: *src_stride, count: *count,
+            },
+            IndexMap::Gather(indices) => IndexMap::Scatter(indices.clone()),
+            IndexMap::Scatter(indices) => IndexMap::Gather(indices.clone()),
+        }
+    }
+
+    /// Returns the number of pairs.
+    fn count(&self) -> usize {
+        match self {
+            IndexMap::Pairs(pairs) => pairs.len(),
+            IndexMap::Affine {count, ..} => *count,
+            IndexMap::Gather(indices) | IndexMap::Scatter(indices) => indices.len(),
+        }
+    }
+
+    /// Calls the function on every pair of a source and a target index.
+    fn for_each_pair(&self, func: &mut impl FnMut(usize, usize)) {
+        match self {
+            IndexMap::Pairs(pairs) => pairs.iter().for_each(|(src, tar)| func(*src, *tar)),
+            IndexMap::Affine {src_start, src_stride, tar_start, tar_stride, count} =>
+                (0..*count).for_each(|k| func(src_start + k * src_stride, tar_start + k * tar_stride)),
+            IndexMap::Gather(indices) => indices.iter().enumerate().for_each(|(tar, src)| func(*src, tar)),
+            IndexMap::Scatter(indices) => indices.iter().enumerate().for_each(|(src, tar)| func(src, *tar)),
+        }
+    }
+
+    /// Returns the maximal source and target indices, or `None` if there are no pairs.
+    fn max_indices(&self) -> Option<(usize, usize)> {
+        match self {
+            IndexMap::Pairs(pairs) => pairs.iter().copied().reduce(|(s1, t1), (s2, t2)| (s1.max(s2), t1.max(t2))),
+            IndexMap::Affine {count: 0, ..} => None,
+            IndexMap::Affine {src_start, src_stride, tar_start, tar_stride, count} =>
+                Some((src_start + (count - 1) * src_stride, tar_start + (count - 1) * tar_stride)),
+            IndexMap::Gather(indices) => indices.iter().max().map(|max| (*max, indices.len() - 1)),
+            IndexMap::Scatter(indices) => indices.iter().max().map(|max| (indices.len() - 1, *max)),
+        }
+    }
+}
 
 /// A computation that takes indices from an array.
 /// Can be used to take ranges of an array, to perform permutations, etc.
@@ -7,10 +128,10 @@ use crate::array::DArray;
 pub struct IndexComp {
     /// The parent array.
     array: DArray,
-    /// A list of indices in the parent array taken to the child array, in the format `[(par_idx, child_idx), ...]`.
+    /// The indices in the parent array taken to the child array, as pairs of `(par_idx, child_idx)`.
     /// If an index in the child array appears several times, the appropriate elements of the parent
     /// array are summed.
-    indices: Vec<(usize, usize)>,
+    indices: IndexMap,
     /// The length of the child array generated.
     length: usize,
 }
@@ -24,20 +145,46 @@ impl IndexComp {
         iter: impl Iterator<Item = (usize, usize)>,
         length: usize,
     ) -> IndexComp {
-        // Making sure all indices are legal.
-        let indices: Vec<(usize, usize)> = iter.collect();
-
-        assert!(indices.iter().all(|idx| idx.0 < array.len()));
-        assert!(indices.iter().all(|idx| idx.1 < length));
-
-        IndexComp {array: array.clone(), indices, length}
+        IndexComp {array: array.clone(), indices: IndexMap::Pairs(iter.collect()), length}
     }
 
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     pub fn map_indices(array: &DArray,
                        iter: impl Iterator<Item = (usize, usize)>,
                        length: usize) -> DArray {
         DArray::from(IndexComp::new(array, iter, length))
     }
+
+    /// Takes the range of the parent array to the indices starting at `offset` of a child array of the given length.
+    /// Stores only the bounds of the range.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn from_range(array: &DArray, range: Range<usize>, offset: usize, length: usize) -> DArray {
+        IndexComp::from_strided(array, (range.start, 1), (offset, 1), range.len(), length)
+    }
+
+    /// Takes `count` elements of the parent array, at indices `start + k * stride` for the `(start, stride)` of the
+    /// parent, to the indices `start + k * stride` for the `(start, stride)` of the child array of the given length.
+    /// A stride of zero in the parent broadcasts an element, and a stride of zero in the child sums the elements.
+    /// Stores only the starts and the strides.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn from_strided(array: &DArray, src: (usize, usize), tar: (usize, usize), count: usize, length: usize) -> DArray {
+        let indices = IndexMap::Affine {src_start: src.0, src_stride: src.1, tar_start: tar.0, tar_stride: tar.1, count};
+        DArray::from(IndexComp {array: array.clone(), indices, length})
+    }
+
+    /// Creates a child array of the given length, whose element at every index `i` is the element of the parent
+    /// array at index `func(i)`. Stores a single index for every element of the child array.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn from_fn(array: &DArray, length: usize, func: impl Fn(usize) -> usize) -> DArray {
+        let indices = IndexMap::Gather((0..length).map(func).collect());
+        DArray::from(IndexComp {array: array.clone(), indices, length})
+    }
+
+    /// Adds every element of the parent array to the element of the child array at the given index.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    fn scatter(array: &DArray, indices: &[usize], length: usize) -> DArray {
+        DArray::from(IndexComp {array: array.clone(), indices: IndexMap::Scatter(indices.into()), length})
+    }
 }
 
 impl Computation for IndexComp {
@@ -47,54 +194,240 @@ impl Computation for IndexComp {
 
     fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
         let src_len = self.array.len();
-        let inverted_indices = self.indices.iter().map(|(i, j)| (*j, *i));
-
-        vec![DArray::from(IndexComp::new(&res_grads, inverted_indices, src_len))]
+        vec![DArray::from(IndexComp {array: res_grads, indices: self.indices.inverted(), length: src_len})]
     }
 
     fn apply(&self, res_array: &mut [f64]) {
-        let data = self.array.data();
-        for (src, tar) in self.indices.iter() {
-            res_array[*tar] += data[*src];
-        }
+        for_each_summand(&self.array, &mut |data| {
+            match &self.indices {
+                IndexMap::Pairs(pairs) => {
+                    for (src, tar) in pairs.iter() {
+                        res_array[*tar] += data[*src];
+                    }
+                }
+                IndexMap::Affine {src_start, src_stride, tar_start, tar_stride, count} => {
+                    for k in 0..*count {
+                        res_array[tar_start + k * tar_stride] += data[src_start + k * src_stride];
+                    }
+                }
+                IndexMap::Gather(indices) => {
+                    for (res, src) in res_array.iter_mut().zip(indices.iter()) {
+                        *res += data[*src];
+                    }
+                }
+                IndexMap::Scatter(indices) => {
+                    for (x, tar) in data.iter().zip(indices.iter()) {
+                        res_array[*tar] += x;
+                    }
+                }
+            }
+        });
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Linear
     }
 
     fn len(&self) -> usize {
         self.length
     }
+
+    /// Every index pair reads an element of the source and adds it to an element of the result.
+    fn cost(&self) -> Cost {
+        let count = self.indices.count();
+        Cost {flops: count, bytes_read: count * F64_SIZE, bytes_written: self.length * F64_SIZE}
+    }
+
+    /// Every index pair adds an element of the source to an entry.
+    fn sparse_entries(&self) -> Option<Vec<(usize, f64)>> {
+        let data = self.array.data();
+        let mut entries = Vec::with_capacity(self.indices.count());
+        self.indices.for_each_pair(&mut |src, tar| entries.push((tar, data[src])));
+        Some(entries)
+    }
+
+    /// Checks that all indices are in the bounds of the source and result arrays,
+    /// and that a scatter has an index for every element of the source.
+    fn validate(&self) -> Result<(), String> {
+        if let IndexMap::Scatter(indices) = &self.indices {
+            if indices.len() != self.array.len() {
+                return Err(format!("{} indices were given for an array of length {}.", indices.len(), self.array.len()));
+            }
+        }
+        let Some((src, tar)) = self.indices.max_indices() else {
+            return Ok(());
+        };
+        if src >= self.array.len() {
+            return Err(format!("Source index {} is out of bounds for a source of length {}.", src, self.array.len()));
+        }
+        if tar >= self.length {
+            return Err(format!("Target index {} is out of bounds for a result of length {}.", tar, self.length));
+        }
+        Ok(())
+    }
 }
 
 impl DArray {
+    /// Sums the gradients of an array from its parents.
+    /// The index computations among the gradients, which touch fewer elements than the length of the array,
+    /// are merged into a single index computation over the concatenation of their sources,
+    /// so that the accumulated gradient keeps their sparse entries instead of summing them as dense arrays.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub(crate) fn add_gradients(grads: &[DArray]) -> DArray {
+        let length = grads[0].len();
+        let mut sparse = Vec::new();
+        let mut dense = Vec::new();
+        for grad in grads {
+            match grad.comp() {
+                NodeComp::Index(comp) if !grad.is_initialized() => sparse.push(comp),
+                _ => dense.push(grad.clone()),
+            }
+        }
+        let count = sparse.iter().map(|comp| comp.indices.count()).sum::<usize>();
+        if sparse.len() < 2 || count >= length {
+            return DArray::add_n(grads);
+        }
+
+        let mut pairs = Vec::with_capacity(count);
+        let mut offset = 0;
+        for comp in sparse.iter() {
+            comp.indices.for_each_pair(&mut |src, tar| pairs.push((src + offset, tar)));
+            offset += comp.array.len();
+        }
+        let sources = DArray::concat(&sparse.iter().map(|comp| &comp.array).collect::<Vec<_>>());
+        dense.push(DArray::from(IndexComp {array: sources, indices: IndexMap::Pairs(pairs), length}));
+        DArray::add_n(&dense)
+    }
+
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     pub fn index(&self, idx: usize) -> DArray {
-        IndexComp::map_indices(self, [(idx, 0)].iter().cloned(), 1)
+        IndexComp::from_range(self, idx..idx + 1, 0, 1)
     }
+}
 
-    /// Returns the maximal element of the array.
-    pub fn reduce_max(&self) -> DArray {
-        let mx_idx = self.data().iter().enumerate().reduce(|p1, p2| if p1.1 > p2.1 {p1} else {p2}).unwrap().0;
-        self.index(mx_idx)
+/// The elements of an array selected by its data, which receive the gradients of the selection.
+#[derive(Copy, Clone)]
+enum Route {
+    /// The first maximal or minimal element.
+    Extremum {max: bool},
+    /// The `k` largest elements, from the largest.
+    TopK(usize),
+    /// The maximal element of every window of the given length.
+    WindowMax(usize),
+}
+
+impl Route {
+    /// Returns the number of selected elements of an array of the given length.
+    fn count(&self, len: usize) -> usize {
+        match self {
+            Route::Extremum {..} => 1,
+            Route::TopK(k) => *k,
+            Route::WindowMax(window) => (len + 1).saturating_sub(*window),
+        }
     }
-    /// Returns the minimal element of the array.
-    pub fn reduce_min(&self) -> DArray {
-        let mn_idx = self.data().iter().enumerate().reduce(|p1, p2| if p1.1 < p2.1 {p1} else {p2}).unwrap().0;
-        self.index(mn_idx)
+
+    /// Returns the indices of the selected elements of the data.
+    fn indices(&self, data: &[f64]) -> Vec<usize> {
+        match self {
+            Route::Extremum {max} => vec![extremum(data, *max)],
+            Route::TopK(k) => top_indices(data, *k),
+            Route::WindowMax(window) => window_argmax(data, *window),
+        }
     }
 }
 
-/// A computation that handles summing all elements in an array.
+/// A computation passing gradients to the elements of an array selected by its data, or taking the gradients
+/// of the selected elements. The selection is calculated when the gradients are evaluated,
+/// so that deriving a selection doesn't evaluate the array.
 #[derive(Clone)]
-struct SumComp {
+struct RouteComp {
+    /// The array whose data selects the elements.
     src: DArray,
+    grads: DArray,
+    route: Route,
+    /// If set, the gradients are as long as the array, and the gradients of the selected elements are taken.
+    gather: bool,
 }
 
-impl Computation for SumComp {
+impl Computation for RouteComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone(), self.grads.clone()]
+    }
+
+    /// The selection is piecewise constant, so the derivative by the array is zero.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let zeros = DArray::with_backend(vec![0.; self.src.len()], self.src.backend());
+        vec![zeros, DArray::from(RouteComp {grads: res_grads, gather: !self.gather, ..self.clone()})]
+    }
+
+    fn len(&self) -> usize {
+        if self.gather { self.route.count(self.src.len()) } else { self.src.len() }
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let grads = self.grads.data();
+        for (k, idx) in self.route.indices(self.src.data()).into_iter().enumerate() {
+            if self.gather {
+                res_array[k] += grads[idx];
+            } else {
+                res_array[idx] += grads[k];
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let expected = if self.gather { self.src.len() } else { self.route.count(self.src.len()) };
+        if self.grads.len() == expected {
+            Ok(())
+        } else {
+            Err(format!("Got {} gradients instead of {}.", self.grads.len(), expected))
+        }
+    }
+}
+
+/// Compares elements by how extremal they are for the maximum or the minimum.
+/// NaN is more extremal than every number for both, so that NaN elements are selected by every reduction
+/// choosing elements by their order, and propagate through them.
+fn cmp_extremal(x: f64, y: f64, max: bool) -> Ordering {
+    match (x.is_nan(), y.is_nan()) {
+        (false, false) if max => x.partial_cmp(&y).unwrap(),
+        (false, false) => y.partial_cmp(&x).unwrap(),
+        (x_nan, y_nan) => x_nan.cmp(&y_nan),
+    }
+}
+
+/// Returns the index of the maximal or the minimal element of the data, or of its first NaN.
+/// If several elements are extremal, the first of them is chosen.
+fn extremum(data: &[f64], max: bool) -> usize {
+    data.iter().enumerate()
+        .reduce(|p1, p2| if cmp_extremal(*p2.1, *p1.1, max).is_gt() {p2} else {p1})
+        .unwrap().0
+}
+
+/// A computation taking the maximal or the minimal element of an array, or its index.
+#[derive(Clone)]
+pub struct MaxReduceComp {
+    src: DArray,
+    /// If set, takes the maximum, and otherwise the minimum.
+    max: bool,
+    /// If set, returns the index of the extremal element instead of its value.
+    index: bool,
+}
+
+impl Computation for MaxReduceComp {
     fn sources(&self) -> Vec<DArray> {
         vec![self.src.clone()]
     }
 
+    /// The gradient is passed to the extremal element, which is the first of them on ties.
+    /// Indices are constant, and have zero derivatives.
     fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
         assert_eq!(res_grads.len(), self.len());
-        vec![DArray::from(ExpandComp::new(res_grads, self.src.len()))]
+        if self.index {
+            return vec![DArray::with_backend(vec![0.; self.src.len()], self.src.backend())];
+        }
+        let route = Route::Extremum {max: self.max};
+        vec![DArray::from(RouteComp {src: self.src.clone(), grads: res_grads, route, gather: false})]
     }
 
     fn len(&self) -> usize {
@@ -103,119 +436,2636 @@ impl Computation for SumComp {
 
     fn apply(&self, res_array: &mut [f64]) {
         assert_eq!(res_array.len(), 1);
-        for v in self.src.data() {
-            res_array[0] += v;
+        let data = self.src.data();
+        let idx = extremum(data, self.max);
+        res_array[0] += if self.index { idx as f64 } else { data[idx] };
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: self.src.len(), bytes_read: self.src.len() * F64_SIZE, bytes_written: F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.src.len() == 0 {
+            Err("The extremum of an empty array is undefined.".to_string())
+        } else {
+            Ok(())
         }
     }
 }
 
 impl DArray {
-    pub fn sum(&self) -> DArray {
-        DArray::from(SumComp {src: self.clone()})
+    /// Returns the maximal element of the array, which is NaN if the array has NaN elements.
+    /// The gradient is passed to the first maximal element.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn reduce_max(&self) -> DArray {
+        DArray::from(MaxReduceComp {src: self.clone(), max: true, index: false})
+    }
+    /// Returns the minimal element of the array, which is NaN if the array has NaN elements.
+    /// The gradient is passed to the first minimal element.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn reduce_min(&self) -> DArray {
+        DArray::from(MaxReduceComp {src: self.clone(), max: false, index: false})
+    }
+
+    /// Returns the index of the first maximal element of the array, or of its first NaN element, evaluating it.
+    pub fn argmax(&self) -> usize {
+        self.argmax_array().item() as usize
+    }
+    /// Returns the index of the first minimal element of the array, or of its first NaN element, evaluating it.
+    pub fn argmin(&self) -> usize {
+        self.argmin_array().item() as usize
+    }
+    /// Returns a scalar array holding the index of the first maximal element of the array.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn argmax_array(&self) -> DArray {
+        DArray::from(MaxReduceComp {src: self.clone(), max: true, index: true})
+    }
+    /// Returns a scalar array holding the index of the first minimal element of the array.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn argmin_array(&self) -> DArray {
+        DArray::from(MaxReduceComp {src: self.clone(), max: false, index: true})
     }
 }
 
+/// Returns the indices of the `k` largest elements of the data, from the largest, where NaN is larger than every number.
+/// Equal elements are ordered by their indices.
+fn top_indices(data: &[f64], k: usize) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..data.len()).collect();
+    let order = |i1: &usize, i2: &usize| cmp_extremal(data[*i2], data[*i1], true).then(i1.cmp(i2));
+    if k < indices.len() {
+        indices.select_nth_unstable_by(k, order);
+        indices.truncate(k);
+    }
+    indices.sort_unstable_by(order);
+    indices
+}
 
-/// A computation that handles expanding a scalar to an array.
-/// This operation can be done with IndexComp, but this should be both lighter, since it doesn't require
-/// the index mapping array, and easier to use.
+/// A computation taking the `k` largest elements of an array, from the largest.
 #[derive(Clone)]
-pub struct ExpandComp {
+pub struct TopKComp {
     src: DArray,
-    length: usize,
+    k: usize,
 }
 
-impl ExpandComp {
-    pub fn new(src: DArray, length: usize) -> ExpandComp {
-        assert_eq!(src.len(), 1);
-        ExpandComp {src, length}
+impl Computation for TopKComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    /// The gradients are passed to the selected elements.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![DArray::from(RouteComp {src: self.src.clone(), grads: res_grads, route: Route::TopK(self.k), gather: false})]
+    }
+
+    fn len(&self) -> usize {
+        self.k
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let data = self.src.data();
+        for (res, idx) in res_array.iter_mut().zip(top_indices(data, self.k)) {
+            *res += data[idx];
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 2 * self.src.len(), bytes_read: self.src.len() * F64_SIZE, bytes_written: self.k * F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.k <= self.src.len() {
+            Ok(())
+        } else {
+            Err(format!("Cannot take the {} largest elements of an array of length {}.", self.k, self.src.len()))
+        }
     }
 }
 
-impl Computation for ExpandComp {
+impl DArray {
+    /// Returns the `k` largest elements of the array, from the largest, where NaN is larger than every number.
+    /// Equal elements are ordered by their indices, and the gradients are passed to the selected elements.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn topk(&self, k: usize) -> DArray {
+        DArray::from(TopKComp {src: self.clone(), k})
+    }
+    /// Returns the indices of the elements selected by [`DArray::topk`], evaluating the array.
+    pub fn topk_indices(&self, k: usize) -> Vec<usize> {
+        assert!(k <= self.len(), "Cannot take the {} largest elements of an array of length {}.", k, self.len());
+        top_indices(self.data(), k)
+    }
+}
+
+/// A computation that handles summing all elements in an array.
+#[derive(Clone)]
+pub struct SumComp {
+    src: DArray,
+}
+
+impl Computation for SumComp {
     fn sources(&self) -> Vec<DArray> {
         vec![self.src.clone()]
     }
 
     fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
-        vec![DArray::from(SumComp {src: res_grads})]
+        assert_eq!(res_grads.len(), self.len());
+        vec![DArray::from(ExpandComp::new(res_grads, self.src.len()))]
     }
 
     fn len(&self) -> usize {
-        self.length
+        1
     }
 
     fn apply(&self, res_array: &mut [f64]) {
-        assert_eq!(res_array.len(), self.len());
-        let src = self.src.data()[0];
-        for i in res_array.iter_mut() {
-            *i += src;
+        assert_eq!(res_array.len(), 1);
+        if cfg!(feature = "compensated-sum") {
+            let mut total = Accumulator::default();
+            for_each_summand(&self.src, &mut |data| data.iter().for_each(|x| total.add(*x)));
+            res_array[0] += total.total();
+        } else {
+            let backend = self.src.backend();
+            for_each_summand(&self.src, &mut |data| res_array[0] += backend.sum(data));
         }
     }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Linear
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: self.src.len(), bytes_read: self.src.len() * F64_SIZE, bytes_written: F64_SIZE}
+    }
 }
 
-/// If the source array is a scalar, expand it to an array of the same length as the target length.
-pub fn expand_array(src: DArray, tar_len: &DArray) -> DArray {
-    if src.is_scalar() && !tar_len.is_scalar() {
-        DArray::from(ExpandComp::new(src, tar_len.len()))
-    } else {
-        src
+impl DArray {
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn sum(&self) -> DArray {
+        DArray::from(SumComp {src: self.clone()})
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::DArray;
-    use crate::test_utils::*;
+/// A computation calculating the inner product of two arrays in a single pass.
+#[derive(Clone)]
+pub struct DotComp {
+    p1: DArray,
+    p2: DArray,
+}
 
+impl Computation for DotComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.p1.clone(), self.p2.clone()]
+    }
 
-    #[test]
-    fn test_sum() {
-        let mut rng = StdRng::from_seed(SEED);
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        assert_eq!(res_grads.len(), self.len());
+        vec![&res_grads * &self.p2, &res_grads * &self.p1]
+    }
 
-        for _ in 0..100 {
-            let arr: Vec<f64> = (0..10).map(|_|rng.gen::<f64>()).collect();
-            let arr_sum = arr.iter().sum();
-            let array_arr = DArray::from(arr);
-            let array_sum = array_arr.sum();
+    fn len(&self) -> usize {
+        1
+    }
 
-            assert!(array_sum.is_scalar());
-            assert_close(arr_sum, array_sum.data()[0]);
-            array_sum.derive().get(&array_arr).unwrap().data().iter().for_each(|f|assert_close(*f, 1.))
+    fn apply(&self, res_array: &mut [f64]) {
+        assert_eq!(res_array.len(), 1);
+        if cfg!(feature = "compensated-sum") {
+            let total: Accumulator = izip!(self.p1.data(), self.p2.data()).map(|(p1, p2)| p1 * p2).collect();
+            res_array[0] += total.total();
+        } else {
+            res_array[0] += self.p1.backend().dot(self.p1.data(), self.p2.data());
         }
     }
 
-    #[test]
-    fn test_max() {
-        let mut rng = StdRng::from_seed(SEED);
+    fn cost(&self) -> Cost {
+        Cost {flops: 2 * self.p1.len(), bytes_read: 2 * self.p1.len() * F64_SIZE, bytes_written: F64_SIZE}
+    }
 
-        for _ in 0..100 {
-            let arr: Vec<f64> = (0..10).map(|_|rng.gen::<f64>()).collect();
-            let arr_max = arr.iter().cloned().reduce(|f1, f2|f1.max(f2)).unwrap();
-            let array_arr = DArray::from(arr);
-            let array_max = array_arr.reduce_max();
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.p1, &self.p2)
+    }
+}
 
-            assert!(array_max.is_scalar());
-            assert_close(arr_max, array_max.data()[0]);
-        }
+impl DArray {
+    /// Calculates the inner product of two arrays of the same length,
+    /// without allocating their pointwise product.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn dot(&self, other: &DArray) -> DArray {
+        DArray::from(DotComp {p1: self.clone(), p2: other.clone()})
     }
+}
 
-    #[test]
-    fn test_min() {
-        let mut rng = StdRng::from_seed(SEED);
+/// A computation that handles multiplying all elements in an array.
+#[derive(Clone)]
+pub struct ProdComp {
+    src: DArray,
+}
 
-        for _ in 0..100 {
-            let arr: Vec<f64> = (0..10).map(|_|rng.gen::<f64>()).collect();
-            let arr_min = arr.iter().cloned().reduce(|f1, f2|f1.min(f2)).unwrap();
-            let array_arr = DArray::from(arr);
-            let array_min = array_arr.reduce_min();
+impl Computation for ProdComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
 
-            assert!(array_min.is_scalar());
-            assert_close(arr_min, array_min.data()[0]);
-        }
+    /// The derivative by every element is the product of the other elements, which is calculated without
+    /// dividing by the element, so that it is correct for zero elements.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        assert_eq!(res_grads.len(), self.len());
+        let exclusive = DArray::from(ExclusiveProdComp {src: self.src.clone()});
+        vec![exclusive * res_grads]
+    }
+
+    fn len(&self) -> usize {
+        1
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        assert_eq!(res_array.len(), 1);
+        res_array[0] += self.src.data().iter().product::<f64>();
+    }
+}
+
+/// A computation calculating the product of all other elements of an array for every element,
+/// from the products of the elements before and after it.
+#[derive(Clone)]
+struct ExclusiveProdComp {
+    src: DArray,
+}
+
+impl Computation for ExclusiveProdComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    /// The derivative of the product of the elements other than `x_i` by `x_k` is the product of the elements
+    /// other than `x_i` and `x_k`. Summed over the gradients, it is calculated by dividing by the elements,
+    /// so the second derivatives of products are only correct for nonzero elements.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let ratios = &res_grads / &self.src;
+        vec![DArray::from(self.clone()) * (ratios.sum() - &ratios)]
+    }
+
+    fn len(&self) -> usize {
+        self.src.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let data = self.src.data();
+        let mut prefixes = Vec::with_capacity(data.len());
+        let mut prefix = 1.;
+        for x in data {
+            prefixes.push(prefix);
+            prefix *= x;
+        }
+        let mut suffix = 1.;
+        for (res, prefix, x) in izip!(res_array.iter_mut(), prefixes, data).rev() {
+            *res += prefix * suffix;
+            suffix *= x;
+        }
+    }
+}
+
+impl DArray {
+    /// Returns the product of all elements of the array.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn prod(&self) -> DArray {
+        DArray::from(ProdComp {src: self.clone()})
+    }
+}
+
+/// A computation calculating the cumulative sums of an array, from its start or from its end.
+#[derive(Clone)]
+pub struct CumSumComp {
+    src: DArray,
+    /// If set, every element is the sum of the elements from it to the end of the array.
+    reverse: bool,
+}
+
+impl Computation for CumSumComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    /// Every element of the source receives the gradients of the sums containing it,
+    /// which are the cumulative sums of the gradients in the other direction.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![DArray::from(CumSumComp {src: res_grads, reverse: !self.reverse})]
+    }
+
+    fn len(&self) -> usize {
+        self.src.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        for_each_summand(&self.src, &mut |data| {
+            let mut total = 0.;
+            if self.reverse {
+                for (res, x) in res_array.iter_mut().zip(data).rev() {
+                    total += x;
+                    *res += total;
+                }
+            } else {
+                for (res, x) in res_array.iter_mut().zip(data) {
+                    total += x;
+                    *res += total;
+                }
+            }
+        });
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Linear
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: self.len(), bytes_read: self.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+}
+
+/// A computation calculating the cumulative products of an array.
+#[derive(Clone)]
+pub struct CumProdComp {
+    src: DArray,
+}
+
+impl Computation for CumProdComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![DArray::from(CumProdGradComp {src: self.src.clone(), res_grads})]
+    }
+
+    fn len(&self) -> usize {
+        self.src.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let mut total = 1.;
+        for (res, x) in res_array.iter_mut().zip(self.src.data()) {
+            total *= x;
+            *res += total;
+        }
+    }
+}
+
+/// A computation calculating the gradients of cumulative products by their source.
+///
+/// The gradient of `x_i` is `sum_{j >= i} g_j * prod_{k <= j, k != i} x_k`, which is the product of the elements
+/// before `x_i` and of `s_i = g_i + x_{i+1} * s_{i+1}`, so that it is correct for zero elements.
+#[derive(Clone)]
+struct CumProdGradComp {
+    src: DArray,
+    res_grads: DArray,
+}
+
+impl Computation for CumProdGradComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone(), self.res_grads.clone()]
+    }
+
+    /// The gradients are `G_i = r_i / x_i` for the reverse cumulative sums `r` of `g * y`, where `y` are the
+    /// cumulative products. They are derived in this form, so the second derivatives of cumulative products
+    /// are only correct for nonzero elements.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let prefix_grads = DArray::from(CumSumComp {src: &res_grads / &self.src, reverse: false});
+        let products = self.src.cumprod();
+        let direct = res_grads * DArray::from(self.clone()) / &self.src;
+        let src_grads = DArray::from(CumProdGradComp {src: self.src.clone(), res_grads: &prefix_grads * &self.res_grads});
+        vec![src_grads - direct, products * prefix_grads]
+    }
+
+    fn len(&self) -> usize {
+        self.src.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let data = self.src.data();
+        let mut prefixes = Vec::with_capacity(data.len());
+        let mut prefix = 1.;
+        for x in data {
+            prefixes.push(prefix);
+            prefix *= x;
+        }
+        let mut suffix = 0.;
+        let mut next = 0.;
+        for (res, prefix, x, g) in izip!(res_array.iter_mut(), prefixes, data, self.res_grads.data()).rev() {
+            suffix = g + next * suffix;
+            *res += prefix * suffix;
+            next = *x;
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.src, &self.res_grads)
+    }
+}
+
+impl DArray {
+    /// Returns the cumulative sums of the array, where every element is the sum of the elements up to it.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn cumsum(&self) -> DArray {
+        DArray::from(CumSumComp {src: self.clone(), reverse: false})
+    }
+    /// Returns the cumulative products of the array, where every element is the product of the elements up to it.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn cumprod(&self) -> DArray {
+        DArray::from(CumProdComp {src: self.clone()})
+    }
+}
+
+/// A computation calculating the variance of the elements of an array, or their standard deviation,
+/// in two passes over the data.
+/// The sum of the squared deviations is divided by the number of elements minus `ddof`.
+#[derive(Clone)]
+pub struct VarianceComp {
+    src: DArray,
+    ddof: usize,
+    /// If set, calculates the standard deviation instead of the variance.
+    std: bool,
+}
+
+impl Computation for VarianceComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    /// The derivative of the variance by every element is `2 * (x - mean) / (n - ddof)`, and the derivative
+    /// of the standard deviation divides it by twice the deviation. The derivatives of the standard
+    /// deviation of constant arrays are zero.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        assert_eq!(res_grads.len(), self.len());
+        let len = self.src.len() as f64;
+        let centered = &self.src - self.src.sum() * (1. / len);
+        let scale = if self.std {
+            res_grads / DArray::from(self.clone()).max(f64::MIN_POSITIVE) * (1. / (len - self.ddof as f64))
+        } else {
+            res_grads * (2. / (len - self.ddof as f64))
+        };
+        vec![centered * scale]
+    }
+
+    fn len(&self) -> usize {
+        1
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        assert_eq!(res_array.len(), 1);
+        let data = self.src.data();
+        let mean = data.iter().copied().collect::<Accumulator>().total() / data.len() as f64;
+        let squares = data.iter().map(|x| (x - mean) * (x - mean)).collect::<Accumulator>().total();
+        let variance = squares / (data.len() - self.ddof) as f64;
+        res_array[0] += if self.std { variance.sqrt() } else { variance };
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 4 * self.src.len(), bytes_read: 2 * self.src.len() * F64_SIZE, bytes_written: F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.src.len() > self.ddof {
+            Ok(())
+        } else {
+            Err(format!("The variance of {} elements is undefined with {} delta degrees of freedom.", self.src.len(), self.ddof))
+        }
+    }
+}
+
+impl DArray {
+    /// Returns the variance of the elements, dividing the sum of their squared deviations by the number
+    /// of elements minus `ddof`. A `ddof` of 0 gives the biased population variance, and 1 the unbiased
+    /// sample variance.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn var(&self, ddof: usize) -> DArray {
+        DArray::from(VarianceComp {src: self.clone(), ddof, std: false})
+    }
+    /// Returns the standard deviation of the elements, the square root of [`DArray::var`].
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn std(&self, ddof: usize) -> DArray {
+        DArray::from(VarianceComp {src: self.clone(), ddof, std: true})
+    }
+}
+
+/// A computation calculating the mean and the population variance of an array in a single pass,
+/// with Welford's algorithm. The result holds the mean followed by the variance.
+/// With the `compensated-sum` feature, the updates of the mean and of the sum of squares are compensated like other sums.
+#[derive(Clone)]
+pub struct MomentsComp {
+    src: DArray,
+}
+
+impl Computation for MomentsComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    /// The derivative of the mean by every element is `1 / n`, and the derivative of the variance is `2 * (x - mean) / n`.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        assert_eq!(res_grads.len(), self.len());
+        let len = self.src.len() as f64;
+        let centered = &self.src - DArray::from(self.clone()).index(0);
+        vec![centered * (res_grads.index(1) * (2. / len)) + res_grads.index(0) * (1. / len)]
+    }
+
+    fn len(&self) -> usize {
+        2
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        assert_eq!(res_array.len(), 2);
+        let mut mean = Accumulator::default();
+        let mut squares = Accumulator::default();
+        for (idx, x) in self.src.data().iter().enumerate() {
+            let delta = x - mean.total();
+            mean.add(delta / (idx + 1) as f64);
+            squares.add(delta * (x - mean.total()));
+        }
+        res_array[0] += mean.total();
+        res_array[1] += squares.total() / self.src.len() as f64;
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 6 * self.src.len(), bytes_read: self.src.len() * F64_SIZE, bytes_written: 2 * F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.src.len() > 0 {
+            Ok(())
+        } else {
+            Err("The moments of an empty array are undefined.".to_string())
+        }
+    }
+}
+
+impl DArray {
+    /// Returns the mean and the population variance of the elements, calculated together in a single pass.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn moments(&self) -> (DArray, DArray) {
+        let moments = DArray::from(MomentsComp {src: self.clone()});
+        (moments.index(0), moments.index(1))
+    }
+}
+
+/// A computation summing or averaging the elements of an array where a mask is nonzero.
+#[derive(Clone)]
+pub struct MaskedSumComp {
+    src: DArray,
+    mask: DArray,
+    /// If set, divides the sum by the number of nonzero elements of the mask.
+    mean: bool,
+}
+
+impl MaskedSumComp {
+    /// Returns the factor of the sum, which is zero for the mean of an empty mask.
+    fn scale(&self) -> f64 {
+        let count = self.mask.data().iter().filter(|m| **m != 0.).count();
+        match (self.mean, count) {
+            (false, _) => 1.,
+            (true, 0) => 0.,
+            (true, count) => 1. / count as f64,
+        }
+    }
+}
+
+impl Computation for MaskedSumComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone(), self.mask.clone()]
+    }
+
+    /// The gradients of the masked elements are zero. The mask is constant, and has zero derivatives.
+    /// The number of elements of a mean is counted in the graph, so that deriving it doesn't evaluate the mask.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        assert_eq!(res_grads.len(), self.len());
+        let res_grads = if self.mean {
+            // The gradients of an empty mask are all masked, so its count can be replaced by 1.
+            let ones = DArray::with_backend(vec![1.; self.mask.len()], self.mask.backend());
+            res_grads / ones.masked_sum(&self.mask).max(1.)
+        } else {
+            res_grads
+        };
+        let grads = DArray::select(&self.mask, &res_grads, &DArray::from(0.));
+        vec![grads, DArray::with_backend(vec![0.; self.mask.len()], self.mask.backend())]
+    }
+
+    fn len(&self) -> usize {
+        1
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        assert_eq!(res_array.len(), 1);
+        let total = self.src.data().iter().zip(self.mask.data())
+            .filter(|(_, m)| **m != 0.)
+            .map(|(x, _)| *x)
+            .collect::<Accumulator>();
+        res_array[0] += total.total() * self.scale();
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 2 * self.src.len(), bytes_read: 2 * self.src.len() * F64_SIZE, bytes_written: F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_same_len(&self.src, &self.mask)
+    }
+}
+
+impl DArray {
+    /// Returns the sum of the elements where the mask is nonzero.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn masked_sum(&self, mask: &DArray) -> DArray {
+        DArray::from(MaskedSumComp {src: self.clone(), mask: mask.clone(), mean: false})
+    }
+    /// Returns the mean of the elements where the mask is nonzero, which is zero if the mask is zero.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn masked_mean(&self, mask: &DArray) -> DArray {
+        DArray::from(MaskedSumComp {src: self.clone(), mask: mask.clone(), mean: true})
+    }
+}
+
+/// A computation calculating the Lp norm `(sum |x|^p)^(1/p)` of an array, for a finite `p >= 1`.
+/// The norm for an infinite power is built by [`DArray::norm_lp`] as a reduction of the absolute values.
+#[derive(Clone)]
+pub struct NormComp {
+    src: DArray,
+    power: f64,
+}
+
+impl Computation for NormComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    /// The derivative by every element is `sign(x) * |x|^(p - 1) / norm^(p - 1)`, which is `sign(x)` for the L1 norm
+    /// and `x / norm` for the L2 norm. The derivatives at the origin are zero for `p > 1`.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        assert_eq!(res_grads.len(), self.len());
+        if self.power == 1. {
+            return vec![self.src.signum() * res_grads];
+        }
+        let norm = DArray::from(self.clone()).max(f64::MIN_POSITIVE);
+        let scale = res_grads / norm.powf(self.power - 1.);
+        vec![self.src.signed_pow(self.power - 1.) * scale]
+    }
+
+    fn len(&self) -> usize {
+        1
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        assert_eq!(res_array.len(), 1);
+        let data = self.src.data();
+        res_array[0] += match self.power {
+            1. => data.iter().map(|x| x.abs()).sum::<f64>(),
+            2. => data.iter().map(|x| x * x).sum::<f64>().sqrt(),
+            power => data.iter().map(|x| x.abs().powf(power)).sum::<f64>().powf(1. / power),
+        };
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.power >= 1. {
+            Ok(())
+        } else {
+            Err(format!("The power of a norm must be at least 1, but is {}.", self.power))
+        }
+    }
+}
+
+impl DArray {
+    /// Returns the L1 norm, the sum of the absolute values of the elements.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn norm_l1(&self) -> DArray {
+        self.norm_lp(1.)
+    }
+    /// Returns the L2 norm, the square root of the sum of the squares of the elements.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn norm_l2(&self) -> DArray {
+        self.norm_lp(2.)
+    }
+    /// Returns the Lp norm `(sum |x|^p)^(1/p)` for `p >= 1`.
+    /// The norm for an infinite power is the maximal absolute value of the elements,
+    /// whose gradient is passed to the first element of maximal absolute value.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn norm_lp(&self, power: f64) -> DArray {
+        if power == f64::INFINITY {
+            return self.abs().reduce_max();
+        }
+        DArray::from(NormComp {src: self.clone(), power})
+    }
+}
+
+/// A computation calculating the softmax of an array, or its logarithm.
+/// The maximal element is subtracted before exponentiating, so that large elements don't overflow.
+#[derive(Clone)]
+pub struct SoftmaxComp {
+    src: DArray,
+    /// If set, calculates the logarithm of the softmax.
+    log: bool,
+}
+
+impl Computation for SoftmaxComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    /// The gradients of the softmax `y` are `y * (g - sum(g * y))`,
+    /// and the gradients of its logarithm are `g - y * sum(g)`.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        if self.log {
+            let softmax = self.src.softmax();
+            vec![&res_grads - softmax * res_grads.sum()]
+        } else {
+            let softmax = DArray::from(self.clone());
+            let total = (&res_grads * &softmax).sum();
+            vec![softmax * (res_grads - total)]
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.src.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let data = self.src.data();
+        let max = data.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        let total = data.iter().map(|x| (x - max).exp()).collect::<Accumulator>().total();
+        if self.log {
+            let shift = max + total.ln();
+            for (res, x) in res_array.iter_mut().zip(data) {
+                *res += x - shift;
+            }
+        } else {
+            for (res, x) in res_array.iter_mut().zip(data) {
+                *res += (x - max).exp() / total;
+            }
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 4 * self.len(), bytes_read: 2 * self.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+}
+
+impl DArray {
+    /// Returns the softmax of the array, the exponents of the elements divided by their sum.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn softmax(&self) -> DArray {
+        DArray::from(SoftmaxComp {src: self.clone(), log: false})
+    }
+    /// Returns the logarithm of the softmax of the array, the elements minus the logarithm of the sum of their exponents.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn log_softmax(&self) -> DArray {
+        DArray::from(SoftmaxComp {src: self.clone(), log: true})
+    }
+}
+
+/// A computation dividing an array by its L2 norm, clamped from below by `eps`.
+#[derive(Clone)]
+pub struct NormalizeComp {
+    src: DArray,
+    eps: f64,
+}
+
+impl Computation for NormalizeComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    /// Projects the gradients onto the tangent space of the unit sphere at the result.
+    /// If the norm is clamped, the result is a constant multiple of the source, and the gradients
+    /// are only scaled.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let eps_squared = self.eps * self.eps;
+        let squared_norm = (&self.src * &self.src).sum();
+        let inv_norm = squared_norm.max(eps_squared).rsqrt();
+        let normalized = &self.src * &inv_norm;
+        let projection = (&normalized * &res_grads).sum() * squared_norm.gt(eps_squared);
+        vec![(res_grads - normalized * projection) * inv_norm]
+    }
+
+    fn len(&self) -> usize {
+        self.src.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let src = self.src.data();
+        let norm = src.iter().map(|x| x * x).sum::<f64>().sqrt().max(self.eps);
+        for (res, x) in res_array.iter_mut().zip(src) {
+            *res += x / norm;
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 3 * self.src.len(), bytes_read: self.src.len() * F64_SIZE, bytes_written: self.src.len() * F64_SIZE}
+    }
+}
+
+impl DArray {
+    /// Divides the array by its L2 norm, or by `eps` if the norm is smaller.
+    /// Computed in a single computation, which is more stable than dividing by the norm.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn normalize(&self, eps: f64) -> DArray {
+        DArray::from(NormalizeComp {src: self.clone(), eps})
+    }
+}
+
+/// The reduction applied to the windows of a rolling computation.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+enum Rolling {
+    Sum,
+    Mean,
+    Max,
+}
+
+/// Returns the index of the maximal element of every window of the given length, where NaN is larger than every number,
+/// using a monotonic queue of the candidates for the maximum.
+fn window_argmax(data: &[f64], window: usize) -> Vec<usize> {
+    let mut candidates = std::collections::VecDeque::new();
+    let mut argmax = Vec::with_capacity(data.len() + 1 - window);
+    for (idx, val) in data.iter().enumerate() {
+        while candidates.back().is_some_and(|&last| cmp_extremal(data[last], *val, true).is_le()) {
+            candidates.pop_back();
+        }
+        candidates.push_back(idx);
+        if candidates[0] + window <= idx {
+            candidates.pop_front();
+        }
+        if idx + 1 >= window {
+            argmax.push(candidates[0]);
+        }
+    }
+    argmax
+}
+
+/// A computation reducing every window of consecutive elements of an array.
+/// The result has an element for every window fully contained in the array.
+#[derive(Clone)]
+pub struct RollingComp {
+    src: DArray,
+    window: usize,
+    reduction: Rolling,
+}
+
+impl Computation for RollingComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    /// Every element of the source receives the gradients of the windows containing it,
+    /// which is a rolling sum of the gradients padded with zeros.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let padding = self.window - 1;
+        let grads = match self.reduction {
+            Rolling::Sum | Rolling::Mean => {
+                let padded = IndexComp::from_range(&res_grads, 0..self.len(), padding, self.len() + 2 * padding);
+                let grads = padded.rolling_sum(self.window);
+                if self.reduction == Rolling::Mean { grads * (1. / self.window as f64) } else { grads }
+            }
+            Rolling::Max => {
+                let route = Route::WindowMax(self.window);
+                DArray::from(RouteComp {src: self.src.clone(), grads: res_grads, route, gather: false})
+            }
+        };
+        vec![grads]
+    }
+
+    fn len(&self) -> usize {
+        (self.src.len() + 1).saturating_sub(self.window)
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        match self.reduction {
+            Rolling::Sum | Rolling::Mean => {
+                let scale = if self.reduction == Rolling::Mean { 1. / self.window as f64 } else { 1. };
+                for_each_summand(&self.src, &mut |data| {
+                    let mut total: f64 = data[..self.window - 1].iter().sum();
+                    for (idx, res) in res_array.iter_mut().enumerate() {
+                        total += data[idx + self.window - 1];
+                        *res += total * scale;
+                        total -= data[idx];
+                    }
+                });
+            }
+            Rolling::Max => {
+                let data = self.src.data();
+                for (res, idx) in res_array.iter_mut().zip(window_argmax(data, self.window)) {
+                    *res += data[idx];
+                }
+            }
+        }
+    }
+
+    /// Rolling sums and means are linear, and can read through their source.
+    fn get_type(&self) -> ComputationType {
+        match self.reduction {
+            Rolling::Sum | Rolling::Mean => ComputationType::Linear,
+            Rolling::Max => ComputationType::Other,
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 2 * self.src.len(), bytes_read: self.src.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+
+    /// Checks that the window is nonempty and fits in the source.
+    fn validate(&self) -> Result<(), String> {
+        if self.window == 0 || self.window > self.src.len() {
+            return Err(format!("The window length {} is invalid for an array of length {}.", self.window, self.src.len()));
+        }
+        Ok(())
+    }
+}
+
+impl DArray {
+    /// Sums every window of `window` consecutive elements.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn rolling_sum(&self, window: usize) -> DArray {
+        DArray::from(RollingComp {src: self.clone(), window, reduction: Rolling::Sum})
+    }
+    /// Averages every window of `window` consecutive elements.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn rolling_mean(&self, window: usize) -> DArray {
+        DArray::from(RollingComp {src: self.clone(), window, reduction: Rolling::Mean})
+    }
+    /// Takes the maximum of every window of `window` consecutive elements, which is NaN if the window has NaN elements.
+    /// The gradients of a window go to its maximal element.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn rolling_max(&self, window: usize) -> DArray {
+        DArray::from(RollingComp {src: self.clone(), window, reduction: Rolling::Max})
+    }
+}
+
+
+/// A computation taking the elements of an array where a mask is nonzero, in order,
+/// or placing the elements of an array back at the positions where the mask is nonzero.
+/// The length of a selection depends on the data of the mask, so it is deferred until it is needed.
+#[derive(Clone)]
+pub struct MaskSelectComp {
+    src: DArray,
+    mask: DArray,
+    /// If set, the source has an element for every nonzero element of the mask,
+    /// which are placed at their positions in an array as long as the mask.
+    scatter: bool,
+}
+
+impl Computation for MaskSelectComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone(), self.mask.clone()]
+    }
+
+    /// The derivative of a selection places the gradients back at the selected positions, and the reverse.
+    /// The mask is piecewise constant, so its derivative is zero.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let zeros = DArray::with_backend(vec![0.; self.mask.len()], self.mask.backend());
+        vec![DArray::from(MaskSelectComp {src: res_grads, scatter: !self.scatter, ..self.clone()}), zeros]
+    }
+
+    /// Evaluates the mask to count the selected elements of a selection.
+    fn len(&self) -> usize {
+        if self.scatter {
+            self.mask.len()
+        } else {
+            self.mask.data().iter().filter(|m| **m != 0.).count()
+        }
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let selected = self.mask.data().iter().enumerate().filter(|(_, m)| **m != 0.).map(|(idx, _)| idx);
+        let data = self.src.data();
+        for (pos, idx) in selected.enumerate() {
+            if self.scatter {
+                res_array[idx] += data[pos];
+            } else {
+                res_array[pos] += data[idx];
+            }
+        }
+    }
+
+    fn deferred_len(&self) -> bool {
+        !self.scatter
+    }
+
+    /// Checks that the mask has an element for every element of a selected array.
+    /// The number of elements placed back isn't checked, since counting them evaluates the mask.
+    fn validate(&self) -> Result<(), String> {
+        if !self.scatter && self.src.len() != self.mask.len() {
+            return Err(format!("The mask has length {} instead of {}.", self.mask.len(), self.src.len()));
+        }
+        Ok(())
+    }
+}
+
+impl DArray {
+    /// Returns the sums of the elements of every segment, where `segment_ids` contains the segment of every element.
+    /// Segments without elements are zero.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn segment_sum(&self, segment_ids: &[usize], num_segments: usize) -> DArray {
+        IndexComp::scatter(self, segment_ids, num_segments)
+    }
+    /// Returns the elements of the array at the given indices, which may repeat, as used for embedding lookups.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn gather(&self, indices: &[usize]) -> DArray {
+        IndexComp::from_fn(self, indices.len(), |idx| indices[idx])
+    }
+    /// Adds every element of the array to the given index of a zero array of length `len`.
+    /// This is the transpose of [`DArray::gather`], and the same as [`DArray::segment_sum`].
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn scatter_add(&self, indices: &[usize], len: usize) -> DArray {
+        self.segment_sum(indices, len)
+    }
+    /// Returns the elements of the array where the mask is nonzero, in order.
+    /// The length of the result depends on the data of the mask, so the mask is only evaluated
+    /// when the length is first needed, by evaluating the result or by building a computation checking it.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn mask_select(&self, mask: &DArray) -> DArray {
+        DArray::from(MaskSelectComp {src: self.clone(), mask: mask.clone(), scatter: false})
+    }
+    /// Permutes the array, so that the `i`-th element of the result is the `perm[i]`-th element of the array.
+    /// The gradients are permuted back by the inverse permutation.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn permute(&self, perm: &[usize]) -> DArray {
+        assert_eq!(perm.len(), self.len(), "The permutation has length {} instead of {}.", perm.len(), self.len());
+        let mut seen = vec![false; perm.len()];
+        for idx in perm {
+            assert!(*idx < perm.len() && !std::mem::replace(&mut seen[*idx], true), "The indices are not a permutation.");
+        }
+        self.gather(perm)
+    }
+    /// Permutes the array randomly.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn shuffle(&self, rng: &mut impl rand::Rng) -> DArray {
+        let mut perm: Vec<usize> = (0..self.len()).collect();
+        perm.shuffle(rng);
+        self.gather(&perm)
+    }
+}
+
+/// A computation summing the rows or the columns of a matrix stored by rows, or expanding them back to the matrix.
+/// The rows are the chunks of `chunk_len` consecutive elements, and the columns are the elements at the same
+/// position in every chunk. Expanding the rows repeats every element, and expanding the columns tiles the array.
+#[derive(Clone)]
+pub struct ChunkComp {
+    src: DArray,
+    chunk_len: usize,
+    num_chunks: usize,
+    /// If set, reduces the columns, and otherwise the rows.
+    strided: bool,
+    /// If set, the source holds the sums, which are taken to every element of their row or column.
+    expand: bool,
+}
+
+impl ChunkComp {
+    /// Returns the length of the sums.
+    fn reduced_len(&self) -> usize {
+        if self.strided { self.chunk_len } else { self.num_chunks }
+    }
+}
+
+impl Computation for ChunkComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    /// The derivative of summing the rows or the columns expands the gradients to them, and the reverse.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![DArray::from(ChunkComp {src: res_grads, expand: !self.expand, ..self.clone()})]
+    }
+
+    fn len(&self) -> usize {
+        if self.expand { self.chunk_len * self.num_chunks } else { self.reduced_len() }
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        for_each_summand(&self.src, &mut |data| {
+            for chunk in 0..self.num_chunks {
+                for pos in 0..self.chunk_len {
+                    let idx = chunk * self.chunk_len + pos;
+                    let reduced = if self.strided { pos } else { chunk };
+                    if self.expand {
+                        res_array[idx] += data[reduced];
+                    } else {
+                        res_array[reduced] += data[idx];
+                    }
+                }
+            }
+        });
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Linear
+    }
+
+    fn cost(&self) -> Cost {
+        let len = self.chunk_len * self.num_chunks;
+        Cost {flops: len, bytes_read: self.src.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+
+    /// Checks that the source is split into whole chunks.
+    fn validate(&self) -> Result<(), String> {
+        let expected = if self.expand { self.reduced_len() } else { self.chunk_len * self.num_chunks };
+        if self.src.len() != expected {
+            Err(format!("An array of length {} cannot be split into chunks of length {}.", self.src.len(), self.chunk_len))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl DArray {
+    /// Returns the sums of the chunks of `chunk_len` consecutive elements of the array,
+    /// which are the row sums of a matrix with rows of length `chunk_len`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn sum_chunks(&self, chunk_len: usize) -> DArray {
+        let num_chunks = self.len().checked_div(chunk_len).unwrap_or(0);
+        DArray::from(ChunkComp {src: self.clone(), chunk_len, num_chunks, strided: false, expand: false})
+    }
+    /// Returns the sums of every `stride`-th element of the array, starting from each of the first `stride` elements,
+    /// which are the column sums of a matrix with rows of length `stride`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn sum_strided(&self, stride: usize) -> DArray {
+        let num_chunks = self.len().checked_div(stride).unwrap_or(0);
+        DArray::from(ChunkComp {src: self.clone(), chunk_len: stride, num_chunks, strided: true, expand: false})
+    }
+    /// Repeats every element of the array `count` times.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn repeat(&self, count: usize) -> DArray {
+        DArray::from(ChunkComp {src: self.clone(), chunk_len: count, num_chunks: self.len(), strided: false, expand: true})
+    }
+    /// Repeats the whole array `count` times.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn tile(&self, count: usize) -> DArray {
+        DArray::from(ChunkComp {src: self.clone(), chunk_len: self.len(), num_chunks: count, strided: true, expand: true})
+    }
+}
+
+impl DArray {
+    /// Returns the elements of the array in the given range.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn slice(&self, range: Range<usize>) -> DArray {
+        IndexComp::from_range(self, range.clone(), 0, range.len())
+    }
+    /// Returns every `stride`-th element of the array, starting from the element at `offset`,
+    /// as used for taking a channel of interleaved data.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn take_strided(&self, offset: usize, stride: usize) -> DArray {
+        assert!(stride > 0, "The stride must be positive.");
+        let length = self.len().saturating_sub(offset).div_ceil(stride);
+        IndexComp::from_strided(self, (offset, stride), (0, 1), length, length)
+    }
+    /// Splits the array into consecutive slices of the given sizes, which must sum to the length of the array.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn split(&self, sizes: &[usize]) -> Vec<DArray> {
+        let total: usize = sizes.iter().sum();
+        assert_eq!(total, self.len(), "Cannot split an array of length {} into slices of total length {}.", self.len(), total);
+        let mut start = 0;
+        sizes.iter()
+            .map(|size| {
+                start += size;
+                self.slice(start - size..start)
+            })
+            .collect()
+    }
+    /// Splits the array into consecutive slices of length `chunk_len`, where the last slice may be shorter.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn chunks(&self, chunk_len: usize) -> Vec<DArray> {
+        assert!(chunk_len > 0, "The chunks must be nonempty.");
+        (0..self.len()).step_by(chunk_len)
+            .map(|start| self.slice(start..self.len().min(start + chunk_len)))
+            .collect()
+    }
+}
+
+/// A computation reversing the order of the elements of an array.
+#[derive(Clone)]
+pub struct ReverseComp {
+    src: DArray,
+}
+
+impl Computation for ReverseComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![res_grads.reverse()]
+    }
+
+    fn len(&self) -> usize {
+        self.src.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        for_each_summand(&self.src, &mut |data| {
+            for (res, x) in res_array.iter_mut().zip(data.iter().rev()) {
+                *res += x;
+            }
+        });
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Linear
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: self.len(), bytes_read: self.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+}
+
+impl DArray {
+    /// Returns the elements of the array in reverse order.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn reverse(&self) -> DArray {
+        DArray::from(ReverseComp {src: self.clone()})
+    }
+}
+
+/// A computation shifting the elements of an array by an offset, either circularly or filling
+/// the elements shifted in with a constant.
+#[derive(Clone)]
+pub struct ShiftComp {
+    src: DArray,
+    shift: isize,
+    /// The value of the elements shifted in, or `None` for a circular shift.
+    fill: Option<f64>,
+}
+
+impl Computation for ShiftComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    /// The gradients are shifted back, and the gradients of the elements shifted out are dropped.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![DArray::from(ShiftComp {src: res_grads, shift: -self.shift, fill: self.fill.map(|_| 0.)})]
+    }
+
+    fn len(&self) -> usize {
+        self.src.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let len = self.len() as isize;
+        if len == 0 {
+            return;
+        }
+        for_each_summand(&self.src, &mut |data| {
+            match self.fill {
+                None => {
+                    let offset = self.shift.rem_euclid(len) as usize;
+                    let (head, tail) = data.split_at(data.len() - offset);
+                    for (res, x) in res_array.iter_mut().zip(tail.iter().chain(head)) {
+                        *res += x;
+                    }
+                }
+                Some(_) => {
+                    let shift = self.shift.clamp(-len, len);
+                    let (res, data) = if shift >= 0 {
+                        (&mut res_array[shift as usize..], data)
+                    } else {
+                        (&mut *res_array, &data[(-shift) as usize..])
+                    };
+                    for (res, x) in res.iter_mut().zip(data) {
+                        *res += x;
+                    }
+                }
+            }
+        });
+        if let Some(fill) = self.fill {
+            let shift = self.shift.clamp(-len, len);
+            let filled = if shift >= 0 { 0..shift as usize } else { (len + shift) as usize..len as usize };
+            for res in res_array[filled].iter_mut() {
+                *res += fill;
+            }
+        }
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Linear
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: self.len(), bytes_read: self.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+}
+
+impl DArray {
+    /// Shifts the elements of the array circularly by `shift` positions towards its end,
+    /// so that the last elements are moved to its start.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn roll(&self, shift: isize) -> DArray {
+        DArray::from(ShiftComp {src: self.clone(), shift, fill: None})
+    }
+    /// Shifts the elements of the array by `shift` positions towards its end, dropping the elements shifted out
+    /// and filling the elements shifted in with `fill`. Negative shifts move the elements towards the start.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn shift(&self, shift: isize, fill: f64) -> DArray {
+        DArray::from(ShiftComp {src: self.clone(), shift, fill: Some(fill)})
+    }
+}
+
+/// The values of the elements added by padding an array.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Padding {
+    /// Pads with a constant.
+    Constant(f64),
+    /// Pads with the first and last elements.
+    Edge,
+    /// Pads with the elements mirrored around the first and last elements, excluding them.
+    Reflect,
+}
+
+/// A computation padding an array on both sides, or folding the gradients of a padded array back to its elements.
+#[derive(Clone)]
+pub struct PadComp {
+    src: DArray,
+    left: usize,
+    right: usize,
+    /// The length of the array before padding.
+    inner: usize,
+    padding: Padding,
+    /// If set, the source is padded, and every element of the result is the sum of the elements padded from it.
+    fold: bool,
+}
+
+impl PadComp {
+    /// Returns the index of the element of the array padded to the given index, if there is one.
+    fn source_index(&self, idx: usize) -> Option<usize> {
+        let idx = idx as isize - self.left as isize;
+        let last = self.inner as isize - 1;
+        if (0..=last).contains(&idx) {
+            return Some(idx as usize);
+        }
+        match self.padding {
+            Padding::Constant(_) => None,
+            Padding::Edge => Some(idx.clamp(0, last) as usize),
+            Padding::Reflect => {
+                let mut idx = idx;
+                while !(0..=last).contains(&idx) {
+                    idx = if idx < 0 { -idx } else { 2 * last - idx };
+                }
+                Some(idx as usize)
+            }
+        }
+    }
+}
+
+impl Computation for PadComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    /// The gradients of constant padding are dropped, and the gradients of the other paddings
+    /// are added to the elements they were padded from.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![DArray::from(PadComp {src: res_grads, fold: !self.fold, ..self.clone()})]
+    }
+
+    fn len(&self) -> usize {
+        if self.fold { self.inner } else { self.left + self.inner + self.right }
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let padded_len = self.left + self.inner + self.right;
+        for_each_summand(&self.src, &mut |data| {
+            for idx in 0..padded_len {
+                match (self.source_index(idx), self.fold) {
+                    (Some(src), false) => res_array[idx] += data[src],
+                    (Some(src), true) => res_array[src] += data[idx],
+                    (None, _) => {}
+                }
+            }
+        });
+        if let (Padding::Constant(fill), false) = (self.padding, self.fold) {
+            for idx in (0..self.left).chain(self.left + self.inner..padded_len) {
+                res_array[idx] += fill;
+            }
+        }
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Linear
+    }
+
+    fn cost(&self) -> Cost {
+        let padded_len = self.left + self.inner + self.right;
+        Cost {flops: padded_len, bytes_read: self.src.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+
+    /// Checks that there are enough elements to pad from.
+    fn validate(&self) -> Result<(), String> {
+        let required = match self.padding {
+            Padding::Constant(_) => 0,
+            Padding::Edge => 1,
+            Padding::Reflect => 2,
+        };
+        if self.left + self.right > 0 && self.inner < required {
+            Err(format!("Padding with {:?} requires at least {} elements, but the array has {}.", self.padding, required, self.inner))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl DArray {
+    /// Pads the array with `left` elements before it and `right` elements after it.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn pad(&self, left: usize, right: usize, padding: Padding) -> DArray {
+        DArray::from(PadComp {src: self.clone(), left, right, inner: self.len(), padding, fold: false})
+    }
+}
+
+/// A computation correlating a signal with a kernel at every `stride` elements, such that
+/// `res[o] = sum(signal[o * stride + t] * kernel[t])`, or spreading the gradients of a correlation back to its signal.
+#[derive(Clone)]
+pub struct CorrelateComp {
+    src: DArray,
+    kernel: DArray,
+    stride: usize,
+    /// The length of the correlated signal.
+    signal_len: usize,
+    /// If set, the source is the correlation, and every element of the signal receives the sum of the elements
+    /// of the correlation it contributes to, weighted by the kernel.
+    transposed: bool,
+}
+
+impl CorrelateComp {
+    /// Returns the number of windows of the kernel in the signal.
+    fn windows(&self) -> usize {
+        (self.signal_len - self.kernel.len()) / self.stride + 1
+    }
+}
+
+impl Computation for CorrelateComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone(), self.kernel.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let (signal, correlation) = if self.transposed {
+            (res_grads.clone(), self.src.clone())
+        } else {
+            (self.src.clone(), res_grads.clone())
+        };
+        let kernel_grads = KernelGradComp {signal, correlation, stride: self.stride, kernel_len: self.kernel.len()};
+        vec![
+            DArray::from(CorrelateComp {src: res_grads, transposed: !self.transposed, ..self.clone()}),
+            DArray::from(kernel_grads),
+        ]
+    }
+
+    fn len(&self) -> usize {
+        if self.transposed { self.signal_len } else { self.windows() }
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        assert_eq!(res_array.len(), self.len());
+        let (data, kernel) = (self.src.data(), self.kernel.data());
+        if self.transposed {
+            for (o, g) in data.iter().enumerate() {
+                for (res, k) in res_array[o * self.stride..].iter_mut().zip(kernel) {
+                    *res += g * k;
+                }
+            }
+        } else {
+            let backend = self.src.backend();
+            for (o, res) in res_array.iter_mut().enumerate() {
+                *res += backend.dot(&data[o * self.stride..o * self.stride + kernel.len()], kernel);
+            }
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        let flops = 2 * self.windows() * self.kernel.len();
+        Cost {flops, bytes_read: (self.src.len() + self.kernel.len()) * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.stride == 0 {
+            Err("The stride of a correlation must be positive.".to_string())
+        } else if self.kernel.len() == 0 || self.kernel.len() > self.signal_len {
+            Err(format!("A kernel of length {} can't be correlated with a signal of length {}.", self.kernel.len(), self.signal_len))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A computation calculating the gradients of the kernel of a correlation,
+/// such that `res[t] = sum(correlation[o] * signal[o * stride + t])`.
+#[derive(Clone)]
+struct KernelGradComp {
+    signal: DArray,
+    correlation: DArray,
+    stride: usize,
+    kernel_len: usize,
+}
+
+impl Computation for KernelGradComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.signal.clone(), self.correlation.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let correlate = CorrelateComp {
+            src: self.signal.clone(),
+            kernel: res_grads.clone(),
+            stride: self.stride,
+            signal_len: self.signal.len(),
+            transposed: false,
+        };
+        let spread = CorrelateComp {src: self.correlation.clone(), kernel: res_grads, transposed: true, ..correlate.clone()};
+        vec![DArray::from(spread), DArray::from(correlate)]
+    }
+
+    fn len(&self) -> usize {
+        self.kernel_len
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        assert_eq!(res_array.len(), self.len());
+        let signal = self.signal.data();
+        for (o, g) in self.correlation.data().iter().enumerate() {
+            for (res, x) in res_array.iter_mut().zip(&signal[o * self.stride..]) {
+                *res += g * x;
+            }
+        }
+    }
+}
+
+impl DArray {
+    /// Correlates the array with the kernel at every `stride` elements, after padding it with `padding` zeros on
+    /// both sides, such that `res[o] = sum(padded[o * stride + t] * kernel[t])`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn correlate1d(&self, kernel: &DArray, stride: usize, padding: usize) -> DArray {
+        let src = if padding > 0 { self.pad(padding, padding, Padding::Constant(0.)) } else { self.clone() };
+        let signal_len = src.len();
+        DArray::from(CorrelateComp {src, kernel: kernel.clone(), stride, signal_len, transposed: false})
+    }
+
+    /// Convolves the array with the kernel at every `stride` elements, after padding it with `padding` zeros on
+    /// both sides. The convolution is the correlation with the reversed kernel.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn conv1d(&self, kernel: &DArray, stride: usize, padding: usize) -> DArray {
+        self.correlate1d(&kernel.reverse(), stride, padding)
+    }
+}
+
+/// A computation concatenating arrays.
+#[derive(Clone)]
+pub struct ConcatComp {
+    arrays: Vec<DArray>,
+    /// If set, the arrays are stacked, and must have the same length.
+    stacked: bool,
+}
+
+impl Computation for ConcatComp {
+    fn sources(&self) -> Vec<DArray> {
+        self.arrays.clone()
+    }
+
+    /// Every source receives the range of the gradients at its offset.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let mut start = 0;
+        self.arrays.iter()
+            .map(|array| {
+                start += array.len();
+                res_grads.slice(start - array.len()..start)
+            })
+            .collect()
+    }
+
+    fn len(&self) -> usize {
+        self.arrays.iter().map(|array| array.len()).sum()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let mut start = 0;
+        for array in self.arrays.iter() {
+            for (res, x) in res_array[start..start + array.len()].iter_mut().zip(array.data()) {
+                *res += x;
+            }
+            start += array.len();
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: self.len(), bytes_read: self.len() * F64_SIZE, bytes_written: self.len() * F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.arrays.is_empty() {
+            return Err("Concatenation requires at least one array.".to_string());
+        }
+        let len = self.arrays[0].len();
+        match self.arrays.iter().find(|array| array.len() != len) {
+            Some(array) if self.stacked => Err(format!("Cannot stack arrays of lengths {} and {}.", len, array.len())),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl DArray {
+    /// Returns the concatenation of the arrays, in order.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn concat(arrays: &[&DArray]) -> DArray {
+        DArray::from(ConcatComp {arrays: arrays.iter().map(|array| (*array).clone()).collect(), stacked: false})
+    }
+    /// Stacks arrays of the same length into a batch, where the `i`-th element of the `k`-th array is at
+    /// index `k * len + i`. The sums over the batch are given by [`DArray::sum_strided`], and the sums of every
+    /// array by [`DArray::sum_chunks`].
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn stack(arrays: &[&DArray]) -> DArray {
+        DArray::from(ConcatComp {arrays: arrays.iter().map(|array| (*array).clone()).collect(), stacked: true})
+    }
+}
+
+/// A computation creating a batch of one-hot vectors, the `k`-th of which is at indices `k * len..(k + 1) * len`.
+#[derive(Clone)]
+pub struct OneHotComp {
+    /// The index of the nonzero element of every vector.
+    indices: Vec<usize>,
+    /// The length of every vector.
+    len: usize,
+}
+
+impl Computation for OneHotComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![]
+    }
+
+    fn derivatives(&self, _: DArray) -> Vec<DArray> {
+        vec![]
+    }
+
+    fn len(&self) -> usize {
+        self.indices.len() * self.len
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        for (row, idx) in self.indices.iter().enumerate() {
+            res_array[row * self.len + idx] += 1.;
+        }
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 0, bytes_read: 0, bytes_written: self.len() * F64_SIZE}
+    }
+
+    /// Checks that the indices are in the bounds of the vectors.
+    fn validate(&self) -> Result<(), String> {
+        match self.indices.iter().find(|idx| **idx >= self.len) {
+            Some(idx) => Err(format!("Index {} is out of bounds for a one-hot vector of length {}.", idx, self.len)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl DArray {
+    /// Returns a vector of length `len` which is one at the index and zero elsewhere.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn one_hot(index: usize, len: usize) -> DArray {
+        DArray::from(OneHotComp {indices: vec![index], len})
+    }
+    /// Returns a batch of one-hot vectors of length `len` for the indices, stacked as by [`DArray::stack`].
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn one_hot_batch(indices: &[usize], len: usize) -> DArray {
+        DArray::from(OneHotComp {indices: indices.to_vec(), len})
+    }
+}
+
+/// A computation that handles expanding a scalar to an array.
+/// This operation can be done with IndexComp, but this should be both lighter, since it doesn't require
+/// the index mapping array, and easier to use.
+#[derive(Clone)]
+pub struct ExpandComp {
+    src: DArray,
+    length: usize,
+}
+
+impl ExpandComp {
+    pub fn new(src: DArray, length: usize) -> ExpandComp {
+        ExpandComp {src, length}
+    }
+}
+
+impl Computation for ExpandComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        vec![DArray::from(SumComp {src: res_grads})]
+    }
+
+    fn len(&self) -> usize {
+        self.length
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        assert_eq!(res_array.len(), self.len());
+        let src = self.src.data()[0];
+        for i in res_array.iter_mut() {
+            *i += src;
+        }
+    }
+
+    fn get_type(&self) -> ComputationType {
+        ComputationType::Expand
+    }
+
+    /// Applies the source on zero on the first element, and copies it to the rest of the array.
+    fn apply_on_zero(&self, res_array: &mut [f64]) {
+        if self.src.is_initialized() || res_array.is_empty() {
+            self.apply(res_array);
+            return;
+        }
+        self.src.comp().apply_on_zero(&mut res_array[..1]);
+        let src = res_array[0];
+        for i in res_array[1..].iter_mut() {
+            *i = src;
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.src.is_scalar() {
+            Ok(())
+        } else {
+            Err(format!("Only scalars can be expanded, but the source has length {}.", self.src.len()))
+        }
+    }
+}
+
+/// If the source array is a scalar, expand it to an array of the same length as the target length.
+#[cfg_attr(feature = "debug-provenance", track_caller)]
+pub fn expand_array(src: DArray, tar_len: &DArray) -> DArray {
+    if src.is_scalar() && !tar_len.is_scalar() {
+        DArray::from(ExpandComp::new(src, tar_len.len()))
+    } else {
+        src
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DArray;
+    use crate::computation::NodeComp;
+    use crate::index_functions::{IndexComp, Padding};
+    use crate::test_utils::*;
+
+
+    #[test]
+    fn test_index_builders() {
+        let array = DArray::from(vec![1., 2., 3., 4.]);
+        assert_eq!(IndexComp::from_range(&array, 1..3, 2, 5).data(), &[0., 0., 2., 3., 0.]);
+        assert_eq!(IndexComp::from_strided(&array, (3, 0), (0, 1), 3, 3).data(), &[4., 4., 4.]);
+        assert_eq!(IndexComp::from_strided(&array, (0, 1), (0, 0), 4, 1).data(), &[10.]);
+        assert_eq!(IndexComp::from_fn(&array, 3, |idx| 3 - idx).data(), &[4., 3., 2.]);
+
+        let weights = DArray::from(vec![1., 2., 3.]);
+        let grads = |res: DArray| (res * &weights).sum().derive()[&array].data().to_vec();
+        assert_eq!(grads(IndexComp::from_strided(&array, (1, 2), (0, 2), 2, 3)), vec![0., 1., 0., 3.]);
+        assert_eq!(grads(IndexComp::from_strided(&array, (3, 0), (0, 1), 3, 3)), vec![0., 0., 0., 6.]);
+        assert_eq!(grads(IndexComp::from_fn(&array, 3, |idx| idx / 2)), vec![3., 3., 0., 0.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 6);
+        assert_gradients(|arrays| {
+            let strided = IndexComp::from_strided(&arrays[0], (1, 2), (2, 1), 3, 5);
+            (strided * &IndexComp::from_fn(&arrays[0], 5, |idx| (idx * 7) % 6).sin()).sum()
+        }, &[&v]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Source index 4 is out of bounds for a source of length 4.")]
+    fn test_index_builders_fail() {
+        IndexComp::from_strided(&DArray::from(vec![1.; 4]), (0, 2), (0, 1), 3, 3);
+    }
+
+    #[test]
+    fn test_sparse_entries() {
+        let table = DArray::from(vec![1.; 1_000_000]);
+        let w = DArray::from(vec![1., 2., 3.]);
+        let loss = (table.gather(&[5, 999_999, 5]) * &w).sum() + (IndexComp::from_range(&table, 7..9, 0, 2) * 10.).sum();
+        let grads = loss.derive();
+        let grad = &grads[&table];
+        // The gradients from both index computations are accumulated in a single sparse computation.
+        assert!(matches!(grad.comp(), NodeComp::Index(_)));
+        assert_eq!(grad.sparse_entries(), Some(vec![(5, 4.), (7, 10.), (8, 10.), (999_999, 2.)]));
+        assert!(!grad.is_initialized());
+        assert_eq!(grads[&w].sparse_entries(), None);
+
+        // Sparse entries agree with the densified gradients.
+        assert_eq!(grad.data()[5], 4.);
+        assert_eq!(grad.data().iter().sum::<f64>(), 26.);
+        assert_eq!(grad.sparse_entries(), None);
+
+        // The nonzero elements of calculated summands are read as entries.
+        let scatter = IndexComp::from_range(&DArray::from(vec![1., 2.]), 0..2, 3, 1_000_000);
+        let total = DArray::add_n(&[grad.clone(), scatter.clone()]);
+        let expected = vec![(3, 1.), (4, 2.), (5, 4.), (7, 10.), (8, 10.), (999_999, 2.)];
+        assert_eq!(total.sparse_entries(), Some(expected.clone()));
+        assert_eq!((scatter + grad).sparse_entries(), Some(expected));
+    }
+
+    #[test]
+    fn test_sum() {
+        let mut rng = StdRng::from_seed(SEED);
+
+        for _ in 0..100 {
+            let arr: Vec<f64> = (0..10).map(|_|rng.gen::<f64>()).collect();
+            let arr_sum = arr.iter().sum();
+            let array_arr = DArray::from(arr);
+            let array_sum = array_arr.sum();
+
+            assert!(array_sum.is_scalar());
+            assert_close(arr_sum, array_sum.data()[0]);
+            array_sum.derive().get(&array_arr).unwrap().data().iter().for_each(|f|assert_close(*f, 1.))
+        }
+    }
+
+    #[test]
+    fn test_dot() {
+        let p1 = DArray::from(vec![1., 2., 3.]);
+        let p2 = DArray::from(vec![4., -5., 6.]);
+        assert_eq!(p1.dot(&p2).data(), &[12.]);
+        assert_eq!((&p1 + &p1).dot(&p2.sin()).data(), (&p1 * 2. * p2.sin()).sum().data());
+
+        let grads = (p1.dot(&p2) * 2.).derive();
+        assert_eq!(grads[&p1].data(), &[8., -10., 12.]);
+        assert_eq!(grads[&p2].data(), &[2., 4., 6.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v1 = random_vec(&mut rng, 10);
+        let v2 = random_vec(&mut rng, 10);
+        assert_gradients(|arrays| arrays[0].dot(&arrays[1].exp()).sin(), &[&v1, &v2]);
+        assert_gradients(|arrays| arrays[0].dot(&arrays[0]), &[&v1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "The sources have different lengths (3 and 2).")]
+    fn test_dot_fail() {
+        DArray::from(vec![1., 2., 3.]).dot(&DArray::from(vec![1., 2.])).data();
+    }
+
+    /// Tests that the rounding errors of sums are compensated.
+    #[cfg(feature = "compensated-sum")]
+    #[test]
+    fn test_compensated_sum() {
+        let array = DArray::from(vec![1., 1e100, 1., -1e100]);
+        assert_eq!(array.sum().data(), &[2.]);
+        assert_eq!((&array + &array).sum().data(), &[4.]);
+
+        let array = DArray::from(vec![0.1; 1_000_000]);
+        assert_eq!(array.sum().data(), &[100_000.]);
+        let array = DArray::from(vec![1e8, 1e8 + 1., 1e8 + 2.]);
+        assert_eq!(array.var(0).data(), &[2. / 3.]);
+
+        // The moments are updated with compensated sums.
+        let (mean, var) = DArray::from((0..1000).map(|idx| 1e8 + 0.1 * idx as f64).collect::<Vec<_>>()).moments();
+        assert_eq!(mean.item(), 100_000_049.95);
+        assert!((var.item() - 833.332500000596).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_prod() {
+        let array = DArray::from(vec![2., -3., 0.5, 4.]);
+        assert_eq!(array.prod().data(), &[-12.]);
+        assert_eq!(array.prod().derive()[&array].data(), &[-6., 4., -24., -3.]);
+
+        // The derivatives are correct for zero elements.
+        let array = DArray::from(vec![2., 0., 3.]);
+        assert_eq!(array.prod().derive()[&array].data(), &[0., 6., 0.]);
+        let array = DArray::from(vec![0., 2., 0.]);
+        assert_eq!(array.prod().derive()[&array].data(), &[0., 0., 0.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 6);
+        assert_gradients(|arrays| arrays[0].prod() * 3., &[&v]);
+        assert_gradients(|arrays| (arrays[0].prod() + arrays[0].sum()).powi(2), &[&v]);
+        // Tests the second derivatives.
+        assert_gradients(|arrays| (arrays[0].prod().derive()[&arrays[0]].clone() * &arrays[0]).sum(), &[&v]);
+    }
+
+    #[test]
+    fn test_cumulative() {
+        let array = DArray::from(vec![2., -3., 0.5, 4.]);
+        assert_eq!(array.cumsum().data(), &[2., -1., -0.5, 3.5]);
+        assert_eq!(array.cumprod().data(), &[2., -6., -3., -12.]);
+        assert_eq!(array.cumsum().sum().derive()[&array].data(), &[4., 3., 2., 1.]);
+        assert_eq!(array.cumprod().sum().derive()[&array].data(), &[-9.5, 7., -30., -3.]);
+
+        // The derivatives of cumulative products are correct for zero elements.
+        let array = DArray::from(vec![2., 0., 3.]);
+        assert_eq!(array.cumprod().sum().derive()[&array].data(), &[1., 8., 0.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 6);
+        assert_gradients(|arrays| (arrays[0].cumsum() * &arrays[0].sin()).sum(), &[&v]);
+        assert_gradients(|arrays| (arrays[0].cumprod() * &arrays[0].sin()).sum(), &[&v]);
+        // Tests the second derivatives.
+        assert_gradients(|arrays| {
+            let grads = (arrays[0].cumprod() * &arrays[0].sin()).sum().derive();
+            (grads[&arrays[0]].clone() * &arrays[0].cos()).sum()
+        }, &[&v]);
+    }
+
+    #[test]
+    fn test_variance() {
+        let array = DArray::from(vec![1., 2., 3., 6.]);
+        assert_eq!(array.var(0).data(), &[3.5]);
+        assert_eq!(array.var(1).data(), &[14. / 3.]);
+        assert_eq!(array.std(0).data(), &[3.5_f64.sqrt()]);
+        assert_eq!(array.var(0).derive()[&array].data(), &[-1., -0.5, 0., 1.5]);
+        // The standard deviation of a constant array has zero derivatives.
+        let constant = DArray::from(vec![2., 2.]);
+        assert_eq!(constant.std(1).derive()[&constant].data(), &[0., 0.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 6);
+        for ddof in [0, 1] {
+            assert_gradients(|arrays| arrays[0].var(ddof) * 3., &[&v]);
+            assert_gradients(|arrays| arrays[0].std(ddof) * 3., &[&v]);
+            // Tests the second derivatives.
+            assert_gradients(|arrays| (arrays[0].var(ddof).derive()[&arrays[0]].clone() * &arrays[0]).sum(), &[&v]);
+            assert_gradients(|arrays| (arrays[0].std(ddof).derive()[&arrays[0]].clone() * &arrays[0]).sum(), &[&v]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "The variance of 2 elements is undefined with 2 delta degrees of freedom.")]
+    fn test_variance_fail() {
+        DArray::from(vec![1., 2.]).var(2);
+    }
+
+    #[test]
+    fn test_moments() {
+        let array = DArray::from(vec![1., 2., 3., 6.]);
+        let (mean, var) = array.moments();
+        assert_eq!(mean.data(), &[3.]);
+        assert_eq!(var.data(), &[3.5]);
+        assert_eq!(var.derive()[&array].data(), &[-1., -0.5, 0., 1.5]);
+        assert_eq!(mean.derive()[&array].data(), &[0.25; 4]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 6);
+        let array = DArray::from(v.clone());
+        let (mean, var) = array.moments();
+        assert_close(mean.item(), array.sum().item() / 6.);
+        assert_close(var.item(), array.var(0).item());
+        assert_gradients(|arrays| {
+            let (mean, var) = arrays[0].moments();
+            mean.sin() * var.exp()
+        }, &[&v]);
+        // Tests the second derivatives.
+        assert_gradients(|arrays| {
+            let (mean, var) = arrays[0].moments();
+            ((mean * 3. + var).derive()[&arrays[0]].clone() * &arrays[0].sin()).sum()
+        }, &[&v]);
+    }
+
+    #[test]
+    #[should_panic(expected = "The moments of an empty array are undefined.")]
+    fn test_moments_fail() {
+        DArray::from(Vec::<f64>::new()).moments();
+    }
+
+    #[test]
+    fn test_masked_sum() {
+        let array = DArray::from(vec![1., 2., f64::NAN, 4.]);
+        let mask = DArray::from(vec![1., 1., 0., 2.]);
+        assert_eq!(array.masked_sum(&mask).data(), &[7.]);
+        assert_close(array.masked_mean(&mask).item(), 7. / 3.);
+        assert_eq!(array.masked_sum(&mask).derive()[&array].data(), &[1., 1., 0., 1.]);
+        assert_eq!((array.masked_mean(&mask) * 3.).derive()[&array].data(), &[1., 1., 0., 1.]);
+
+        // Deriving the mean doesn't evaluate the mask.
+        let lazy_mask = &mask * 1.;
+        let grads = array.masked_mean(&lazy_mask).derive();
+        assert!(!lazy_mask.is_initialized());
+        assert_eq!((&grads[&array] * 3.).data(), &[1., 1., 0., 1.]);
+
+        // The mean of an empty mask is zero.
+        let empty = DArray::from(vec![0.; 4]);
+        assert_eq!(array.masked_mean(&empty).data(), &[0.]);
+        assert_eq!(array.masked_mean(&empty).derive()[&array].data(), &[0.; 4]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 6);
+        let mask = DArray::from(vec![0., 1., 1., 0., 1., 1.]);
+        assert_gradients(|arrays| arrays[0].masked_sum(&mask).sin() + arrays[0].exp().masked_mean(&mask), &[&v]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid computation MaskedSumComp")]
+    fn test_masked_sum_fail() {
+        DArray::from(vec![1., 2.]).masked_sum(&DArray::from(vec![1., 0., 1.]));
+    }
+
+    #[test]
+    fn test_norm() {
+        let array = DArray::from(vec![3., -4., 0.]);
+        assert_eq!(array.norm_l1().data(), &[7.]);
+        assert_eq!(array.norm_l2().data(), &[5.]);
+        assert_close(array.norm_lp(3.).data()[0], 91_f64.cbrt());
+        assert_eq!(array.norm_l1().derive()[&array].data()[..2], [1., -1.]);
+        for (grad, expected) in array.norm_l2().derive()[&array].data().iter().zip([0.6, -0.8, 0.]) {
+            assert_close(*grad, expected);
+        }
+        // The norm for an infinite power is the maximal absolute value.
+        assert_eq!(array.norm_lp(f64::INFINITY).data(), &[4.]);
+        assert_eq!(array.norm_lp(f64::INFINITY).derive()[&array].data(), &[0., -1., 0.]);
+
+        // The derivatives at the origin are zero.
+        let zeros = DArray::from(vec![0., 0.]);
+        assert_eq!(zeros.norm_l2().derive()[&zeros].data(), &[0., 0.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 6);
+        for power in [1., 1.5, 2., 3., f64::INFINITY] {
+            assert_gradients(|arrays| arrays[0].norm_lp(power) * 3., &[&v]);
+        }
+        // Tests the second derivatives.
+        for power in [2., 3.] {
+            assert_gradients(|arrays| (arrays[0].norm_lp(power).derive()[&arrays[0]].clone() * &arrays[0].sin()).sum(), &[&v]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "The power of a norm must be at least 1, but is 0.5.")]
+    fn test_norm_fail() {
+        DArray::from(vec![1., 2.]).norm_lp(0.5);
+    }
+
+    #[test]
+    fn test_max() {
+        let mut rng = StdRng::from_seed(SEED);
+
+        for _ in 0..100 {
+            let arr: Vec<f64> = (0..10).map(|_|rng.gen::<f64>()).collect();
+            let arr_max = arr.iter().cloned().reduce(|f1, f2|f1.max(f2)).unwrap();
+            let array_arr = DArray::from(arr);
+            let array_max = array_arr.reduce_max();
+
+            assert!(array_max.is_scalar());
+            assert_close(arr_max, array_max.data()[0]);
+        }
+    }
+
+    #[test]
+    fn test_min() {
+        let mut rng = StdRng::from_seed(SEED);
+
+        for _ in 0..100 {
+            let arr: Vec<f64> = (0..10).map(|_|rng.gen::<f64>()).collect();
+            let arr_min = arr.iter().cloned().reduce(|f1, f2|f1.min(f2)).unwrap();
+            let array_arr = DArray::from(arr);
+            let array_min = array_arr.reduce_min();
+
+            assert!(array_min.is_scalar());
+            assert_close(arr_min, array_min.data()[0]);
+        }
+    }
+
+    #[test]
+    fn test_reduce_max_lazy() {
+        let x = DArray::from(vec![1., 3., -2., 3.]);
+        let y = &x * 2.;
+        let max = y.reduce_max();
+        let min = y.reduce_min();
+        // Building the reduction doesn't evaluate its source.
+        assert!(!y.is_initialized());
+
+        // Deriving the reduction doesn't evaluate its source either.
+        let grads = max.derive();
+        assert!(!y.is_initialized());
+        // On ties, the gradient is passed to the first extremal element.
+        assert_eq!(grads[&x].data(), &[0., 2., 0., 0.]);
+        assert_eq!(min.derive()[&x].data(), &[0., 0., 2., 0.]);
+        assert_eq!(max.data(), &[6.]);
+        assert_eq!(min.data(), &[-4.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 8);
+        assert_gradients(|arrays| arrays[0].sin().reduce_max() * &arrays[0].reduce_min(), &[&v]);
+    }
+
+    #[test]
+    fn test_argmax() {
+        let x = DArray::from(vec![1., 3., -2., 3., -2.]);
+        let y = &x * 2.;
+        let argmax = y.argmax_array();
+        assert!(!y.is_initialized());
+        assert_eq!(argmax.data(), &[1.]);
+        assert_eq!(y.argmax(), 1);
+        assert_eq!(y.argmin(), 2);
+        assert_eq!(y.argmin_array().data(), &[2.]);
+
+        // Indexing by the argmax is differentiable by the selected element.
+        let selected = x.index(x.argmax()) * 3.;
+        assert_eq!(selected.derive()[&x].data(), &[0., 3., 0., 0., 0.]);
+        assert_eq!((argmax * &x.sum()).derive()[&x].data(), &[1.; 5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "The extremum of an empty array is undefined.")]
+    fn test_reduce_max_empty() {
+        DArray::from(Vec::<f64>::new()).reduce_max();
+    }
+
+    #[test]
+    fn test_topk() {
+        let x = DArray::from(vec![1., 5., -2., 5., 3.]);
+        let y = &x * 1.;
+        let top = y.topk(3);
+        let weights = DArray::from(vec![1., 2., 3.]);
+        let grads = (&top * &weights).sum().derive();
+        // Deriving the selection doesn't evaluate its source.
+        assert!(!y.is_initialized());
+        assert_eq!(grads[&x].data(), &[0., 1., 0., 2., 3.]);
+        assert_eq!(top.data(), &[5., 5., 3.]);
+        assert_eq!(x.topk_indices(3), vec![1, 3, 4]);
+        assert_eq!(x.topk_indices(5), vec![1, 3, 4, 0, 2]);
+        assert_eq!(x.topk(0).len(), 0);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 8);
+        let w = random_vec(&mut rng, 4);
+        assert_gradients(|arrays| (arrays[0].sin().topk(4) * &arrays[1]).sum(), &[&v, &w]);
+    }
+
+    #[test]
+    fn test_extremum_nan() {
+        // NaN elements are selected by the maximum, the minimum and the top elements alike.
+        let x = DArray::from(vec![1., f64::NAN, 3., -f64::NAN]);
+        assert!(x.reduce_max().item().is_nan());
+        assert!(x.reduce_min().item().is_nan());
+        assert_eq!((x.argmax(), x.argmin()), (1, 1));
+        assert_eq!(x.topk_indices(4), vec![1, 3, 2, 0]);
+        assert!(x.rolling_max(2).data().iter().all(|max| max.is_nan()));
+        assert_eq!(x.reduce_max().derive()[&x].data(), &[0., 1., 0., 0.]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot take the 3 largest elements of an array of length 2.")]
+    fn test_topk_fail() {
+        DArray::from(vec![1., 2.]).topk(3);
+    }
+
+    #[test]
+    fn test_softmax() {
+        let array = DArray::from(vec![1., 2., 3.]);
+        let total = 1_f64.exp() + 2_f64.exp() + 3_f64.exp();
+        for (res, x) in array.softmax().data().iter().zip([1., 2., 3.]) {
+            assert_close(*res, f64::exp(x) / total);
+        }
+        for (res, x) in array.log_softmax().data().iter().zip([1., 2., 3.]) {
+            assert_close(*res, x - total.ln());
+        }
+
+        // Large elements don't overflow.
+        let large = DArray::from(vec![1000., 1000.]);
+        assert_eq!(large.softmax().data(), &[0.5, 0.5]);
+        assert_close(large.log_softmax().data()[0], -2_f64.ln());
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 6);
+        let w = random_vec(&mut rng, 6);
+        assert_gradients(|arrays| (arrays[0].softmax() * &arrays[1]).sum(), &[&v, &w]);
+        assert_gradients(|arrays| (arrays[0].log_softmax() * &arrays[1]).sum(), &[&v, &w]);
+        // Tests the second derivatives.
+        for log in [false, true] {
+            assert_gradients(|arrays| {
+                let softmax = if log { arrays[0].log_softmax() } else { arrays[0].softmax() };
+                let grads = (softmax * &arrays[1]).sum().derive();
+                (grads[&arrays[0]].clone() * &arrays[0].sin()).sum()
+            }, &[&v, &w]);
+        }
+    }
+
+    #[test]
+    fn test_normalize() {
+        let normalized = DArray::from(vec![3., 4.]).normalize(1e-12);
+        assert_close(normalized.data()[0], 0.6);
+        assert_close(normalized.data()[1], 0.8);
+
+        // A zero array stays zero, with finite gradients.
+        let zeros = DArray::from(vec![0., 0.]);
+        let normalized = zeros.normalize(1e-6);
+        assert_eq!(normalized.data(), &[0., 0.]);
+        assert_eq!(normalized.sum().derive().get(&zeros).unwrap().data(), &[1e6, 1e6]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        for _ in 0..10 {
+            let arr = random_vec(&mut rng, 5);
+            let weights = DArray::from(random_vec(&mut rng, 5));
+            assert_gradients(|arrays| (arrays[0].normalize(1e-12) * &weights).sum(), &[&arr]);
+            // A large `eps` clamps the norm.
+            assert_gradients(|arrays| (arrays[0].normalize(100.) * &weights).sum(), &[&arr]);
+        }
+    }
+
+    #[test]
+    fn test_rolling() {
+        let arr = DArray::from(vec![1., 3., 2., 5., 4.]);
+        assert_eq!(arr.rolling_sum(2).data(), &[4., 5., 7., 9.]);
+        assert_eq!(arr.rolling_mean(5).data(), &[3.]);
+        assert_eq!(arr.rolling_max(3).data(), &[3., 5., 5.]);
+        assert_eq!(arr.rolling_max(1).data(), arr.data());
+
+        let grads = arr.rolling_max(3).sum().derive();
+        assert_eq!(grads.get(&arr).unwrap().data(), &[0., 1., 0., 2., 0.]);
+        // Deriving the maxima doesn't evaluate the array.
+        let doubled = &arr * 2.;
+        let grads = doubled.rolling_max(2).sum().derive();
+        assert!(!doubled.is_initialized());
+        assert_eq!(grads[&arr].data(), &[0., 4., 0., 4., 0.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        for _ in 0..10 {
+            let arr: Vec<f64> = random_vec(&mut rng, 8);
+            let naive: Vec<f64> = arr.windows(3).map(|window| window.iter().cloned().fold(f64::MIN, f64::max)).collect();
+            assert_eq!(DArray::from(arr.clone()).rolling_max(3).data(), naive.as_slice());
+
+            let weights = DArray::from(random_vec(&mut rng, 6));
+            assert_gradients(|arrays| (arrays[0].rolling_sum(3) * &weights).sum(), &[&arr]);
+            assert_gradients(|arrays| (arrays[0].rolling_mean(3) * &weights).sum(), &[&arr]);
+            assert_gradients(|arrays| (arrays[0].rolling_max(3) * &weights).sum(), &[&arr]);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_rolling_fail() {
+        let _rolling = DArray::from(vec![1., 2.]).rolling_sum(3);
+    }
+
+    /// Tests that the binary functions on an array and a scalar work properly.
+    #[test]
+    fn test_segment_sum() {
+        let array = DArray::from(vec![1., 2., 3., 4., 5.]);
+        let sums = array.segment_sum(&[0, 2, 0, 2, 3], 4);
+        assert_eq!(sums.data(), &[4., 0., 6., 5.]);
+        let weights = DArray::from(vec![1., 2., 3., 4.]);
+        assert_eq!((sums * &weights).sum().derive()[&array].data(), &[1., 3., 1., 3., 4.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 6);
+        let w = random_vec(&mut rng, 3);
+        assert_gradients(|arrays| (arrays[0].segment_sum(&[2, 0, 2, 1, 0, 2], 3).sin() * &arrays[1]).sum(), &[&v, &w]);
+    }
+
+    #[test]
+    fn test_gather() {
+        let table = DArray::from(vec![10., 20., 30.]);
+        let rows = table.gather(&[2, 0, 2, 2]);
+        assert_eq!(rows.data(), &[30., 10., 30., 30.]);
+        let weights = DArray::from(vec![1., 2., 3., 4.]);
+        assert_eq!((rows * &weights).sum().derive()[&table].data(), &[2., 0., 8.]);
+        let scattered = DArray::from(vec![1., 2., 3.]).scatter_add(&[1, 1, 3], 4);
+        assert_eq!(scattered.data(), &[0., 3., 0., 3.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 4);
+        let w = random_vec(&mut rng, 5);
+        assert_gradients(|arrays| (arrays[0].gather(&[3, 1, 1, 0, 3]).sin() * &arrays[1]).sum(), &[&v, &w]);
+        assert_gradients(|arrays| (arrays[1].scatter_add(&[3, 1, 1, 0, 3], 4).sin() * &arrays[0]).sum(), &[&v, &w]);
+    }
+
+    #[test]
+    fn test_mask_select() {
+        let array = DArray::from(vec![1., 2., 3., 4.]);
+        let doubled = &array * 2.;
+        let mask = &DArray::from(vec![0., 1., 0., 3.]) * 1.;
+        let selected = doubled.mask_select(&mask);
+        // Building the selection evaluates neither the mask nor the array.
+        assert!(!mask.is_initialized() && !doubled.is_initialized());
+        assert_eq!(selected.len(), 2);
+        assert!(mask.is_initialized() && !doubled.is_initialized());
+        assert_eq!(selected.data(), &[4., 8.]);
+        let weights = DArray::from(vec![1., 2.]);
+        assert_eq!((selected * &weights).sum().derive()[&array].data(), &[0., 2., 0., 4.]);
+        assert_eq!(array.mask_select(&DArray::from(vec![0.; 4])).len(), 0);
+
+        // The gradients of a selection are placed back lazily, and can be derived again.
+        let mask = DArray::from(vec![1., 0., 1., 1.]);
+        let grads = (array.mask_select(&mask).powi(2) * &array.mask_select(&mask)).sum().derive();
+        assert_eq!(grads[&array].data(), &[3., 0., 27., 48.]);
+        assert_eq!(grads[&mask].data(), &[0.; 4]);
+        let second = grads[&array].sum().derive();
+        assert_eq!(second[&array].data(), &[6., 0., 18., 24.]);
+    }
+
+    #[test]
+    #[should_panic(expected = "The mask has length 2 instead of 4.")]
+    fn test_mask_select_fail() {
+        DArray::from(vec![1.; 4]).mask_select(&DArray::from(vec![1.; 2]));
+    }
+
+    #[test]
+    fn test_permute() {
+        let array = DArray::from(vec![1., 2., 3., 4.]);
+        let permuted = array.permute(&[2, 0, 3, 1]);
+        assert_eq!(permuted.data(), &[3., 1., 4., 2.]);
+        let weights = DArray::from(vec![1., 2., 3., 4.]);
+        assert_eq!((permuted * &weights).sum().derive()[&array].data(), &[2., 4., 1., 3.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let shuffled = DArray::from((0..100).map(f64::from).collect::<Vec<_>>()).shuffle(&mut rng);
+        let mut elements = shuffled.data().to_vec();
+        assert_ne!(elements, (0..100).map(f64::from).collect::<Vec<_>>());
+        elements.sort_by(f64::total_cmp);
+        assert_eq!(elements, (0..100).map(f64::from).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "The indices are not a permutation.")]
+    fn test_permute_fail() {
+        DArray::from(vec![1., 2., 3.]).permute(&[0, 2, 0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Source index 3 is out of bounds for a source of length 3.")]
+    fn test_gather_fail() {
+        DArray::from(vec![1., 2., 3.]).gather(&[0, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Target index 3 is out of bounds for a result of length 3.")]
+    fn test_segment_sum_fail() {
+        DArray::from(vec![1., 2.]).segment_sum(&[0, 3], 3);
+    }
+
+    #[test]
+    fn test_sum_chunks() {
+        let array = DArray::from(vec![1., 2., 3., 4., 5., 6.]);
+        assert_eq!(array.sum_chunks(3).data(), &[6., 15.]);
+        assert_eq!(array.sum_strided(3).data(), &[5., 7., 9.]);
+        assert_eq!(array.sum_strided(2).data(), &[9., 12.]);
+        let weights = DArray::from(vec![1., 2., 3.]);
+        assert_eq!((array.sum_strided(3) * &weights).sum().derive()[&array].data(), &[1., 2., 3., 1., 2., 3.]);
+        let weights = DArray::from(vec![1., 2.]);
+        assert_eq!((array.sum_chunks(3) * &weights).sum().derive()[&array].data(), &[1., 1., 1., 2., 2., 2.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 12);
+        let w = random_vec(&mut rng, 4);
+        assert_gradients(|arrays| (arrays[0].sum_chunks(3).sin() * &arrays[1]).sum(), &[&v, &w]);
+        assert_gradients(|arrays| (arrays[0].sum_strided(4).sin() * &arrays[1]).sum(), &[&v, &w]);
+    }
+
+    #[test]
+    fn test_repeat() {
+        let array = DArray::from(vec![1., 2., 3.]);
+        assert_eq!(array.repeat(2).data(), &[1., 1., 2., 2., 3., 3.]);
+        assert_eq!(array.tile(2).data(), &[1., 2., 3., 1., 2., 3.]);
+        assert_eq!(array.repeat(0).len(), 0);
+        assert_eq!(array.repeat(0).sum().derive()[&array].data(), &[0.; 3]);
+        let weights = DArray::from(vec![1., 2., 3., 4., 5., 6.]);
+        assert_eq!((array.repeat(2) * &weights).sum().derive()[&array].data(), &[3., 7., 11.]);
+        assert_eq!((array.tile(2) * &weights).sum().derive()[&array].data(), &[5., 7., 9.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 3);
+        let w = random_vec(&mut rng, 12);
+        assert_gradients(|arrays| (arrays[0].repeat(4).sin() * &arrays[1]).sum(), &[&v, &w]);
+        assert_gradients(|arrays| (arrays[0].tile(4).sin() * &arrays[1]).sum(), &[&v, &w]);
+    }
+
+    #[test]
+    #[should_panic(expected = "An array of length 5 cannot be split into chunks of length 2.")]
+    fn test_sum_chunks_fail() {
+        DArray::from(vec![1.; 5]).sum_chunks(2);
+    }
+
+    #[test]
+    fn test_slice() {
+        let array = DArray::from(vec![1., 2., 3., 4., 5.]);
+        assert_eq!(array.slice(1..4).data(), &[2., 3., 4.]);
+        assert_eq!(array.slice(2..2).len(), 0);
+        let weights = DArray::from(vec![1., 2., 3.]);
+        assert_eq!((array.slice(2..5) * &weights).sum().derive()[&array].data(), &[0., 0., 1., 2., 3.]);
+        // Slices read through sums.
+        assert_eq!((&array + &array).slice(3..5).data(), &[8., 10.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 8);
+        assert_gradients(|arrays| (arrays[0].slice(1..5) * &arrays[0].slice(4..8).sin()).sum(), &[&v]);
+    }
+
+    #[test]
+    fn test_split() {
+        let array = DArray::from(vec![1., 2., 3., 4., 5.]);
+        let parts = array.split(&[2, 0, 3]);
+        assert_eq!(parts.iter().map(|part| part.data().to_vec()).collect::<Vec<_>>(), vec![vec![1., 2.], vec![], vec![3., 4., 5.]]);
+        let chunks = array.chunks(2);
+        assert_eq!(chunks.iter().map(|chunk| chunk.len()).collect::<Vec<_>>(), vec![2, 2, 1]);
+        assert_eq!(chunks[2].data(), &[5.]);
+
+        // The gradients of the parts are assembled in the array.
+        let loss = parts[0].sum() * 2. + (&parts[2] * &parts[2]).sum();
+        assert_eq!(loss.derive()[&array].data(), &[2., 2., 6., 8., 10.]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot split an array of length 5 into slices of total length 4.")]
+    fn test_split_fail() {
+        DArray::from(vec![1.; 5]).split(&[1, 3]);
+    }
+
+    #[test]
+    fn test_take_strided() {
+        let array = DArray::from(vec![1., 2., 3., 4., 5., 6., 7.]);
+        assert_eq!(array.take_strided(0, 3).data(), &[1., 4., 7.]);
+        assert_eq!(array.take_strided(1, 3).data(), &[2., 5.]);
+        assert_eq!(array.take_strided(9, 2).len(), 0);
+        let weights = DArray::from(vec![1., 2.]);
+        assert_eq!((array.take_strided(2, 3) * &weights).sum().derive()[&array].data(), &[0., 0., 1., 0., 0., 2., 0.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 9);
+        assert_gradients(|arrays| (arrays[0].take_strided(1, 2) * &arrays[0].take_strided(0, 2).slice(0..4).sin()).sum(), &[&v]);
+    }
+
+    #[test]
+    #[should_panic(expected = "The stride must be positive.")]
+    fn test_take_strided_fail() {
+        DArray::from(vec![1.; 5]).take_strided(1, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Source index 5 is out of bounds for a source of length 5.")]
+    fn test_slice_fail() {
+        DArray::from(vec![1.; 5]).slice(3..6);
+    }
+
+    #[test]
+    fn test_reverse() {
+        let array = DArray::from(vec![1., 2., 3.]);
+        assert_eq!(array.reverse().data(), &[3., 2., 1.]);
+        assert_eq!((&array + &array).reverse().data(), &[6., 4., 2.]);
+        let weights = DArray::from(vec![1., 2., 3.]);
+        assert_eq!((array.reverse() * &weights).sum().derive()[&array].data(), &[3., 2., 1.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 5);
+        assert_gradients(|arrays| (arrays[0].reverse() * &arrays[0].sin()).sum(), &[&v]);
+    }
+
+    #[test]
+    fn test_shift() {
+        let array = DArray::from(vec![1., 2., 3., 4.]);
+        assert_eq!(array.roll(1).data(), &[4., 1., 2., 3.]);
+        assert_eq!(array.roll(-5).data(), &[2., 3., 4., 1.]);
+        assert_eq!(array.shift(2, -1.).data(), &[-1., -1., 1., 2.]);
+        assert_eq!(array.shift(-1, 0.).data(), &[2., 3., 4., 0.]);
+        assert_eq!(array.shift(6, 5.).data(), &[5.; 4]);
+        // The fill is added once for the summands of the source.
+        assert_eq!((&array + &array).shift(1, 7.).data(), &[7., 2., 4., 6.]);
+
+        let weights = DArray::from(vec![1., 2., 3., 4.]);
+        assert_eq!((array.roll(1) * &weights).sum().derive()[&array].data(), &[2., 3., 4., 1.]);
+        assert_eq!((array.shift(1, 9.) * &weights).sum().derive()[&array].data(), &[2., 3., 4., 0.]);
+        assert_eq!((array.shift(-2, 9.) * &weights).sum().derive()[&array].data(), &[0., 0., 1., 2.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 5);
+        assert_gradients(|arrays| (arrays[0].roll(2) * &arrays[0].sin()).sum(), &[&v]);
+        assert_gradients(|arrays| (arrays[0].shift(-3, 0.5) * &arrays[0].sin()).sum(), &[&v]);
+    }
+
+    #[test]
+    fn test_pad() {
+        let array = DArray::from(vec![1., 2., 3.]);
+        assert_eq!(array.pad(2, 1, Padding::Constant(-1.)).data(), &[-1., -1., 1., 2., 3., -1.]);
+        assert_eq!(array.pad(2, 1, Padding::Edge).data(), &[1., 1., 1., 2., 3., 3.]);
+        assert_eq!(array.pad(2, 1, Padding::Reflect).data(), &[3., 2., 1., 2., 3., 2.]);
+        assert_eq!(array.pad(0, 5, Padding::Reflect).data(), &[1., 2., 3., 2., 1., 2., 3., 2.]);
+        assert_eq!((&array + &array).pad(1, 0, Padding::Constant(1.)).data(), &[1., 2., 4., 6.]);
+
+        let weights = DArray::from(vec![1., 2., 3., 4., 5., 6.]);
+        let grads = |padding| (array.pad(2, 1, padding) * &weights).sum().derive()[&array].data().to_vec();
+        assert_eq!(grads(Padding::Constant(-1.)), vec![3., 4., 5.]);
+        assert_eq!(grads(Padding::Edge), vec![6., 4., 11.]);
+        assert_eq!(grads(Padding::Reflect), vec![3., 12., 6.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 4);
+        let w = random_vec(&mut rng, 9);
+        for padding in [Padding::Constant(0.5), Padding::Edge, Padding::Reflect] {
+            assert_gradients(|arrays| (arrays[0].pad(3, 2, padding).sin() * &arrays[1]).sum(), &[&v, &w]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Padding with Reflect requires at least 2 elements, but the array has 1.")]
+    fn test_pad_fail() {
+        DArray::from(vec![1.]).pad(1, 1, Padding::Reflect);
+    }
+
+    #[test]
+    fn test_conv1d() {
+        let signal = DArray::from(vec![1., 2., 3., 4., 5.]);
+        let kernel = DArray::from(vec![1., 0., -1.]);
+        assert_eq!(signal.correlate1d(&kernel, 1, 0).data(), &[-2., -2., -2.]);
+        assert_eq!(signal.conv1d(&kernel, 1, 0).data(), &[2., 2., 2.]);
+        assert_eq!(signal.correlate1d(&kernel, 2, 1).data(), &[-2., -2., 4.]);
+        assert_eq!(signal.correlate1d(&kernel, 3, 0).data(), &[-2.]);
+        assert_eq!((&signal + &signal).conv1d(&DArray::from(vec![1., 1.]), 1, 0).data(), &[6., 10., 14., 18.]);
+
+        let grads = (signal.correlate1d(&kernel, 2, 0) * DArray::from(vec![1., 2.])).sum().derive();
+        assert_eq!(grads[&signal].data(), &[1., 0., 1., 0., -2.]);
+        assert_eq!(grads[&kernel].data(), &[7., 10., 13.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 9);
+        let k = random_vec(&mut rng, 3);
+        for (stride, padding) in [(1, 0), (2, 0), (2, 1), (3, 2)] {
+            assert_gradients(|arrays| arrays[0].conv1d(&arrays[1], stride, padding).sin().sum(), &[&v, &k]);
+        }
+    }
+
+    /// Tests that the derivatives of the gradient computations of correlations are correct.
+    #[test]
+    fn test_conv1d_second_derivatives() {
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 8);
+        let k = random_vec(&mut rng, 3);
+        let weights = DArray::from(random_vec(&mut rng, 11));
+
+        assert_gradients(|arrays| {
+            let grads = arrays[0].correlate1d(&arrays[1], 2, 0).powi(2).sum().derive();
+            let res = (DArray::concat(&[&grads[&arrays[0]], &grads[&arrays[1]]]) * &weights).powi(2).sum();
+            res.derive()[&arrays[1]].sum()
+        }, &[&v, &k]);
+    }
+
+    #[test]
+    #[should_panic(expected = "A kernel of length 4 can't be correlated with a signal of length 3.")]
+    fn test_conv1d_fail() {
+        DArray::from(vec![1., 2., 3.]).conv1d(&DArray::from(vec![1.; 4]), 1, 0);
+    }
+
+    #[test]
+    fn test_concat() {
+        let a = DArray::from(vec![1., 2.]);
+        let b = DArray::from(3.);
+        let c = DArray::from(vec![4., 5., 6.]);
+        let concat = DArray::concat(&[&a, &b, &c, &a]);
+        assert_eq!(concat.data(), &[1., 2., 3., 4., 5., 6., 1., 2.]);
+        let weights = DArray::from(vec![1., 2., 3., 4., 5., 6., 7., 8.]);
+        let grads = (concat * &weights).sum().derive();
+        assert_eq!(grads[&a].data(), &[8., 10.]);
+        assert_eq!(grads[&b].data(), &[3.]);
+        assert_eq!(grads[&c].data(), &[4., 5., 6.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v1 = random_vec(&mut rng, 3);
+        let v2 = random_vec(&mut rng, 4);
+        let w = random_vec(&mut rng, 7);
+        assert_gradients(|arrays| (DArray::concat(&[&arrays[0].sin(), &arrays[1]]) * &arrays[2]).sum().powi(2), &[&v1, &v2, &w]);
+    }
+
+    #[test]
+    fn test_stack() {
+        let a = DArray::from(vec![1., 2., 3.]);
+        let b = DArray::from(vec![4., 5., 6.]);
+        let batch = DArray::stack(&[&a, &b]);
+        assert_eq!(batch.data(), &[1., 2., 3., 4., 5., 6.]);
+        assert_eq!(batch.sum_strided(3).data(), &[5., 7., 9.]);
+        let weights = DArray::from(vec![1., 2., 3., 4., 5., 6.]);
+        let grads = (batch * &weights).sum().derive();
+        assert_eq!(grads[&a].data(), &[1., 2., 3.]);
+        assert_eq!(grads[&b].data(), &[4., 5., 6.]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot stack arrays of lengths 3 and 2.")]
+    fn test_stack_fail() {
+        DArray::stack(&[&DArray::from(vec![1.; 3]), &DArray::from(vec![1.; 2])]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Concatenation requires at least one array.")]
+    fn test_concat_fail() {
+        DArray::concat(&[]);
+    }
+
+    #[test]
+    fn test_one_hot() {
+        assert_eq!(DArray::one_hot(1, 3).data(), &[0., 1., 0.]);
+        assert_eq!(DArray::one_hot_batch(&[2, 0], 3).data(), &[0., 0., 1., 1., 0., 0.]);
+        assert_eq!(DArray::one_hot_batch(&[], 3).len(), 0);
+
+        // Selects an element differentiably.
+        let array = DArray::from(vec![1., 2., 3.]);
+        let selected = (&array * &DArray::one_hot(2, 3)).sum();
+        assert_eq!(selected.data(), &[3.]);
+        assert_eq!(selected.derive()[&array].data(), &[0., 0., 1.]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Index 3 is out of bounds for a one-hot vector of length 3.")]
+    fn test_one_hot_fail() {
+        DArray::one_hot_batch(&[0, 3], 3);
     }
 
-    /// Tests that the binary functions on an array and a scalar work properly.
     #[test]
     fn test_expand() {
         let mut rng = StdRng::from_seed(SEED);
@@ -5,10 +5,19 @@ pub mod array;
 pub mod unary_functions;
 pub mod binary_functions;
 pub mod index_functions;
+pub mod broadcast_functions;
+pub mod tensor;
+pub mod analysis;
+pub mod evaluation;
+pub mod backend;
+pub mod loss_functions;
+pub mod privacy;
+pub mod fuzz;
 mod test_utils;
 
 pub use crate::array::DArray;
 pub use crate::index_functions::IndexComp;
+pub use crate::tensor::DTensor;
 
 #[cfg(test)]
 mod tests {
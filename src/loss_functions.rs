@@ -0,0 +1,246 @@
+use crate::computation::{Computation, Cost, F64_SIZE};
+use crate::array::DArray;
+
+/// The smallest probability used by the binary cross-entropy, which keeps its logarithms finite.
+const BCE_EPS: f64 = 1e-12;
+
+/// The reduction applied to the pointwise losses of a loss function.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Reduction {
+    /// Sums the pointwise losses.
+    Sum,
+    /// Averages the pointwise losses.
+    Mean,
+}
+
+impl Reduction {
+    /// Returns the factor multiplying the sum of the pointwise losses of an array of the given length.
+    fn scale(&self, len: usize) -> f64 {
+        match self {
+            Reduction::Sum => 1.,
+            Reduction::Mean => 1. / len as f64,
+        }
+    }
+}
+
+/// Checks that the predictions and the targets of a loss function have the same length.
+fn validate_targets(pred: &DArray, target: &DArray) -> Result<(), String> {
+    if pred.len() == target.len() {
+        Ok(())
+    } else {
+        Err(format!("The predictions and the targets have different lengths ({} and {}).", pred.len(), target.len()))
+    }
+}
+
+/// A computation calculating the binary cross-entropy between predictions and targets,
+/// given either as probabilities or as logits.
+#[derive(Clone)]
+pub struct BceComp {
+    pred: DArray,
+    target: DArray,
+    /// If the predictions are logits, and not probabilities.
+    logits: bool,
+    reduction: Reduction,
+}
+
+impl BceComp {
+    /// Clamps the probabilities away from 0 and 1.
+    fn clamp(p: f64) -> f64 {
+        p.clamp(BCE_EPS, 1. - BCE_EPS)
+    }
+
+    /// Calculates the loss of a single prediction.
+    fn loss(&self, pred: f64, target: f64) -> f64 {
+        if self.logits {
+            // Equal to `-t * ln(sigmoid(x)) - (1 - t) * ln(1 - sigmoid(x))`, without overflowing.
+            pred.max(0.) - pred * target + (-pred.abs()).exp().ln_1p()
+        } else {
+            let p = BceComp::clamp(pred);
+            -(target * p.ln() + (1. - target) * (1. - p).ln())
+        }
+    }
+}
+
+impl Computation for BceComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.pred.clone(), self.target.clone()]
+    }
+
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let res_grads = res_grads * self.reduction.scale(self.pred.len());
+        let (pred_grads, target_grads) = if self.logits {
+            (self.pred.sigmoid() - &self.target, -&self.pred)
+        } else {
+            let p = self.pred.max(BCE_EPS).min(1. - BCE_EPS);
+            let complement = 1. - &p;
+            ((&p - &self.target) / (&p * &complement), complement.ln() - p.ln())
+        };
+        vec![pred_grads * &res_grads, target_grads * &res_grads]
+    }
+
+    fn len(&self) -> usize {
+        1
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let total: f64 = self.pred.data().iter().zip(self.target.data())
+            .map(|(pred, target)| self.loss(*pred, *target))
+            .sum();
+        res_array[0] += total * self.reduction.scale(self.pred.len());
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 6 * self.pred.len(), bytes_read: 2 * self.pred.len() * F64_SIZE, bytes_written: F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_targets(&self.pred, &self.target)
+    }
+}
+
+impl DArray {
+    /// Calculates the binary cross-entropy between predicted probabilities and targets.
+    /// The probabilities are clamped away from 0 and 1, so saturated predictions don't produce NaNs.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn bce(&self, target: &DArray, reduction: Reduction) -> DArray {
+        DArray::from(BceComp {pred: self.clone(), target: target.clone(), logits: false, reduction})
+    }
+    /// Calculates the binary cross-entropy between the probabilities given by logits and targets.
+    /// More stable than applying a sigmoid and calling [`DArray::bce`].
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn bce_with_logits(&self, target: &DArray, reduction: Reduction) -> DArray {
+        DArray::from(BceComp {pred: self.clone(), target: target.clone(), logits: true, reduction})
+    }
+}
+
+/// A computation calculating the hinge loss `max(0, 1 - label * score)` of scores and labels of ±1,
+/// or its square.
+#[derive(Clone)]
+pub struct HingeComp {
+    scores: DArray,
+    labels: DArray,
+    squared: bool,
+    reduction: Reduction,
+}
+
+impl Computation for HingeComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.scores.clone(), self.labels.clone()]
+    }
+
+    /// Uses the subgradient zero at the hinge point.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let res_grads = res_grads * self.reduction.scale(self.scores.len());
+        let margin = (&self.scores * &self.labels).affine(-1., 1.);
+        // The derivative of the loss by the margin.
+        let margin_grads = if self.squared {
+            margin.max(0.) * 2.
+        } else {
+            margin.gt(0.)
+        };
+        let margin_grads = margin_grads * &res_grads;
+        vec![-(&margin_grads * &self.labels), -(&margin_grads * &self.scores)]
+    }
+
+    fn len(&self) -> usize {
+        1
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let total: f64 = self.scores.data().iter().zip(self.labels.data())
+            .map(|(score, label)| {
+                let loss = (1. - label * score).max(0.);
+                if self.squared { loss * loss } else { loss }
+            })
+            .sum();
+        res_array[0] += total * self.reduction.scale(self.scores.len());
+    }
+
+    fn cost(&self) -> Cost {
+        Cost {flops: 3 * self.scores.len(), bytes_read: 2 * self.scores.len() * F64_SIZE, bytes_written: F64_SIZE}
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        validate_targets(&self.scores, &self.labels)
+    }
+}
+
+impl DArray {
+    /// Calculates the hinge loss `max(0, 1 - label * score)` of the scores, given labels of ±1.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn hinge_loss(&self, labels: &DArray, reduction: Reduction) -> DArray {
+        DArray::from(HingeComp {scores: self.clone(), labels: labels.clone(), squared: false, reduction})
+    }
+    /// Calculates the squared hinge loss `max(0, 1 - label * score)^2` of the scores, given labels of ±1.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn squared_hinge_loss(&self, labels: &DArray, reduction: Reduction) -> DArray {
+        DArray::from(HingeComp {scores: self.clone(), labels: labels.clone(), squared: true, reduction})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DArray;
+    use crate::loss_functions::Reduction;
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_bce() {
+        let mut rng = StdRng::from_seed(SEED);
+        for _ in 0..10 {
+            let logits = random_vec(&mut rng, 5);
+            let probs: Vec<f64> = logits.iter().map(|x| 1. / (1. + (-x).exp())).collect();
+            let targets: Vec<f64> = (0..5).map(|_| rng.gen::<f64>()).collect();
+
+            let expected: f64 = probs.iter().zip(targets.iter())
+                .map(|(p, t)| -(t * p.ln() + (1. - t) * (1. - p).ln()))
+                .sum();
+            let target = DArray::from(targets.clone());
+            assert_close(DArray::from(probs.clone()).bce(&target, Reduction::Sum).item(), expected);
+            assert_close(DArray::from(logits.clone()).bce_with_logits(&target, Reduction::Mean).item(), expected / 5.);
+
+            assert_gradients(|arrays| arrays[0].bce(&arrays[1], Reduction::Mean), &[&probs, &targets]);
+            assert_gradients(|arrays| arrays[0].bce_with_logits(&arrays[1], Reduction::Sum), &[&logits, &targets]);
+        }
+    }
+
+    #[test]
+    fn test_hinge() {
+        let scores = DArray::from(vec![2., 0.5, -1., 1.]);
+        let labels = DArray::from(vec![1., 1., 1., 1.]);
+        assert_eq!(scores.hinge_loss(&labels, Reduction::Sum).item(), 2.5);
+        assert_eq!(scores.squared_hinge_loss(&labels, Reduction::Mean).item(), 4.25 / 4.);
+
+        // The subgradient at the hinge point, where the score is 1, is zero.
+        let grads = scores.hinge_loss(&labels, Reduction::Sum).derive();
+        assert_eq!(grads.get(&scores).unwrap().data(), &[0., -1., -1., 0.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        for _ in 0..10 {
+            let scores = random_vec(&mut rng, 5);
+            let labels: Vec<f64> = (0..5).map(|_| if rng.gen::<bool>() {1.} else {-1.}).collect();
+            let labels = DArray::from(labels);
+            assert_gradients(|arrays| arrays[0].hinge_loss(&labels, Reduction::Mean), &[&scores]);
+            assert_gradients(|arrays| arrays[0].squared_hinge_loss(&labels, Reduction::Sum), &[&scores]);
+        }
+    }
+
+    /// Tests that saturated predictions produce finite losses and gradients.
+    #[test]
+    fn test_bce_saturated() {
+        let target = DArray::from(vec![1., 0.]);
+        let probs = DArray::from(vec![0., 1.]);
+        let logits = DArray::from(vec![-1000., 1000.]);
+
+        for loss in [probs.bce(&target, Reduction::Sum), logits.bce_with_logits(&target, Reduction::Sum)] {
+            assert!(loss.item().is_finite());
+            let grads = loss.derive();
+            for source in [&probs, &logits, &target] {
+                if let Some(grad) = grads.get(source) {
+                    assert!(grad.data().iter().all(|g| g.is_finite()));
+                }
+            }
+        }
+        assert_close(logits.bce_with_logits(&target, Reduction::Sum).item(), 2000.);
+    }
+}
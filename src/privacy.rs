@@ -0,0 +1,147 @@
+use fxhash::FxHashMap;
+use crate::array::DArray;
+
+/// The gradients of a loss by its parameters.
+pub type GradientMap = FxHashMap<DArray, DArray>;
+
+/// Calculates the gradients of every per-example loss by the parameters, in a separate backward pass
+/// for every example.
+/// Every map contains all parameters, with zero gradients for the parameters an example doesn't use.
+#[cfg_attr(feature = "debug-provenance", track_caller)]
+pub fn per_example_gradients(losses: &[DArray], params: &[DArray]) -> Vec<GradientMap> {
+    losses.iter()
+        .map(|loss| {
+            let mut grads = loss.derive();
+            params.iter()
+                .map(|param| {
+                    let grad = grads.remove(param)
+                        .unwrap_or_else(|| DArray::with_backend(vec![0.; param.len()], param.backend()));
+                    (param.clone(), grad)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns the L2 norm of all gradients in the map together.
+pub fn gradient_norm(grads: &GradientMap) -> f64 {
+    grads.values().flat_map(|grad| grad.data()).map(|g| g * g).sum::<f64>().sqrt()
+}
+
+/// Scales the gradients down so that their norm is at most `max_norm`.
+/// Gradients with a smaller norm are returned unchanged.
+#[cfg_attr(feature = "debug-provenance", track_caller)]
+pub fn clip_gradients(grads: &GradientMap, max_norm: f64) -> GradientMap {
+    let norm = gradient_norm(grads);
+    if norm <= max_norm {
+        return grads.clone();
+    }
+    let scale = max_norm / norm;
+    grads.iter().map(|(param, grad)| (param.clone(), grad * scale)).collect()
+}
+
+/// Calculates a differentially private average of per-example gradients, as used by DP-SGD.
+///
+/// The gradients of every example are clipped to `max_norm`, summed, and Gaussian noise with a standard
+/// deviation of `noise_multiplier * max_norm` is added to every element before dividing by the number of examples.
+/// The result is a map of constant arrays, holding the evaluated gradients.
+///
+/// Panics if there are no examples, or if the examples have gradients of different parameters,
+/// as the maps returned by [`per_example_gradients`] for the same parameters never do.
+pub fn private_gradients(
+    per_example: &[GradientMap],
+    max_norm: f64,
+    noise_multiplier: f64,
+    rng: &mut impl rand::Rng,
+) -> GradientMap {
+    assert!(!per_example.is_empty(), "Private gradients require at least one example!");
+    for grads in per_example.iter() {
+        assert!(
+            grads.len() == per_example[0].len() && grads.keys().all(|param| per_example[0].contains_key(param)),
+            "The examples have gradients of different parameters!"
+        );
+    }
+    let clipped: Vec<GradientMap> = per_example.iter().map(|grads| clip_gradients(grads, max_norm)).collect();
+    let std = noise_multiplier * max_norm;
+
+    clipped[0].keys()
+        .map(|param| {
+            let mut total = vec![0.; param.len()];
+            for grads in clipped.iter() {
+                for (total, g) in total.iter_mut().zip(grads[param].data()) {
+                    *total += g;
+                }
+            }
+            let noisy = total.iter()
+                .map(|total| (total + std * standard_normal(rng)) / per_example.len() as f64)
+                .collect();
+            (param.clone(), DArray::with_backend(noisy, param.backend()))
+        })
+        .collect()
+}
+
+/// Samples a standard normal variable with the Box-Muller transform.
+fn standard_normal(rng: &mut impl rand::Rng) -> f64 {
+    let radius = (-2. * (1. - rng.gen::<f64>()).ln()).sqrt();
+    radius * (2. * std::f64::consts::PI * rng.gen::<f64>()).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::DArray;
+    use crate::privacy::{clip_gradients, gradient_norm, per_example_gradients, private_gradients, standard_normal};
+    use crate::test_utils::*;
+
+    #[test]
+    fn test_clip_gradients() {
+        let w = DArray::from(vec![1., 2.]);
+        let b = DArray::from(3.);
+        let unused = DArray::from(vec![1., 1., 1.]);
+        let examples = [DArray::from(vec![3., 0.]), DArray::from(vec![0.1, 0.1])];
+        let losses: Vec<DArray> = examples.iter().map(|x| (&w * x).sum() + &b * 2.).collect();
+
+        let per_example = per_example_gradients(&losses, &[w.clone(), b.clone(), unused.clone()]);
+        assert_eq!(per_example[0][&w].data(), &[3., 0.]);
+        assert_eq!(per_example[0][&unused].data(), &[0., 0., 0.]);
+        assert_close(gradient_norm(&per_example[0]), 13_f64.sqrt());
+
+        let clipped = clip_gradients(&per_example[0], 1.);
+        assert_close(gradient_norm(&clipped), 1.);
+        assert_close(clipped[&b].item(), 2. / 13_f64.sqrt());
+        let unclipped = clip_gradients(&per_example[1], 10.);
+        assert_eq!(unclipped[&w].data(), per_example[1][&w].data());
+
+        // Without noise, the result is the average of the clipped gradients.
+        let mut rng = StdRng::from_seed(SEED);
+        let private = private_gradients(&per_example, 1., 0., &mut rng);
+        let expected = (clipped[&b].item() + clip_gradients(&per_example[1], 1.)[&b].item()) / 2.;
+        assert_close(private[&b].item(), expected);
+        assert_eq!(private[&w].len(), 2);
+    }
+
+    #[test]
+    fn test_private_noise() {
+        let mut rng = StdRng::from_seed(SEED);
+        let samples: Vec<f64> = (0..10000).map(|_| standard_normal(&mut rng)).collect();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+        assert!(mean.abs() < 0.05);
+        assert!((variance - 1.).abs() < 0.05);
+
+        // The noise of the sum is scaled by the clipping norm and the noise multiplier.
+        let param = DArray::from(vec![0.; 1000]);
+        let per_example = per_example_gradients(&[param.sum() * 0.], std::slice::from_ref(&param));
+        let private = private_gradients(&per_example, 2., 1.5, &mut rng);
+        let std = (private[&param].data().iter().map(|x| x * x).sum::<f64>() / 1000.).sqrt();
+        assert!((std - 3.).abs() < 0.3);
+    }
+
+    #[test]
+    #[should_panic(expected = "The examples have gradients of different parameters!")]
+    fn test_private_gradients_fail() {
+        let (w, b) = (DArray::from(vec![1., 2.]), DArray::from(3.));
+        let mut per_example = per_example_gradients(&[w.sum(), w.sum() + &b], &[w.clone(), b.clone()]);
+        per_example[1].remove(&b);
+        private_gradients(&per_example, 1., 0., &mut StdRng::from_seed(SEED));
+    }
+}
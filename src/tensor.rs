@@ -0,0 +1,197 @@
+use std::ops::{Add, Div, Mul, Sub};
+use crate::array::DArray;
+use crate::broadcast_functions::{BroadcastComp, SumAxisComp};
+
+/// An array with a shape, storing the elements of a tensor in row-major order.
+/// The last axis is contiguous, so the element `(i, j)` of a `rows × cols` tensor is at index `i * cols + j`.
+#[derive(Clone)]
+pub struct DTensor {
+    array: DArray,
+    shape: Vec<usize>,
+}
+
+/// Returns the shape which both shapes are broadcast to.
+/// The shapes are aligned by their last axes, and axes of length 1 are repeated to the length of the other axis.
+/// Panics if the lengths of an axis differ and neither is 1.
+fn broadcast_shape(shape1: &[usize], shape2: &[usize]) -> Vec<usize> {
+    let rank = shape1.len().max(shape2.len());
+    let axis = |shape: &[usize], idx: usize| (idx + shape.len()).checked_sub(rank).map_or(1, |idx| shape[idx]);
+    (0..rank)
+        .map(|idx| match (axis(shape1, idx), axis(shape2, idx)) {
+            (len1, len2) if len1 == len2 || len2 == 1 => len1,
+            (1, len2) => len2,
+            _ => panic!("Cannot broadcast the shapes {:?} and {:?}.", shape1, shape2),
+        })
+        .collect()
+}
+
+impl DTensor {
+    /// Creates a tensor of the given shape from the elements of the array.
+    pub fn new(array: DArray, shape: &[usize]) -> DTensor {
+        let size: usize = shape.iter().product();
+        assert_eq!(size, array.len(), "An array of length {} cannot have the shape {:?}.", array.len(), shape);
+        DTensor {array, shape: shape.to_vec()}
+    }
+
+    /// Returns the array of the elements of the tensor.
+    pub fn array(&self) -> &DArray {
+        &self.array
+    }
+
+    /// Returns the lengths of the axes of the tensor.
+    pub fn shape(&self) -> &[usize] {
+        &self.shape
+    }
+
+    /// Returns a tensor with the same elements and a different shape of the same size.
+    pub fn reshape(&self, shape: &[usize]) -> DTensor {
+        DTensor::new(self.array.clone(), shape)
+    }
+
+    /// Applies a pointwise computation on the elements of the tensor, keeping its shape.
+    pub fn map(&self, func: impl FnOnce(&DArray) -> DArray) -> DTensor {
+        DTensor::new(func(&self.array), &self.shape)
+    }
+
+    /// Returns the outer product of the tensors, whose shape is the shape of `self` followed by the shape of `other`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn outer(&self, other: &DTensor) -> DTensor {
+        let shape: Vec<usize> = self.shape.iter().chain(&other.shape).copied().collect();
+        DTensor::new(self.array.outer(&other.array), &shape)
+    }
+
+    /// Returns the sum of all elements of the tensor.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn sum(&self) -> DArray {
+        self.array.sum()
+    }
+
+    /// Returns the lengths of the axes before the axis, of the axis and of the axes after it.
+    fn split_axis(&self, axis: usize) -> (usize, usize, usize) {
+        assert!(axis < self.shape.len(), "Axis {} is out of bounds for a tensor of shape {:?}.", axis, self.shape);
+        (self.shape[..axis].iter().product(), self.shape[axis], self.shape[axis + 1..].iter().product())
+    }
+
+    /// Sums the tensor along the axis, removing it from the shape.
+    /// Named apart from [`DArray::sum_axis`], which sums an axis of a matrix of the given size.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn sum_along(&self, axis: usize) -> DTensor {
+        let split = self.split_axis(axis);
+        let mut shape = self.shape.clone();
+        shape.remove(axis);
+        DTensor {array: DArray::from(SumAxisComp::from_split(self.array.clone(), split)), shape}
+    }
+
+    /// Averages the tensor along the axis, removing it from the shape.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn mean_along(&self, axis: usize) -> DTensor {
+        let len = self.split_axis(axis).1;
+        self.sum_along(axis).map(|sum| sum * (1. / len as f64))
+    }
+
+    /// Broadcasts the tensor to the shape, adding axes of length 1 before its axes and repeating its axes of length 1.
+    /// Panics if an axis of the tensor has neither length 1 nor the length of the axis of the shape.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn broadcast_to(&self, shape: &[usize]) -> DTensor {
+        assert!(shape.len() >= self.shape.len(), "Cannot broadcast the shape {:?} to {:?}.", self.shape, shape);
+        let mut current = vec![1; shape.len() - self.shape.len()];
+        current.extend_from_slice(&self.shape);
+        let mut array = self.array.clone();
+        for (axis, len) in shape.iter().enumerate() {
+            if current[axis] == *len {
+                continue;
+            }
+            assert_eq!(current[axis], 1, "Cannot broadcast the shape {:?} to {:?}.", self.shape, shape);
+            let (outer, inner) = (current[..axis].iter().product(), current[axis + 1..].iter().product());
+            array = DArray::from(BroadcastComp::from_split(array, (outer, *len, inner)));
+            current[axis] = *len;
+        }
+        DTensor {array, shape: current}
+    }
+}
+
+/// Implements a pointwise operator on tensors, broadcasting them to the same shape.
+/// The operators panic if the shapes can't be broadcast.
+macro_rules! tensor_op {
+    ($trait:ident, $method:ident) => {
+        impl $trait<&DTensor> for &DTensor {
+            type Output = DTensor;
+
+            #[cfg_attr(feature = "debug-provenance", track_caller)]
+            fn $method(self, rhs: &DTensor) -> DTensor {
+                let shape = broadcast_shape(&self.shape, &rhs.shape);
+                let array = self.broadcast_to(&shape).array.$method(&rhs.broadcast_to(&shape).array);
+                DTensor {array, shape}
+            }
+        }
+    };
+}
+
+tensor_op!(Add, add);
+tensor_op!(Sub, sub);
+tensor_op!(Mul, mul);
+tensor_op!(Div, div);
+
+#[cfg(test)]
+mod tests {
+    use crate::DArray;
+    use crate::tensor::DTensor;
+    use crate::test_utils::*;
+
+    fn tensor(len: usize, shape: &[usize]) -> DTensor {
+        DTensor::new(DArray::from((0..len).map(|x| x as f64).collect::<Vec<_>>()), shape)
+    }
+
+    #[test]
+    fn test_sum_along() {
+        let t = tensor(24, &[2, 3, 4]);
+        let sums = t.sum_along(1);
+        assert_eq!(sums.shape(), &[2, 4]);
+        assert_eq!(sums.array().data(), &[12., 15., 18., 21., 48., 51., 54., 57.]);
+        assert_eq!(t.sum_along(2).array().data()[..3], [6., 22., 38.]);
+        assert_eq!(t.mean_along(0).shape(), &[3, 4]);
+        assert_eq!(t.mean_along(0).array().data()[0], 6.);
+        assert_eq!(t.reshape(&[6, 4]).sum_along(0).array().data(), sums.sum_along(0).array().data());
+
+        let weights = DArray::from(vec![1., 2., 3., 4., 5., 6., 7., 8.]);
+        let grads = (sums.array() * &weights).sum().derive();
+        assert_eq!(grads[t.array()].data()[..8], [1., 2., 3., 4., 1., 2., 3., 4.]);
+        assert_eq!(grads[t.array()].data()[12..16], [5., 6., 7., 8.]);
+    }
+
+    #[test]
+    fn test_broadcast() {
+        let a = tensor(6, &[2, 1, 3]);
+        let b = tensor(4, &[4, 1]);
+        let sum = &a + &b;
+        assert_eq!(sum.shape(), &[2, 4, 3]);
+        assert_eq!(sum.array().data()[..6], [0., 1., 2., 1., 2., 3.]);
+        assert_eq!(sum.array().data()[12..15], [3., 4., 5.]);
+        assert_eq!((&tensor(3, &[3]) * &tensor(6, &[2, 3])).array().data(), &[0., 1., 4., 0., 4., 10.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v1 = random_vec(&mut rng, 6);
+        let v2 = random_vec(&mut rng, 4);
+        let w = random_vec(&mut rng, 24);
+        assert_gradients(|arrays| {
+            let a = DTensor::new(arrays[0].clone(), &[2, 1, 3]);
+            let b = DTensor::new(arrays[1].clone(), &[4, 1]);
+            let c = DTensor::new(arrays[2].clone(), &[2, 4, 3]);
+            (&(&(&a * &b) - &c) / &a.map(|x| x.exp())).sum_along(1).map(|x| x.sin()).sum()
+        }, &[&v1, &v2, &w]);
+    }
+
+    #[test]
+    fn test_outer() {
+        let outer = tensor(6, &[2, 3]).outer(&tensor(2, &[2]));
+        assert_eq!(outer.shape(), &[2, 3, 2]);
+        assert_eq!(outer.array().data()[..6], [0., 0., 0., 1., 0., 2.]);
+        assert_eq!(outer.sum_along(2).array().data(), &[0., 1., 2., 3., 4., 5.]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot broadcast the shapes [2, 3] and [2].")]
+    fn test_broadcast_fail() {
+        let _ = &tensor(6, &[2, 3]) + &tensor(2, &[2]);
+    }
+}
@@ -5,6 +5,10 @@ pub use test_utils::*;
 
 #[allow(dead_code)]
 pub mod test_utils {
+    use rand::prelude::StdRng;
+    use rand::Rng;
+    use crate::array::DArray;
+
     /// The seed used for random number generation in tests.
     pub const SEED: [u8; 32] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31];
     /// The difference used for numeric differentiation.
@@ -20,4 +24,40 @@ pub mod test_utils {
             assert!(error < ALLOWED_ERROR, "Values are not close: a={} b={} error={}", a, b, error);
         }
     }
+
+    /// Generates a vector of the given length with random values in the range `[-5, 5)`.
+    pub fn random_vec(rng: &mut StdRng, len: usize) -> Vec<f64> {
+        (0..len).map(|_| rng.gen::<f64>() * 10. - 5.).collect()
+    }
+
+    /// Asserts that the derivatives of a scalar function of several arrays match a numeric derivation.
+    /// Every element of every input is shifted in turn, and the change in the result is compared
+    /// to the change predicted by the derivative.
+    pub fn assert_gradients(func: impl Fn(&[DArray]) -> DArray, inputs: &[impl AsRef<[f64]>]) {
+        let inputs: Vec<Vec<f64>> = inputs.iter().map(|input| input.as_ref().to_vec()).collect();
+        let arrays: Vec<DArray> = inputs.iter().cloned().map(DArray::from).collect();
+        let res = func(&arrays);
+        let res_value = res.data()[0];
+        let grads = res.derive();
+
+        for (arg, input) in inputs.iter().enumerate() {
+            let grad = grads.get(&arrays[arg])
+                .map(|grad| grad.data().to_vec())
+                .unwrap_or_else(|| vec![0.; input.len()]);
+
+            for idx in 0..input.len() {
+                let diff = DIFF * (1. + input[idx].abs());
+                let mut shifted_inputs = inputs.clone();
+                shifted_inputs[arg][idx] += diff;
+                let shifted: Vec<DArray> = shifted_inputs.into_iter().map(DArray::from).collect();
+
+                let expected = grad[idx] * diff;
+                let observed = func(&shifted).data()[0] - res_value;
+                // Differences far below the shift are rounding noise, and are not meaningful.
+                if expected.abs().max(observed.abs()) > diff * 1e-6 {
+                    assert_close(expected, observed);
+                }
+            }
+        }
+    }
 }
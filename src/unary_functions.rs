@@ -1,4 +1,5 @@
 use std::ops::{Div, Mul, Neg};
+use std::sync::Arc;
 use itertools::izip;
 /// Implementation of unary functions for the array.
 /// To make implementing unary functions simpler,
@@ -124,6 +125,7 @@ impl DerivableOp for MulConstFunc {
 impl Mul<f64> for &DArray {
     type Output = DArray;
 
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn mul(self, rhs: f64) -> Self::Output {
         DArray::from(UnaryComp::new(self.clone(), MulConstFunc {cons: rhs}))
     }
@@ -131,6 +133,7 @@ impl Mul<f64> for &DArray {
 impl Mul<f64> for DArray {
     type Output = DArray;
 
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn mul(self, rhs: f64) -> Self::Output {
         DArray::from(UnaryComp::new(self, MulConstFunc {cons: rhs}))
     }
@@ -139,6 +142,7 @@ impl Mul<f64> for DArray {
 impl Div<f64> for &DArray {
     type Output = DArray;
 
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn div(self, rhs: f64) -> Self::Output {
         DArray::from(UnaryComp::new(self.clone(), MulConstFunc {cons: rhs.recip()}))
     }
@@ -147,6 +151,7 @@ impl Div<f64> for &DArray {
 impl Div<f64> for DArray {
     type Output = DArray;
 
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn div(self, rhs: f64) -> Self::Output {
         DArray::from(UnaryComp::new(self, MulConstFunc {cons: rhs.recip()}))
     }
@@ -247,6 +252,51 @@ impl DerivableOp for PowiFunc {
     }
 }
 
+/// The reciprocal function, multiplied by a coefficient.
+#[derive(Copy, Clone, PartialEq)]
+struct RecipFunc {
+    coef: f64,
+}
+
+impl DerivableOp for RecipFunc {
+    type Derivative = ShiftedPowiFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        self.coef / src
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        ShiftedPowiFunc { shift: 0., power: -2, coef: -self.coef }
+    }
+}
+
+/// The power function, given a floating point power.
+#[derive(Copy, Clone, PartialEq)]
+struct PowfFunc {
+    power: f64,
+    coef: f64,
+}
+
+impl DerivableOp for PowfFunc {
+    type Derivative = PowfFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        // The reciprocal square root is common enough to avoid the general power function.
+        if self.power == -0.5 {
+            self.coef / src.sqrt()
+        } else {
+            src.powf(self.power) * self.coef
+        }
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        PowfFunc {
+            power: self.power - 1.,
+            coef: self.coef * self.power,
+        }
+    }
+}
+
 /// The natural logarithm function.
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
 struct LnFunc {}
@@ -263,6 +313,497 @@ impl DerivableOp for LnFunc {
     }
 }
 
+/// The signed power function `coef * |x|^power * sign(x)`, or the power of the absolute value
+/// `coef * |x|^power` if it isn't signed. The derivative of each is the other, which keeps both
+/// defined for negative values and fractional powers.
+#[derive(Copy, Clone, PartialEq)]
+struct SignedPowFunc {
+    power: f64,
+    coef: f64,
+    signed: bool,
+}
+
+impl DerivableOp for SignedPowFunc {
+    type Derivative = SignedPowFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        let abs_pow = src.abs().powf(self.power) * self.coef;
+        if self.signed { abs_pow.copysign(*src) } else { abs_pow }
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        SignedPowFunc {
+            power: self.power - 1.,
+            coef: self.coef * self.power,
+            signed: !self.signed,
+        }
+    }
+}
+
+/// The base 2 logarithm function.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct Log2Func {}
+
+impl DerivableOp for Log2Func {
+    type Derivative = PowfFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        src.log2()
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        PowfFunc { power: -1., coef: std::f64::consts::LOG2_E }
+    }
+}
+
+/// The base 10 logarithm function.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct Log10Func {}
+
+impl DerivableOp for Log10Func {
+    type Derivative = PowfFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        src.log10()
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        PowfFunc { power: -1., coef: std::f64::consts::LOG10_E }
+    }
+}
+
+/// The logarithm function with an arbitrary base.
+#[derive(Copy, Clone, PartialEq)]
+struct LogFunc {
+    base: f64,
+}
+
+impl DerivableOp for LogFunc {
+    type Derivative = PowfFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        src.log(self.base)
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        PowfFunc { power: -1., coef: 1. / self.base.ln() }
+    }
+}
+
+/// The base 2 exponent function, multiplied by a coefficient.
+#[derive(Copy, Clone, PartialEq)]
+struct Exp2Func {
+    coef: f64,
+}
+
+impl DerivableOp for Exp2Func {
+    type Derivative = Exp2Func;
+
+    fn apply(&self, src: &f64) -> f64 {
+        src.exp2() * self.coef
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        Exp2Func { coef: self.coef * std::f64::consts::LN_2 }
+    }
+}
+
+/// The function `e^x - 1`, which is accurate for values near zero.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct ExpM1Func {}
+
+impl DerivableOp for ExpM1Func {
+    type Derivative = ExpFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        src.exp_m1()
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        ExpFunc {}
+    }
+}
+
+/// The power function of a shifted value `coef * (x + shift)^power`, given an integer power.
+#[derive(Copy, Clone, PartialEq)]
+struct ShiftedPowiFunc {
+    shift: f64,
+    power: i32,
+    coef: f64,
+}
+
+impl DerivableOp for ShiftedPowiFunc {
+    type Derivative = ShiftedPowiFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        (src + self.shift).powi(self.power) * self.coef
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        ShiftedPowiFunc {
+            shift: self.shift,
+            power: self.power - 1,
+            coef: self.coef * self.power as f64,
+        }
+    }
+}
+
+/// The function `ln(1 + x)`, which is accurate for values near zero.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct Ln1pFunc {}
+
+impl DerivableOp for Ln1pFunc {
+    type Derivative = ShiftedPowiFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        src.ln_1p()
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        ShiftedPowiFunc { shift: 1., power: -1, coef: 1. }
+    }
+}
+
+/// The function `P(x) * e^(-x^2)` for a polynomial `P`, given by its coefficients from the constant term up.
+/// Used by the derivatives of the error functions, since its derivatives have the same form.
+#[derive(Clone, PartialEq)]
+struct GaussPolyFunc {
+    coefs: Vec<f64>,
+}
+
+impl DerivableOp for GaussPolyFunc {
+    type Derivative = GaussPolyFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        let poly = self.coefs.iter().rev().fold(0., |acc, coef| acc * src + coef);
+        poly * (-src * src).exp()
+    }
+
+    /// The derivative is `(P'(x) - 2 * x * P(x)) * e^(-x^2)`.
+    fn derivative(&self) -> Self::Derivative {
+        let mut coefs = vec![0.; self.coefs.len() + 1];
+        for (power, coef) in self.coefs.iter().enumerate() {
+            coefs[power + 1] -= 2. * coef;
+            if power > 0 {
+                coefs[power - 1] += coef * power as f64;
+            }
+        }
+        GaussPolyFunc { coefs }
+    }
+}
+
+/// The value from which the complementary error function is calculated by a continued fraction.
+const ERFC_FRACTION_START: f64 = 3.;
+/// The depth of the continued fraction of the complementary error function.
+const ERFC_FRACTION_DEPTH: usize = 60;
+
+/// Calculates the error function of values below [`ERFC_FRACTION_START`] by the series
+/// `erf(x) = 2 / sqrt(pi) * e^(-x^2) * sum(2^n * x^(2n + 1) / (1 * 3 * ... * (2n + 1)))`,
+/// whose terms are all positive.
+fn erf_series(src: f64) -> f64 {
+    let mut term = src;
+    let mut total = src;
+    let mut n = 0.;
+    while term.abs() > total.abs() * f64::EPSILON {
+        n += 1.;
+        term *= 2. * src * src / (2. * n + 1.);
+        total += term;
+    }
+    std::f64::consts::FRAC_2_SQRT_PI * (-src * src).exp() * total
+}
+
+/// Calculates the complementary error function of values from [`ERFC_FRACTION_START`] by the continued fraction
+/// `erfc(x) = e^(-x^2) / sqrt(pi) / (x + (1/2) / (x + 1 / (x + (3/2) / (x + ...))))`.
+fn erfc_fraction(src: f64) -> f64 {
+    let denominator = (1..=ERFC_FRACTION_DEPTH).rev()
+        .fold(src, |acc, k| src + k as f64 / 2. / acc);
+    std::f64::consts::FRAC_2_SQRT_PI / 2. * (-src * src).exp() / denominator
+}
+
+/// The error function, or the complementary error function `1 - erf(x)`, which is accurate for large values.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct ErfFunc {
+    complement: bool,
+}
+
+impl DerivableOp for ErfFunc {
+    type Derivative = GaussPolyFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        match (self.complement, src.abs() < ERFC_FRACTION_START) {
+            (false, true) => erf_series(*src),
+            (false, false) => (1. - erfc_fraction(src.abs())).copysign(*src),
+            (true, true) => 1. - erf_series(*src),
+            (true, false) if *src > 0. => erfc_fraction(*src),
+            (true, false) => 2. - erfc_fraction(-src),
+        }
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        let coef = std::f64::consts::FRAC_2_SQRT_PI;
+        GaussPolyFunc { coefs: vec![if self.complement { -coef } else { coef }] }
+    }
+}
+
+/// The value from which the gamma-family functions are calculated by their asymptotic series.
+/// Smaller values are shifted up by the recurrence relations.
+const ASYMPTOTIC_START: f64 = 20.;
+/// The Bernoulli numbers `B_2, B_4, ..., B_14`, used by the asymptotic series of the gamma-family functions.
+const BERNOULLI: [f64; 7] = [1. / 6., -1. / 30., 1. / 42., -1. / 30., 5. / 66., -691. / 2730., 7. / 6.];
+
+/// Calculates `ln|Γ(x)|`, using the reflection formula for small values and Stirling's series for large values.
+fn lgamma(src: f64) -> f64 {
+    if src < 0.5 {
+        // `sin(πx)` is periodic, so it is calculated on the distance from the nearest integer, which is exact.
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * (src - src.round())).sin().abs()).ln() - lgamma(1. - src);
+    }
+    if src == f64::INFINITY {
+        return src;
+    }
+    // Uses `ln Γ(x) = ln Γ(x + 1) - ln(x)`.
+    let mut src = src;
+    let mut shift = 0.;
+    while src < ASYMPTOTIC_START {
+        shift += src.ln();
+        src += 1.;
+    }
+    let mut res = (src - 0.5) * src.ln() - src + 0.5 * (2. * std::f64::consts::PI).ln();
+    for (k, bernoulli) in (1..).zip(BERNOULLI) {
+        res += bernoulli / ((2 * k * (2 * k - 1)) as f64 * src.powi(2 * k - 1));
+    }
+    res - shift
+}
+
+/// Calculates the `n`-th derivative of `cot(πx)`.
+fn cot_derivative(order: i32, src: f64) -> f64 {
+    // The derivatives of `cot(y)` are polynomials in `cot(y)`, with `P_0(c) = c`
+    // and `P_(k+1)(c) = -(1 + c^2) * P_k'(c)`. The coefficients are stored from the constant term up.
+    let mut coefs = vec![0., 1.];
+    for _ in 0..order {
+        let mut next = vec![0.; coefs.len() + 1];
+        for (power, coef) in coefs.iter().enumerate().skip(1) {
+            next[power - 1] -= power as f64 * coef;
+            next[power + 1] -= power as f64 * coef;
+        }
+        coefs = next;
+    }
+    // `cot(πx)` is periodic, so it is calculated on the distance from the nearest integer, which is exact.
+    let pi = std::f64::consts::PI;
+    let cot = 1. / (pi * (src - src.round())).tan();
+    let poly = coefs.iter().rev().fold(0., |acc, coef| acc * cot + coef);
+    pi.powi(order) * poly
+}
+
+/// Calculates the polygamma function `ψ^(n)(x)`, the `n + 1`-th derivative of `ln Γ(x)`.
+/// Values below one half are reflected, and the other values below the start of the asymptotic series are shifted up.
+/// At infinity, the digamma function is infinite and the higher orders are zero. At negative infinity, it is undefined.
+fn polygamma(order: i32, src: f64) -> f64 {
+    if src == f64::INFINITY {
+        return if order == 0 { f64::INFINITY } else { 0. };
+    }
+    if !src.is_finite() {
+        return f64::NAN;
+    }
+    if src < 0.5 {
+        // Uses `ψ^(n)(x) = (-1)^n * ψ^(n)(1 - x) - π * d^n/dx^n cot(πx)`.
+        let pi = std::f64::consts::PI;
+        return (-1_f64).powi(order) * polygamma(order, 1. - src) - pi * cot_derivative(order, src);
+    }
+    let factorial = |n: i32| (1..=n).map(|k| k as f64).product::<f64>();
+    // `(-1)^(n + 1) * n!` is the coefficient of `x^-(n + 1)` in the polygamma function.
+    let coef = -(-1_f64).powi(order) * factorial(order);
+
+    // Uses `ψ^(n)(x) = ψ^(n)(x + 1) + (-1)^(n + 1) * n! / x^(n + 1)`.
+    let mut src = src;
+    let mut res = 0.;
+    while src < ASYMPTOTIC_START + order as f64 {
+        res += coef / src.powi(order + 1);
+        src += 1.;
+    }
+    if order == 0 {
+        res += src.ln() - 0.5 / src;
+        for (k, bernoulli) in (1..).zip(BERNOULLI) {
+            res -= bernoulli / ((2 * k) as f64 * src.powi(2 * k));
+        }
+    } else {
+        let sign = -(-1_f64).powi(order);
+        res += sign * (factorial(order - 1) / src.powi(order) + factorial(order) / (2. * src.powi(order + 1)));
+        for (k, bernoulli) in (1..).zip(BERNOULLI) {
+            // The ratio `(2k + n - 1)! / (2k)!`.
+            let ratio = (2 * k + 1..2 * k + order).map(|j| j as f64).product::<f64>();
+            res += sign * bernoulli * ratio / src.powi(2 * k + order);
+        }
+    }
+    res
+}
+
+/// The logarithm of the absolute value of the gamma function.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct LgammaFunc {}
+
+impl DerivableOp for LgammaFunc {
+    type Derivative = PolygammaFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        lgamma(*src)
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        PolygammaFunc { order: 0 }
+    }
+}
+
+/// The polygamma function of the given order. The order 0 is the digamma function, and 1 is the trigamma function.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct PolygammaFunc {
+    order: i32,
+}
+
+impl DerivableOp for PolygammaFunc {
+    type Derivative = PolygammaFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        polygamma(self.order, *src)
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        PolygammaFunc { order: self.order + 1 }
+    }
+}
+
+/// The piecewise function `slope * x + intercept` for positive values and `alpha * e^x + shift` otherwise.
+/// Represents the ELU and leaky ReLU activations, and all their derivatives.
+#[derive(Copy, Clone, PartialEq)]
+struct PiecewiseFunc {
+    slope: f64,
+    intercept: f64,
+    alpha: f64,
+    shift: f64,
+    /// If set, the negative part is `alpha * x + shift` instead, as in the leaky ReLU.
+    linear: bool,
+}
+
+impl DerivableOp for PiecewiseFunc {
+    type Derivative = PiecewiseFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        if *src > 0. {
+            self.slope * src + self.intercept
+        } else if self.linear {
+            self.alpha * src + self.shift
+        } else {
+            self.alpha * src.exp() + self.shift
+        }
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        PiecewiseFunc {
+            slope: 0.,
+            intercept: self.slope,
+            alpha: if self.linear { 0. } else { self.alpha },
+            shift: if self.linear { self.alpha } else { 0. },
+            linear: self.linear,
+        }
+    }
+}
+
+/// The function `P(x) * Φ(x) + Q(x) * φ(x)` for polynomials `P` and `Q`, given by their coefficients from
+/// the constant term up, where `Φ` and `φ` are the cumulative distribution and the density of the standard normal
+/// distribution. Represents the GELU activation and all its derivatives.
+#[derive(Clone, PartialEq)]
+struct NormalPolyFunc {
+    cdf_coefs: Vec<f64>,
+    pdf_coefs: Vec<f64>,
+}
+
+impl DerivableOp for NormalPolyFunc {
+    type Derivative = NormalPolyFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        let cdf_poly = self.cdf_coefs.iter().rev().fold(0., |acc, coef| acc * src + coef);
+        let pdf_poly = self.pdf_coefs.iter().rev().fold(0., |acc, coef| acc * src + coef);
+        // The complement keeps the precision of the distribution function for negative values.
+        let cdf = ErfFunc { complement: true }.apply(&(-src * std::f64::consts::FRAC_1_SQRT_2)) / 2.;
+        let pdf = (-src * src / 2.).exp() * std::f64::consts::FRAC_2_SQRT_PI * std::f64::consts::FRAC_1_SQRT_2 / 2.;
+        cdf_poly * cdf + pdf_poly * pdf
+    }
+
+    /// The derivative is `P'(x) * Φ(x) + (P(x) + Q'(x) - x * Q(x)) * φ(x)`, since `φ'(x) = -x * φ(x)`.
+    fn derivative(&self) -> Self::Derivative {
+        let cdf_coefs: Vec<f64> = self.cdf_coefs.iter().enumerate().skip(1)
+            .map(|(power, coef)| coef * power as f64)
+            .collect();
+        let mut pdf_coefs = vec![0.; self.cdf_coefs.len().max(self.pdf_coefs.len() + 1)];
+        for (power, coef) in self.cdf_coefs.iter().enumerate() {
+            pdf_coefs[power] += coef;
+        }
+        for (power, coef) in self.pdf_coefs.iter().enumerate() {
+            pdf_coefs[power + 1] -= coef;
+            if power > 0 {
+                pdf_coefs[power - 1] += coef * power as f64;
+            }
+        }
+        NormalPolyFunc { cdf_coefs, pdf_coefs }
+    }
+}
+
+/// The direction in which values are rounded to integers.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Rounding {
+    /// Rounds down.
+    Floor,
+    /// Rounds up.
+    Ceil,
+    /// Rounds to the nearest integer, and away from zero on ties.
+    Round,
+    /// Rounds towards zero.
+    Trunc,
+}
+
+/// A function rounding values to integers.
+/// The derivative is zero, or one when using the straight-through estimator.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct RoundFunc {
+    rounding: Rounding,
+    straight_through: bool,
+}
+
+impl DerivableOp for RoundFunc {
+    type Derivative = ConstFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        match self.rounding {
+            Rounding::Floor => src.floor(),
+            Rounding::Ceil => src.ceil(),
+            Rounding::Round => src.round(),
+            Rounding::Trunc => src.trunc(),
+        }
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        ConstFunc { cons: if self.straight_through { 1. } else { 0. } }
+    }
+}
+
+/// The remainder of the division by a constant, with the sign of the numerator.
+#[derive(Copy, Clone, PartialEq)]
+struct RemConstFunc {
+    val: f64,
+}
+
+impl DerivableOp for RemConstFunc {
+    type Derivative = ConstFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        src % self.val
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        ConstFunc { cons: 1. }
+    }
+}
+
 /// The natural logarithm function.
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
 struct NegFunc {}
@@ -281,12 +822,14 @@ impl DerivableOp for NegFunc {
 
 impl Neg for &DArray {
     type Output = DArray;
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn neg(self) -> Self::Output {
         self.map(NegFunc {})
     }
 }
 impl Neg for DArray {
     type Output = DArray;
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     fn neg(self) -> Self::Output {
         self.map(NegFunc {})
     }
@@ -334,29 +877,434 @@ impl DerivableOp for CosFunc {
     }
 }
 
+/// The hyperbolic sine function.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct SinhFunc {}
+
+/// The hyperbolic cosine function.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct CoshFunc {}
+
+impl DerivableOp for SinhFunc {
+    type Derivative = CoshFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        src.sinh()
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        CoshFunc {}
+    }
+}
+
+impl DerivableOp for CoshFunc {
+    type Derivative = SinhFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        src.cosh()
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        SinhFunc {}
+    }
+}
+
+/// A function whose derivative is a polynomial of the function itself.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+enum PolyBase {
+    /// The hyperbolic tangent `t`, whose derivative is `1 - t^2`.
+    Tanh,
+    /// The sigmoid `1 / (1 + e^-x)`, whose derivative is `s - s^2`.
+    Sigmoid,
+    /// The tangent `t`, whose derivative is `1 + t^2`.
+    Tan,
+}
+
+impl PolyBase {
+    fn apply(&self, src: f64) -> f64 {
+        match self {
+            PolyBase::Tanh => src.tanh(),
+            PolyBase::Tan => src.tan(),
+            // Only exponentiating non-positive values, so large inputs don't overflow.
+            PolyBase::Sigmoid => if src >= 0. {
+                1. / (1. + (-src).exp())
+            } else {
+                let exp = src.exp();
+                exp / (1. + exp)
+            },
+        }
+    }
+
+    /// The coefficients of the derivative as a polynomial of the function, from the constant term up.
+    fn derivative(&self) -> [f64; 3] {
+        match self {
+            PolyBase::Tanh => [1., 0., -1.],
+            PolyBase::Sigmoid => [0., 1., -1.],
+            PolyBase::Tan => [1., 0., 1.],
+        }
+    }
+}
+
+/// A polynomial, given by its coefficients from the constant term up.
+#[derive(Clone, PartialEq)]
+struct PolyFunc {
+    coefs: Vec<f64>,
+}
+
+impl DerivableOp for PolyFunc {
+    type Derivative = PolyFunc;
+
+    /// Evaluates the polynomial with Horner's scheme.
+    fn apply(&self, src: &f64) -> f64 {
+        self.coefs.iter().rev().fold(0., |acc, coef| acc * src + coef)
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        let coefs = self.coefs.iter().enumerate().skip(1).map(|(power, coef)| coef * power as f64).collect();
+        PolyFunc { coefs }
+    }
+}
+
+/// A polynomial of a base function, given by its coefficients from the constant term up.
+/// The derivatives of polynomials of the function are polynomials of it as well.
+#[derive(Clone, PartialEq)]
+struct BasePolyFunc {
+    base: PolyBase,
+    coefs: Vec<f64>,
+}
+
+impl DerivableOp for BasePolyFunc {
+    type Derivative = BasePolyFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        let base = self.base.apply(*src);
+        self.coefs.iter().rev().fold(0., |acc, coef| acc * base + coef)
+    }
+
+    /// The derivative of `P(b)` is `P'(b) * b'`, where `b'` is a polynomial of `b`.
+    fn derivative(&self) -> Self::Derivative {
+        let poly_deriv: Vec<f64> = self.coefs.iter().enumerate().skip(1).map(|(power, coef)| coef * power as f64).collect();
+        let base_deriv = self.base.derivative();
+        let mut coefs = vec![0.; poly_deriv.len() + 2];
+        for (power, coef) in poly_deriv.iter().enumerate() {
+            for (base_power, base_coef) in base_deriv.iter().enumerate() {
+                coefs[power + base_power] += coef * base_coef;
+            }
+        }
+        BasePolyFunc { base: self.base, coefs }
+    }
+}
+
+/// The function `P(x) * (c + a * x^2)^power` for a polynomial `P`, given by its coefficients from the constant term up.
+/// Used by the derivatives of the inverse trigonometric and hyperbolic functions, since its derivatives have the same form.
+#[derive(Clone, PartialEq)]
+struct QuadPowFunc {
+    quad_const: f64,
+    quad_coef: f64,
+    power: f64,
+    coefs: Vec<f64>,
+}
+
+impl DerivableOp for QuadPowFunc {
+    type Derivative = QuadPowFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        let poly = self.coefs.iter().rev().fold(0., |acc, coef| acc * src + coef);
+        poly * (self.quad_const + self.quad_coef * src * src).powf(self.power)
+    }
+
+    /// The derivative is `(2 * a * power * x * P(x) + (c + a * x^2) * P'(x)) * (c + a * x^2)^(power - 1)`.
+    fn derivative(&self) -> Self::Derivative {
+        let mut coefs = vec![0.; self.coefs.len() + 1];
+        for (power, coef) in self.coefs.iter().enumerate() {
+            coefs[power + 1] += 2. * self.quad_coef * self.power * coef;
+            if power > 0 {
+                coefs[power - 1] += self.quad_const * coef * power as f64;
+                coefs[power + 1] += self.quad_coef * coef * power as f64;
+            }
+        }
+        QuadPowFunc { quad_const: self.quad_const, quad_coef: self.quad_coef, power: self.power - 1., coefs }
+    }
+}
+
+/// An inverse trigonometric or hyperbolic function.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+enum InverseTrig {
+    Asin,
+    Acos,
+    Atan,
+    Asinh,
+    Acosh,
+    Atanh,
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct InverseTrigFunc {
+    func: InverseTrig,
+}
+
+impl DerivableOp for InverseTrigFunc {
+    type Derivative = QuadPowFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        match self.func {
+            InverseTrig::Asin => src.asin(),
+            InverseTrig::Acos => src.acos(),
+            InverseTrig::Atan => src.atan(),
+            InverseTrig::Asinh => src.asinh(),
+            InverseTrig::Acosh => src.acosh(),
+            InverseTrig::Atanh => src.atanh(),
+        }
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        match self.func {
+            InverseTrig::Asin => QuadPowFunc { quad_const: 1., quad_coef: -1., power: -0.5, coefs: vec![1.] },
+            InverseTrig::Acos => QuadPowFunc { quad_const: 1., quad_coef: -1., power: -0.5, coefs: vec![-1.] },
+            InverseTrig::Atan => QuadPowFunc { quad_const: 1., quad_coef: 1., power: -1., coefs: vec![1.] },
+            InverseTrig::Asinh => QuadPowFunc { quad_const: 1., quad_coef: 1., power: -0.5, coefs: vec![1.] },
+            InverseTrig::Acosh => QuadPowFunc { quad_const: -1., quad_coef: 1., power: -0.5, coefs: vec![1.] },
+            InverseTrig::Atanh => QuadPowFunc { quad_const: 1., quad_coef: -1., power: -1., coefs: vec![1.] },
+        }
+    }
+}
+
+/// The softplus function `ln(1 + e^x)`, a smooth approximation of `max(x, 0)`.
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+struct SoftplusFunc {}
+
+impl DerivableOp for SoftplusFunc {
+    type Derivative = BasePolyFunc;
+
+    /// Calculated as `max(x, 0) + ln(1 + e^-|x|)`, which doesn't overflow for large inputs.
+    fn apply(&self, src: &f64) -> f64 {
+        src.max(0.) + (-src.abs()).exp().ln_1p()
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        BasePolyFunc { base: PolyBase::Sigmoid, coefs: vec![0., 1.] }
+    }
+}
+
 /// An implementation of the standard f64 functions to floats.
 impl DArray {
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     pub fn sin(&self) -> DArray {
         self.map(SinFunc { sign_flip: false })
     }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     pub fn cos(&self) -> DArray {
         self.map(CosFunc { sign_flip: false })
     }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn tan(&self) -> DArray {
+        self.map(BasePolyFunc { base: PolyBase::Tan, coefs: vec![0., 1.] })
+    }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn asin(&self) -> DArray {
+        self.map(InverseTrigFunc { func: InverseTrig::Asin })
+    }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn acos(&self) -> DArray {
+        self.map(InverseTrigFunc { func: InverseTrig::Acos })
+    }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn atan(&self) -> DArray {
+        self.map(InverseTrigFunc { func: InverseTrig::Atan })
+    }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn sinh(&self) -> DArray {
+        self.map(SinhFunc {})
+    }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn cosh(&self) -> DArray {
+        self.map(CoshFunc {})
+    }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn tanh(&self) -> DArray {
+        self.map(BasePolyFunc { base: PolyBase::Tanh, coefs: vec![0., 1.] })
+    }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn asinh(&self) -> DArray {
+        self.map(InverseTrigFunc { func: InverseTrig::Asinh })
+    }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn acosh(&self) -> DArray {
+        self.map(InverseTrigFunc { func: InverseTrig::Acosh })
+    }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn atanh(&self) -> DArray {
+        self.map(InverseTrigFunc { func: InverseTrig::Atanh })
+    }
+    /// The sigmoid function `1 / (1 + e^-x)`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn sigmoid(&self) -> DArray {
+        self.map(BasePolyFunc { base: PolyBase::Sigmoid, coefs: vec![0., 1.] })
+    }
+    /// The softplus function `ln(1 + e^x)`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn softplus(&self) -> DArray {
+        self.map(SoftplusFunc {})
+    }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     pub fn exp(&self) -> DArray {
         self.map(ExpFunc {})
     }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     pub fn powi(&self, power: i32) -> DArray {
         self.map(PowiFunc { power, coef: 1 })
     }
+    /// Calculates `x^power` for a floating point power.
+    /// Fractional powers of negative values are `NaN`, see [`DArray::signed_pow`] for a version defined for them.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn powf(&self, power: f64) -> DArray {
+        self.map(PowfFunc { power, coef: 1. })
+    }
+    /// Evaluates the polynomial with the given coefficients, from the constant term up, in a single computation.
+    /// For example, the coefficients `[1., 0., 3.]` give `1 + 3 * x^2`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn polyval(&self, coefs: &[f64]) -> DArray {
+        self.map(PolyFunc { coefs: coefs.to_vec() })
+    }
+    /// Calculates the reciprocal `1 / x`, with a single division per element.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn recip(&self) -> DArray {
+        self.recip_scaled(1.)
+    }
+    /// Calculates `coef / x`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub(crate) fn recip_scaled(&self, coef: f64) -> DArray {
+        self.map(RecipFunc { coef })
+    }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     pub fn signum(&self) -> DArray {
         self.map(SignumFunc {})
     }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     pub fn abs(&self) -> DArray {
         self.map(AbsFunc {})
     }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     pub fn ln(&self) -> DArray {
         self.map(LnFunc {})
     }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn log2(&self) -> DArray {
+        self.map(Log2Func {})
+    }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn log10(&self) -> DArray {
+        self.map(Log10Func {})
+    }
+    /// Calculates the logarithm with respect to an arbitrary base.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn log(&self, base: f64) -> DArray {
+        self.map(LogFunc { base })
+    }
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn exp2(&self) -> DArray {
+        self.map(Exp2Func { coef: 1. })
+    }
+    /// Calculates `e^x - 1`, more accurately than subtracting one from the exponent near zero.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn exp_m1(&self) -> DArray {
+        self.map(ExpM1Func {})
+    }
+    /// Calculates `ln(1 + x)`, more accurately than adding one before the logarithm near zero.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn ln_1p(&self) -> DArray {
+        self.map(Ln1pFunc {})
+    }
+    /// The error function `erf(x) = 2 / sqrt(pi) * integral(e^(-t^2), 0, x)`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn erf(&self) -> DArray {
+        self.map(ErfFunc { complement: false })
+    }
+    /// The complementary error function `1 - erf(x)`, which keeps its precision for large values.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn erfc(&self) -> DArray {
+        self.map(ErfFunc { complement: true })
+    }
+    /// The rectified linear unit `max(x, 0)`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn relu(&self) -> DArray {
+        self.leaky_relu(0.)
+    }
+    /// The leaky rectified linear unit, which is `x` for positive values and `alpha * x` otherwise.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn leaky_relu(&self, alpha: f64) -> DArray {
+        self.map(PiecewiseFunc { slope: 1., intercept: 0., alpha, shift: 0., linear: true })
+    }
+    /// The exponential linear unit, which is `x` for positive values and `alpha * (e^x - 1)` otherwise.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn elu(&self, alpha: f64) -> DArray {
+        self.map(PiecewiseFunc { slope: 1., intercept: 0., alpha, shift: -alpha, linear: false })
+    }
+    /// The Gaussian error linear unit `x * Φ(x)`, where `Φ` is the cumulative distribution function
+    /// of the standard normal distribution.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn gelu(&self) -> DArray {
+        self.map(NormalPolyFunc { cdf_coefs: vec![0., 1.], pdf_coefs: vec![] })
+    }
+    /// The logarithm of the absolute value of the gamma function `ln|Γ(x)|`.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn lgamma(&self) -> DArray {
+        self.map(LgammaFunc {})
+    }
+    /// The digamma function `ψ(x)`, the derivative of [`DArray::lgamma`].
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn digamma(&self) -> DArray {
+        self.map(PolygammaFunc { order: 0 })
+    }
+    /// The trigamma function `ψ'(x)`, the derivative of [`DArray::digamma`].
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn trigamma(&self) -> DArray {
+        self.map(PolygammaFunc { order: 1 })
+    }
+    /// Rounds the values to integers in the given direction.
+    /// The derivative is zero, unless `straight_through` is set, in which case the rounding is treated
+    /// as the identity in the backward pass, as needed for quantization-aware training.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn round_with(&self, rounding: Rounding, straight_through: bool) -> DArray {
+        self.map(RoundFunc { rounding, straight_through })
+    }
+    /// Rounds down. The derivative is zero.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn floor(&self) -> DArray {
+        self.round_with(Rounding::Floor, false)
+    }
+    /// Rounds up. The derivative is zero.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn ceil(&self) -> DArray {
+        self.round_with(Rounding::Ceil, false)
+    }
+    /// Rounds to the nearest integer. The derivative is zero.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn round(&self) -> DArray {
+        self.round_with(Rounding::Round, false)
+    }
+    /// Rounds towards zero. The derivative is zero.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn trunc(&self) -> DArray {
+        self.round_with(Rounding::Trunc, false)
+    }
+    /// Returns the pointwise remainder of the division by a constant, with the sign of `self`.
+    /// The derivative is 1 everywhere except on the jumps of the remainder.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn rem_scalar(&self, val: f64) -> DArray {
+        self.map(RemConstFunc { val })
+    }
+    /// Calculates `|x|^power * sign(x)`, which is defined and differentiable for negative values
+    /// and fractional powers, and preserves the sign.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn signed_pow(&self, power: f64) -> DArray {
+        self.map(SignedPowFunc { power, coef: 1., signed: true })
+    }
+    /// Calculates the reciprocal square root `x^(-1/2)` in a single computation.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn rsqrt(&self) -> DArray {
+        self.map(PowfFunc { power: -0.5, coef: 1. })
+    }
 }
 
 /// A function testing if the value is larger than some contant.
@@ -442,29 +1390,127 @@ impl DerivableOp for MinFunc {
 
 impl DArray {
     /// Performs the pointwise maximum function.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     pub fn max(&self, val: f64) -> DArray {
         self.map(MaxFunc { val })
     }
     /// Performs the pointwise minimum function.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     pub fn min(&self, val: f64) -> DArray {
         self.map(MinFunc { val })
     }
     /// Returns an array with ones where the original value is larger than the given value and 0 otherwise.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     pub fn gt(&self, val: f64) -> DArray {
         self.map(GtFunc { val })
     }
     /// Returns an array with ones where the original value is smaller than the given value and 0 otherwise.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
     pub fn lt(&self, val: f64) -> DArray {
         self.map(LtFunc { val })
     }
 }
 
+/// A computation calculating the pointwise maximum or minimum of an array and a threshold given by a scalar array,
+/// so that the threshold can be differentiated as well.
+#[derive(Clone)]
+pub struct ThresholdComp {
+    src: DArray,
+    threshold: DArray,
+    /// If set, calculates the maximum, and otherwise the minimum.
+    max: bool,
+}
+
+impl Computation for ThresholdComp {
+    fn sources(&self) -> Vec<DArray> {
+        vec![self.src.clone(), self.threshold.clone()]
+    }
+
+    /// The gradients flow to the source where it passes the threshold, and to the threshold everywhere else.
+    fn derivatives(&self, res_grads: DArray) -> Vec<DArray> {
+        let shifted = &self.src - &self.threshold;
+        let mask = if self.max { shifted.gt(0.) } else { shifted.lt(0.) };
+        let src_grads = &res_grads * &mask;
+        let threshold_grads = (&res_grads - &src_grads).sum();
+        vec![src_grads, threshold_grads]
+    }
+
+    fn len(&self) -> usize {
+        self.src.len()
+    }
+
+    fn apply(&self, res_array: &mut [f64]) {
+        let threshold = self.threshold.data()[0];
+        for (res, src) in res_array.iter_mut().zip(self.src.data()) {
+            *res += if self.max { src.max(threshold) } else { src.min(threshold) };
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.threshold.is_scalar() {
+            Ok(())
+        } else {
+            Err(format!("The threshold has length {} instead of 1.", self.threshold.len()))
+        }
+    }
+}
+
+impl DArray {
+    /// Performs the pointwise maximum with a threshold given by a scalar array, differentiable by the threshold.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn max_of(&self, threshold: &DArray) -> DArray {
+        DArray::from(ThresholdComp { src: self.clone(), threshold: threshold.clone(), max: true })
+    }
+    /// Performs the pointwise minimum with a threshold given by a scalar array, differentiable by the threshold.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn min_of(&self, threshold: &DArray) -> DArray {
+        DArray::from(ThresholdComp { src: self.clone(), threshold: threshold.clone(), max: false })
+    }
+}
+
+/// A pointwise function given by closures, with its derivative.
+/// The derivative of the derivative isn't known, so it is a function which panics when it is evaluated.
+/// Deriving the derivative again only builds it, so double backward passes which don't need it still work.
+#[derive(Clone)]
+struct ClosureFunc {
+    func: Arc<dyn Fn(f64) -> f64 + Send + Sync>,
+    derivative: Option<Arc<dyn Fn(f64) -> f64 + Send + Sync>>,
+}
+
+impl DerivableOp for ClosureFunc {
+    type Derivative = ClosureFunc;
+
+    fn apply(&self, src: &f64) -> f64 {
+        (self.func)(*src)
+    }
+
+    fn derivative(&self) -> Self::Derivative {
+        let derivative = self.derivative.clone().unwrap_or_else(|| Arc::new(|_| {
+            panic!("The second derivative of a function given by closures is not defined!")
+        }));
+        ClosureFunc { func: derivative, derivative: None }
+    }
+}
+
+impl DArray {
+    /// Applies a custom pointwise function `func`, whose derivative is `derivative`.
+    /// The result can be differentiated once. Evaluating the second derivative panics.
+    #[cfg_attr(feature = "debug-provenance", track_caller)]
+    pub fn map_fn(
+        &self,
+        func: impl Fn(f64) -> f64 + Send + Sync + 'static,
+        derivative: impl Fn(f64) -> f64 + Send + Sync + 'static,
+    ) -> DArray {
+        self.map(ClosureFunc { func: Arc::new(func), derivative: Some(Arc::new(derivative)) })
+    }
+}
 
 /// Tests for the unary functions.
 #[cfg(test)]
 mod tests {
     use std::ops::Neg;
-    use crate::array::DArray;
+    use crate::array::{DArray, DiffMode};
+    use crate::unary_functions::{polygamma, Rounding};
     use crate::test_utils::*;
 
     /// Tests a generic unary function.
@@ -499,6 +1545,77 @@ mod tests {
         test_unary(|array|array.sin());
     }
     #[test]
+    fn test_trigonometric() {
+        assert_close(DArray::from(1.).tan().data()[0], 1_f64.tan());
+        assert_close(DArray::from(0.5).asin().data()[0], std::f64::consts::FRAC_PI_6);
+        assert_close(DArray::from(0.5).acos().data()[0], std::f64::consts::FRAC_PI_3);
+        assert_close(DArray::from(1.).atan().data()[0], std::f64::consts::FRAC_PI_4);
+
+        test_unary(|array|(array * 0.03).tan());
+        test_unary(|array|(array * 0.019).asin());
+        test_unary(|array|(array * 0.019).acos());
+        test_unary(|array|array.atan());
+        // Tests the second derivatives.
+        let second = |func: fn(&DArray) -> DArray| move |array: DArray| {
+            let grad = func(&array).derive().get(&array).unwrap().clone();
+            grad.derive().get(&array).unwrap().clone()
+        };
+        test_unary(second(|array| (array * 0.03).tan()));
+        test_unary(second(|array| (array * 0.019).asin()));
+        test_unary(second(|array| (array * 0.019).acos()));
+        test_unary(second(|array| (array * 0.1).atan()));
+    }
+    #[test]
+    fn test_hyperbolic() {
+        test_unary(|array|array.sinh());
+        test_unary(|array|array.cosh());
+        test_unary(|array|(array * 0.05).tanh());
+        assert_eq!(DArray::from(0.).tanh().data(), &[0.]);
+        assert_eq!(DArray::from(1000.).tanh().data(), &[1.]);
+        // Tests the higher derivatives of the hyperbolic tangent.
+        test_unary(|array| {
+            let grad = (&array * 0.05).tanh().derive().get(&array).unwrap().clone();
+            grad.derive().get(&array).unwrap().clone()
+        });
+    }
+    #[test]
+    fn test_inverse_hyperbolic() {
+        assert_close(DArray::from(1.).asinh().data()[0], (1. + 2_f64.sqrt()).ln());
+        assert_close(DArray::from(2.).acosh().data()[0], (2. + 3_f64.sqrt()).ln());
+        assert_close(DArray::from(0.5).atanh().data()[0], 3_f64.ln() / 2.);
+
+        test_unary(|array|array.asinh());
+        test_unary(|array|(array * 0.1 + 6.).acosh());
+        test_unary(|array|(array * 0.019).atanh());
+        // Tests the second derivatives.
+        let second = |func: fn(&DArray) -> DArray| move |array: DArray| {
+            let grad = func(&array).derive().get(&array).unwrap().clone();
+            grad.derive().get(&array).unwrap().clone()
+        };
+        test_unary(second(|array| array.asinh()));
+        test_unary(second(|array| (array * 0.1 + 6.).acosh()));
+        test_unary(second(|array| (array * 0.019).atanh()));
+    }
+    #[test]
+    fn test_sigmoid_softplus() {
+        let array = DArray::from(vec![-1000., 0., 1000.]);
+        assert_eq!(array.sigmoid().data(), &[0., 0.5, 1.]);
+        assert_eq!(array.softplus().data(), &[0., 2_f64.ln(), 1000.]);
+        assert_close(DArray::from(2.).sigmoid().data()[0], 1. / (1. + (-2_f64).exp()));
+        assert_close(DArray::from(-3.).softplus().data()[0], (-3_f64).exp().ln_1p());
+        let grads = array.sigmoid().sum().derive();
+        assert_eq!(grads.get(&array).unwrap().data(), &[0., 0.25, 0.]);
+
+        test_unary(|array|(array * 0.1).sigmoid());
+        test_unary(|array|array.softplus());
+        // Tests the higher derivatives.
+        test_unary(|array| {
+            let grad = (&array * 0.1).sigmoid().derive().get(&array).unwrap().clone();
+            grad.derive().get(&array).unwrap().clone()
+        });
+        test_unary(|array| (&array * 0.1).softplus().derive().get(&array).unwrap().clone());
+    }
+    #[test]
     fn test_signum() {
         test_unary(|array|array.signum());
     }
@@ -511,12 +1628,271 @@ mod tests {
         test_unary(|array| if array.data()[0] > 0. {array.ln()} else {array});
     }
     #[test]
+    fn test_log2() {
+        assert_eq!(DArray::from(8.).log2().data(), &[3.]);
+        test_unary(|array| if array.data()[0] > 0. {array.log2()} else {array});
+    }
+    #[test]
+    fn test_log10() {
+        assert_eq!(DArray::from(100.).log10().data(), &[2.]);
+        test_unary(|array| if array.data()[0] > 0. {array.log10()} else {array});
+    }
+    #[test]
+    fn test_log() {
+        assert_close(DArray::from(81.).log(3.).data()[0], 4.);
+        assert_close(DArray::from(0.25).log(0.5).data()[0], 2.);
+        for base in [0.5, 3., 7.] {
+            test_unary(|array| if array.data()[0] > 0. {array.log(base)} else {array});
+        }
+    }
+    #[test]
+    fn test_exp2() {
+        assert_eq!(DArray::from(3.).exp2().data(), &[8.]);
+        test_unary(|array| (array * 0.2).exp2());
+    }
+    #[test]
+    fn test_exp_m1_ln_1p() {
+        assert_eq!(DArray::from(1e-20).exp_m1().data(), &[1e-20]);
+        assert_eq!(DArray::from(1e-20).ln_1p().data(), &[1e-20]);
+        let array = DArray::from(vec![1e-20, 1.]);
+        assert_eq!(array.exp_m1().sum().derive().get(&array).unwrap().data(), &[1., 1_f64.exp()]);
+        assert_eq!(array.ln_1p().sum().derive().get(&array).unwrap().data(), &[1., 0.5]);
+
+        test_unary(|array|(array * 0.1).exp_m1());
+        test_unary(|array| if array.data()[0] > -1. {array.ln_1p()} else {array});
+        // Tests the second derivative.
+        test_unary(|array| if array.data()[0] > -1. {array.ln_1p().derive().get(&array).unwrap().clone()} else {array});
+    }
+    #[test]
+    fn test_erf() {
+        let array = DArray::from(vec![-4., -0.5, 0., 1., 2.5, 3., 10.]);
+        let erf = [-0.9999999845827421, -0.5204998778130465, 0., 0.8427007929497149, 0.999593047982555,
+            0.9999779095030014, 1.];
+        let erfc = [1.999999984582742, 1.5204998778130465, 1., 0.15729920705028513, 0.0004069520174449589,
+            2.2090496998585438e-05, 2.088487583762545e-45];
+        for (res, erf) in array.erf().data().iter().zip(erf) {
+            assert_close(*res, erf);
+        }
+        for (res, erfc) in array.erfc().data().iter().zip(erfc) {
+            assert_close(*res, erfc);
+        }
+
+        test_unary(|array|(array * 0.05).erf());
+        test_unary(|array|(array * 0.05).erfc());
+        // Tests the second derivatives.
+        test_unary(|array| (&array * 0.1).erf().derive().get(&array).unwrap().clone());
+        test_unary(|array| (&array * 0.1).erfc().derive().get(&array).unwrap().clone());
+    }
+    #[test]
+    fn test_gamma() {
+        let array = DArray::from(vec![0.5, 3.7, -2.5, 25.]);
+        let lgamma = [0.5723649429247001, 1.428072326665388, -0.05624371649767403, 54.78472939811232];
+        let digamma = [-1.9635100260214235, 1.1671535393615113, 1.103156640645243, 3.198742512851974];
+        let trigamma = [4.934802200544679, 0.3100378576700383, 9.539246644989124, 0.04081066325722558];
+        for (res, expected) in array.lgamma().data().iter().zip(lgamma) {
+            assert_close(*res, expected);
+        }
+        for (res, expected) in array.digamma().data().iter().zip(digamma) {
+            assert_close(*res, expected);
+        }
+        for (res, expected) in array.trigamma().data().iter().zip(trigamma) {
+            assert_close(*res, expected);
+        }
+        assert_close(DArray::from(5.).lgamma().data()[0], 24_f64.ln());
+
+        // Negative values are reflected, so their cost doesn't grow with their magnitude.
+        let array = DArray::from(vec![-7.3, -0.2, -1e5 - 0.25]);
+        let lgamma = [-7.779101629826852, 1.761497590833938, -1051300.6088285745];
+        let digamma = [4.33730730551005, 4.034991433293861, 14.654525618536063];
+        let trigamma = [14.95138318143392, 27.2994741375017, 19.739198802253717];
+        let tetragamma = [68.82117406359034, 245.56988429157832, 124.0251067210993];
+        for (res, expected) in array.lgamma().data().iter().zip(lgamma) {
+            assert_close(*res, expected);
+        }
+        for (res, expected) in array.digamma().data().iter().zip(digamma) {
+            assert_close(*res, expected);
+        }
+        for (res, expected) in array.trigamma().data().iter().zip(trigamma) {
+            assert_close(*res, expected);
+        }
+        for (res, expected) in (0..3).map(|idx| polygamma(2, array.data()[idx])).zip(tetragamma) {
+            assert_close(res, expected);
+        }
+        let infinite = DArray::from(vec![f64::NEG_INFINITY, -1e17, f64::INFINITY]);
+        assert!(infinite.digamma().data()[0].is_nan());
+        assert!(infinite.trigamma().data()[0].is_nan());
+        assert!(DArray::from(f64::NAN).digamma().data()[0].is_nan());
+        assert_eq!(infinite.digamma().data()[2], f64::INFINITY);
+        assert_eq!(infinite.trigamma().data()[2], 0.);
+        assert_eq!(polygamma(2, f64::INFINITY), 0.);
+        assert_eq!(infinite.lgamma().data()[1..], [f64::INFINITY, f64::INFINITY]);
+
+        // The functions are tested on positive values, away from the poles.
+        test_unary(|array| (array * 0.1 + 5.5).lgamma());
+        test_unary(|array| (array * 0.1 + 5.5).digamma());
+        test_unary(|array| (array * 0.1 + 5.5).trigamma());
+        test_unary(|array| (&array * 0.1 + 5.5).trigamma().derive().get(&array).unwrap().clone());
+        // The reflected values are tested between the poles at -3 and -2.
+        test_unary(|array| (array * 0.04 - 2.5).lgamma());
+        test_unary(|array| (array * 0.04 - 2.5).digamma());
+        test_unary(|array| (array * 0.04 - 2.5).trigamma());
+    }
+    #[test]
+    fn test_activations() {
+        let array = DArray::from(vec![-2., -0.5, 0., 1.5]);
+        assert_eq!(array.relu().data(), &[0., 0., 0., 1.5]);
+        assert_eq!(array.leaky_relu(0.1).data(), &[-0.2, -0.05, 0., 1.5]);
+        assert_close(array.elu(2.).data()[0], 2. * ((-2_f64).exp() - 1.));
+        assert_eq!(array.elu(2.).data()[3], 1.5);
+        assert_close(array.gelu().data()[1], -0.5 * 0.3085375387259869);
+        assert_close(array.gelu().data()[3], 1.5 * 0.9331927987311419);
+        assert_eq!(DArray::from(-100.).gelu().data(), &[-0.]);
+
+        let grads = array.relu().sum().derive();
+        assert_eq!(grads.get(&array).unwrap().data(), &[0., 0., 0., 1.]);
+        let grads = array.leaky_relu(0.1).sum().derive();
+        assert_eq!(grads.get(&array).unwrap().data(), &[0.1, 0.1, 0.1, 1.]);
+        let grads = array.elu(2.).sum().derive();
+        assert_eq!(grads.get(&array).unwrap().data(), &[2. * (-2_f64).exp(), 2. * (-0.5_f64).exp(), 2., 1.]);
+
+        test_unary(|array| array.leaky_relu(0.1));
+        test_unary(|array| (array * 0.1).elu(2.));
+        test_unary(|array| (array * 0.1).gelu());
+        // Tests the second derivatives.
+        test_unary(|array| array.leaky_relu(0.1).derive().get(&array).unwrap().clone());
+        test_unary(|array| (&array * 0.1).elu(2.).derive().get(&array).unwrap().clone());
+        test_unary(|array| (&array * 0.1).gelu().derive().get(&array).unwrap().clone());
+        test_unary(|array| {
+            let grad = (&array * 0.1).gelu().derive().get(&array).unwrap().clone();
+            grad.derive().get(&array).unwrap().clone()
+        });
+    }
+    #[test]
+    fn test_rounding() {
+        let array = DArray::from(vec![-1.5, -0.2, 0.5, 2.7]);
+        assert_eq!(array.floor().data(), &[-2., -1., 0., 2.]);
+        assert_eq!(array.ceil().data(), &[-1., -0., 1., 3.]);
+        assert_eq!(array.round().data(), &[-2., -0., 1., 3.]);
+        assert_eq!(array.trunc().data(), &[-1., -0., 0., 2.]);
+
+        for rounding in [Rounding::Floor, Rounding::Ceil, Rounding::Round, Rounding::Trunc] {
+            let res = array.round_with(rounding, false).sum();
+            assert_eq!(res.derive().get(&array).unwrap().data(), &[0.; 4]);
+            let res = array.round_with(rounding, true).sum();
+            assert_eq!(res.derive().get(&array).unwrap().data(), &[1.; 4]);
+        }
+    }
+    #[test]
+    fn test_rem_scalar() {
+        assert_eq!(DArray::from(vec![7., -7., 7.5]).rem_scalar(3.).data(), &[1., -1., 1.5]);
+        test_unary(|array| array.rem_scalar(7.));
+    }
+    #[test]
+    fn test_signed_pow() {
+        assert_eq!(DArray::from(vec![-4., 9.]).signed_pow(0.5).data(), &[-2., 3.]);
+        for power in [0.5, 1.5, 2., 2.5] {
+            test_unary(|array| array.signed_pow(power));
+        }
+        // Tests the second derivative, for the powers where it is continuous.
+        for power in [2., 2.5, 3.] {
+            test_unary(|array| array.signed_pow(power).derive().get(&array).unwrap().clone());
+        }
+    }
+    #[test]
+    fn test_rsqrt() {
+        assert_eq!(DArray::from(vec![4., 0.25]).rsqrt().data(), &[0.5, 2.]);
+        test_unary(|array| if array.data()[0] > 0. {array.rsqrt()} else {array});
+        // Tests the second derivative.
+        test_unary(|array| if array.data()[0] > 0. {array.rsqrt().derive().get(&array).unwrap().clone()} else {array});
+    }
+    #[test]
     fn test_pow() {
         for i in -5..5 {
             test_unary(|array|array.powi(i));
         }
     }
     #[test]
+    fn test_recip() {
+        assert_eq!(DArray::from(vec![4., -0.5]).recip().data(), &[0.25, -2.]);
+        assert_eq!((3. / DArray::from(vec![4., -0.5])).data(), &[0.75, -6.]);
+        test_unary(|array|array.recip());
+        test_unary(|array|3. / array);
+        // Tests the second derivative.
+        test_unary(|array|array.recip().derive().get(&array).unwrap().clone());
+    }
+    #[test]
+    fn test_powf() {
+        assert_eq!(DArray::from(vec![4., 0.25]).powf(1.5).data(), &[8., 0.125]);
+        assert!(DArray::from(-2.).powf(0.3).data()[0].is_nan());
+        for power in [-1.5, 0.3, 2., 3.7] {
+            test_unary(|array| if array.data()[0] > 0. {array.powf(power)} else {array});
+            // Tests the second derivative.
+            test_unary(|array| if array.data()[0] > 0. {array.powf(power).derive().get(&array).unwrap().clone()} else {array});
+        }
+    }
+    #[test]
+    fn test_map_fn() {
+        let array = DArray::from(vec![1., 3.]);
+        let res = array.map_fn(|x| x * x.sin(), |x| x.sin() + x * x.cos());
+        assert_eq!(res.data(), &[1_f64.sin(), 3. * 3_f64.sin()]);
+        assert_eq!(res.sum().derive().get(&array).unwrap().data(), &[1_f64.sin() + 1_f64.cos(), 3_f64.sin() + 3. * 3_f64.cos()]);
+        test_unary(|array| array.map_fn(|x| x * x.sin(), |x| x.sin() + x * x.cos()));
+    }
+    #[test]
+    #[should_panic(expected = "The second derivative of a function given by closures is not defined!")]
+    fn test_map_fn_second_derivative() {
+        let array = DArray::from(vec![1., 3.]);
+        let grad = array.map_fn(|x| x * x, |x| 2. * x).sum().derive().get(&array).unwrap().clone();
+        let second = grad.sum().derive().get(&array).unwrap().clone();
+        second.data();
+    }
+    /// Tests that double backward passes which only need the first derivative don't evaluate the second.
+    #[test]
+    fn test_map_fn_jvp() {
+        let array = DArray::from(vec![1., 3.]);
+        let res = array.map_fn(|x| x * x.sin(), |x| x.sin() + x * x.cos());
+        let jvp = res.jvp(std::slice::from_ref(&array), &[DArray::from(vec![2., -1.])]);
+        assert_eq!(jvp.data(), &[2. * (1_f64.sin() + 1_f64.cos()), -(3_f64.sin() + 3. * 3_f64.cos())]);
+        let jacobian = res.jacobian(&[array], DiffMode::DoubleBackward);
+        assert_eq!(jacobian[0].data(), &[1_f64.sin() + 1_f64.cos(), 0., 0., 3_f64.sin() + 3. * 3_f64.cos()]);
+    }
+    #[test]
+    fn test_polyval() {
+        let array = DArray::from(vec![-1., 0., 2.]);
+        assert_eq!(array.polyval(&[1., 0., 3.]).data(), &[4., 1., 13.]);
+        assert_eq!(array.polyval(&[]).data(), &[0., 0., 0.]);
+        let grads = array.polyval(&[1., 2., 0., -1.]).sum().derive();
+        assert_eq!(grads.get(&array).unwrap().data(), &[-1., 2., -10.]);
+
+        test_unary(|array| (array * 0.1).polyval(&[0.5, -1., 2., 0.3, -0.1]));
+        // Tests the second derivative.
+        test_unary(|array| (&array * 0.1).polyval(&[0.5, -1., 2., 0.3, -0.1]).derive().get(&array).unwrap().clone());
+    }
+    #[test]
+    fn test_threshold() {
+        let array = DArray::from(vec![-1., 0.5, 2.]);
+        let threshold = DArray::from(1.);
+        assert_eq!(array.max_of(&threshold).data(), &[1., 1., 2.]);
+        assert_eq!(array.min_of(&threshold).data(), &[-1., 0.5, 1.]);
+
+        let grads = (array.max_of(&threshold) * DArray::from(vec![1., 2., 3.])).sum().derive();
+        assert_eq!(grads.get(&array).unwrap().data(), &[0., 0., 3.]);
+        assert_eq!(grads.get(&threshold).unwrap().data(), &[3.]);
+        let grads = (array.min_of(&threshold) * DArray::from(vec![1., 2., 3.])).sum().derive();
+        assert_eq!(grads.get(&array).unwrap().data(), &[1., 2., 0.]);
+        assert_eq!(grads.get(&threshold).unwrap().data(), &[3.]);
+
+        let mut rng = StdRng::from_seed(SEED);
+        let v = random_vec(&mut rng, 20);
+        assert_gradients(|arrays| arrays[0].max_of(&arrays[1]).powi(2).sum(), &[&v, &vec![0.3]]);
+        assert_gradients(|arrays| arrays[0].min_of(&arrays[1]).powi(2).sum(), &[&v, &vec![0.3]]);
+    }
+    #[test]
+    #[should_panic(expected = "The threshold has length 2 instead of 1.")]
+    fn test_threshold_fail() {
+        DArray::from(vec![1., 2.]).max_of(&DArray::from(vec![1., 2.]));
+    }
+    #[test]
     fn test_neg() {
         test_unary(|array| (&array).neg());
     }